@@ -3,10 +3,23 @@ use async_std::sync::RwLock;
 use serde::{Deserialize, Serialize};
 
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
-use crate::platform::time::{SystemTime, UNIX_EPOCH};
+use crate::platform::time::{Instant, SystemTime, UNIX_EPOCH};
 use crate::platform::{file_exists, read_file, write_file};
 
+const COMPRESSED_MAGIC: &[u8] = b"EMZ1";
+// Bump when cached ESI types change shape so stale entries are discarded
+const CACHE_VERSION: u32 = 1;
+const UNVERSIONED_CACHE_VERSION: u32 = 1;
+const COMPRESSION_LEVEL: u8 = 6;
+const STATIC_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+const DYNAMIC_SAVE_INTERVAL: Duration = Duration::from_secs(15);
+const IMAGE_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+const DYNAMIC_MAX_BYTES: usize = 64 * 1024 * 1024;
+const IMAGE_MAX_BYTES: usize = 32 * 1024 * 1024;
+
 trait Expiry {
     fn is_expired(expires: u64) -> bool;
 }
@@ -50,15 +63,27 @@ struct Store<T: Expiry> {
     path: PathBuf,
     entries: RwLock<HashMap<String, Entry>>,
     dirty: RwLock<bool>,
+    save_interval: Duration,
+    last_save: RwLock<Instant>,
+    max_bytes: Option<usize>,
+    clock: AtomicU64,
     expiry: std::marker::PhantomData<T>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize)]
 struct Entry {
     expires: u64,
     etag: Option<String>,
     #[serde(with = "serde_bytes")]
     data: Vec<u8>,
+    #[serde(default)]
+    accessed: AtomicU64,
+}
+
+impl Entry {
+    fn size(&self, key: &str) -> usize {
+        key.len() + self.data.len() + self.etag.as_ref().map(String::len).unwrap_or(0)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -80,6 +105,7 @@ pub enum Error {
     Io(std::io::Error),
     Deserialize(flexbuffers::DeserializationError),
     Serialize(flexbuffers::SerializationError),
+    Decompress,
 }
 
 impl Cache {
@@ -92,9 +118,11 @@ impl Cache {
         let dynamic_path = dynamic_store.as_ref();
         let image_path = image_store.as_ref();
 
-        let static_store = Store::load(static_path).await?;
-        let dynamic_store = Store::load(dynamic_path).await?;
-        let image_store = Store::load(image_path).await?;
+        let static_store = Store::load(static_path, None, STATIC_SAVE_INTERVAL).await?;
+        let dynamic_store =
+            Store::load(dynamic_path, Some(DYNAMIC_MAX_BYTES), DYNAMIC_SAVE_INTERVAL).await?;
+        let image_store =
+            Store::load(image_path, Some(IMAGE_MAX_BYTES), IMAGE_SAVE_INTERVAL).await?;
 
         Ok(Cache {
             static_store,
@@ -136,6 +164,12 @@ impl Cache {
         }
     }
 
+    pub async fn clear(&self) {
+        self.static_store.clear().await;
+        self.dynamic_store.clear().await;
+        self.image_store.clear().await;
+    }
+
     pub async fn save(&self) -> Result<(), Error> {
         self.static_store.save().await?;
         self.dynamic_store.save().await?;
@@ -143,24 +177,53 @@ impl Cache {
 
         Ok(())
     }
+
+    pub async fn save_due(&self) -> Result<(), Error> {
+        if self.static_store.save_due().await {
+            self.static_store.save().await?;
+        }
+        if self.dynamic_store.save_due().await {
+            self.dynamic_store.save().await?;
+        }
+        if self.image_store.save_due().await {
+            self.image_store.save().await?;
+        }
+
+        Ok(())
+    }
 }
 
 impl<E: Expiry> Store<E> {
-    async fn load<P: AsRef<Path>>(path: P) -> Result<Store<E>, Error> {
+    async fn load<P: AsRef<Path>>(
+        path: P,
+        max_bytes: Option<usize>,
+        save_interval: Duration,
+    ) -> Result<Store<E>, Error> {
         let path = path.as_ref();
-        let entries = if file_exists(path) {
+        let entries: HashMap<String, Entry> = if file_exists(path) {
             let bytes = read_file(&path).await.map_err(Error::Io)?;
-            flexbuffers::from_slice(&bytes).map_err(Error::Deserialize)?
+            Self::decode(&bytes, CACHE_VERSION)?
         } else {
             HashMap::new()
         };
 
         log::info!("loaded cache {}, {} entries", path.display(), entries.len());
 
+        let clock = entries
+            .values()
+            .map(|e| e.accessed.load(Ordering::Relaxed))
+            .max()
+            .unwrap_or(0)
+            + 1;
+
         Ok(Store {
             path: path.to_owned(),
             entries: RwLock::new(entries),
             dirty: RwLock::new(false),
+            save_interval,
+            last_save: RwLock::new(Instant::now()),
+            max_bytes,
+            clock: AtomicU64::new(clock),
             expiry: Default::default(),
         })
     }
@@ -169,21 +232,19 @@ impl<E: Expiry> Store<E> {
         &self,
         key: K,
     ) -> Result<T, CacheError<T>> {
-        let key = key.as_ref();
         let map = self.entries.read().await;
-        if let Some(entry) = map.get(key) {
-            let data = flexbuffers::from_slice(&entry.data);
-            if let Ok(data) = data {
-                if E::is_expired(entry.expires) {
-                    Err(CacheError::Expired(entry.etag.clone(), data))
-                } else {
-                    Ok(data)
-                }
-            } else {
-                Err(CacheError::NonExistant)
+        let entry = map.get(key.as_ref()).ok_or(CacheError::NonExistant)?;
+        entry.accessed.store(
+            self.clock.fetch_add(1, Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+
+        match flexbuffers::from_slice(&entry.data) {
+            Ok(data) if E::is_expired(entry.expires) => {
+                Err(CacheError::Expired(entry.etag.clone(), data))
             }
-        } else {
-            Err(CacheError::NonExistant)
+            Ok(data) => Ok(data),
+            Err(_) => Err(CacheError::NonExistant),
         }
     }
 
@@ -201,21 +262,290 @@ impl<E: Expiry> Store<E> {
             expires,
             data,
             etag,
+            accessed: AtomicU64::new(self.clock.fetch_add(1, Ordering::Relaxed)),
         };
         map.insert(key.clone(), entry);
+
+        if let Some(max_bytes) = self.max_bytes {
+            Self::evict(&mut map, max_bytes, &key);
+        }
+
         *self.dirty.write().await = true;
         Ok(())
     }
 
+    fn evict(map: &mut HashMap<String, Entry>, max_bytes: usize, keep: &str) {
+        let mut size: usize = map.iter().map(|(k, e)| e.size(k)).sum();
+        if size <= max_bytes {
+            return;
+        }
+
+        let mut entries: Vec<_> = map
+            .iter()
+            .filter(|(k, _)| k.as_str() != keep)
+            .map(|(k, e)| (e.accessed.load(Ordering::Relaxed), k.clone(), e.size(k)))
+            .collect();
+        entries.sort();
+
+        let mut evicted = 0;
+        for (_, key, entry_size) in entries {
+            if size <= max_bytes {
+                break;
+            }
+            map.remove(&key);
+            size -= entry_size;
+            evicted += 1;
+        }
+
+        log::info!("evicted {} cache entries", evicted);
+    }
+
+    async fn save_due(&self) -> bool {
+        *self.dirty.read().await && self.last_save.read().await.elapsed() >= self.save_interval
+    }
+
     async fn save(&self) -> Result<(), Error> {
         if *self.dirty.read().await {
             log::info!("saving cache to {}", self.path.display());
             *self.dirty.write().await = false;
-            let entries = self.entries.read().await;
-            let data = flexbuffers::to_vec(&*entries).map_err(Error::Serialize)?;
-            write_file(&self.path, data).await.map_err(Error::Io)?;
+            *self.last_save.write().await = Instant::now();
+            let data = {
+                let entries = self.entries.read().await;
+                Self::encode(&entries, CACHE_VERSION)?
+            };
+            if let Err(error) = write_file(&self.path, data).await {
+                *self.dirty.write().await = true;
+                return Err(Error::Io(error));
+            }
         }
 
         Ok(())
     }
+
+    async fn clear(&self) {
+        log::info!("clearing cache {}", self.path.display());
+        self.entries.write().await.clear();
+        *self.dirty.write().await = true;
+    }
+
+    fn encode(entries: &HashMap<String, Entry>, version: u32) -> Result<Vec<u8>, Error> {
+        let data = flexbuffers::to_vec(entries).map_err(Error::Serialize)?;
+        let compressed = miniz_oxide::deflate::compress_to_vec(&data, COMPRESSION_LEVEL);
+
+        let mut bytes = Vec::with_capacity(COMPRESSED_MAGIC.len() + 4 + compressed.len());
+        bytes.extend_from_slice(COMPRESSED_MAGIC);
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(&compressed);
+        Ok(bytes)
+    }
+
+    fn decode(bytes: &[u8], version: u32) -> Result<HashMap<String, Entry>, Error> {
+        let header_len = COMPRESSED_MAGIC.len() + 4;
+        let (file_version, data) = if bytes.starts_with(COMPRESSED_MAGIC) {
+            if bytes.len() < header_len {
+                return Err(Error::Decompress);
+            }
+            let mut file_version = [0; 4];
+            file_version.copy_from_slice(&bytes[COMPRESSED_MAGIC.len()..header_len]);
+            let data = miniz_oxide::inflate::decompress_to_vec(&bytes[header_len..])
+                .map_err(|_| Error::Decompress);
+            (u32::from_le_bytes(file_version), data)
+        } else {
+            (UNVERSIONED_CACHE_VERSION, Ok(bytes.to_vec()))
+        };
+
+        if file_version != version {
+            log::warn!(
+                "cache version {} does not match {}, starting fresh",
+                file_version,
+                version
+            );
+            return Ok(HashMap::new());
+        }
+
+        flexbuffers::from_slice(&data?).map_err(Error::Deserialize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use async_std::task::block_on;
+
+    fn test_store(max_bytes: usize) -> Store<NeverExpires> {
+        block_on(Store::load(
+            "test-missing-cache.dat",
+            Some(max_bytes),
+            Duration::from_secs(0),
+        ))
+        .unwrap()
+    }
+
+    fn contains(store: &Store<NeverExpires>, key: &str) -> bool {
+        block_on(store.get::<Vec<u8>, _>(key)).is_ok()
+    }
+
+    #[test]
+    fn evicts_least_recently_used_entries() {
+        let value = vec![7u8; 100];
+        let entry_size = flexbuffers::to_vec(&value).unwrap().len() + 1;
+        let store = test_store(entry_size * 2);
+
+        block_on(store.store("a", &value, None, 0)).unwrap();
+        block_on(store.store("b", &value, None, 0)).unwrap();
+        block_on(store.store("c", &value, None, 0)).unwrap();
+        assert!(!contains(&store, "a"));
+        assert!(contains(&store, "b"));
+        assert!(contains(&store, "c"));
+
+        assert!(contains(&store, "b"));
+        block_on(store.store("d", &value, None, 0)).unwrap();
+        assert!(contains(&store, "b"));
+        assert!(!contains(&store, "c"));
+        assert!(contains(&store, "d"));
+    }
+
+    #[test]
+    fn compressed_cache_round_trips() {
+        let path =
+            std::env::temp_dir().join(format!("eve-mapper-cache-{}.dat", std::process::id()));
+        let store: Store<NeverExpires> =
+            block_on(Store::load(&path, None, Duration::from_secs(0))).unwrap();
+        block_on(store.store("a", vec![1u8; 1000], Some("etag".to_string()), 5)).unwrap();
+        block_on(store.store("b", "value", None, 10)).unwrap();
+        block_on(store.save()).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(bytes.starts_with(COMPRESSED_MAGIC));
+        assert!(bytes.len() < 1000);
+
+        let loaded: Store<NeverExpires> =
+            block_on(Store::load(&path, None, Duration::from_secs(0))).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(
+            block_on(loaded.get::<Vec<u8>, _>("a")).unwrap(),
+            vec![1u8; 1000]
+        );
+        assert_eq!(block_on(loaded.get::<String, _>("b")).unwrap(), "value");
+        let entries = block_on(loaded.entries.read());
+        assert_eq!(entries["a"].etag.as_deref(), Some("etag"));
+        assert_eq!(entries["b"].expires, 10);
+    }
+
+    #[test]
+    fn dirty_stores_wait_for_save_interval() {
+        let path = |name: &str| {
+            std::env::temp_dir().join(format!("eve-mapper-{}-{}.dat", name, std::process::id()))
+        };
+        let static_path = path("save-due");
+        let _ = std::fs::remove_file(&static_path);
+        let interval = Duration::from_millis(100);
+        let cache = Cache {
+            static_store: block_on(Store::load(&static_path, None, interval)).unwrap(),
+            dynamic_store: block_on(Store::load(path("save-due-dynamic"), None, interval)).unwrap(),
+            image_store: block_on(Store::load(path("save-due-image"), None, interval)).unwrap(),
+        };
+
+        block_on(cache.static_store.store("a", "value", None, 0)).unwrap();
+        block_on(cache.save_due()).unwrap();
+        assert!(!static_path.exists());
+
+        std::thread::sleep(Duration::from_millis(150));
+        block_on(cache.save_due()).unwrap();
+        assert!(static_path.exists());
+        assert!(!*block_on(cache.static_store.dirty.read()));
+        let _ = std::fs::remove_file(&static_path);
+    }
+
+    #[derive(Serialize)]
+    struct OldEntry {
+        expires: u64,
+        etag: Option<String>,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    }
+
+    #[test]
+    fn loads_uncompressed_cache() {
+        let mut entries = HashMap::new();
+        entries.insert(
+            "a".to_string(),
+            OldEntry {
+                expires: 1,
+                etag: None,
+                data: flexbuffers::to_vec("value").unwrap(),
+            },
+        );
+        let bytes = flexbuffers::to_vec(&entries).unwrap();
+
+        let decoded = Store::<NeverExpires>::decode(&bytes, UNVERSIONED_CACHE_VERSION).unwrap();
+        assert_eq!(decoded["a"].expires, 1);
+        assert_eq!(
+            flexbuffers::from_slice::<String>(&decoded["a"].data).unwrap(),
+            "value"
+        );
+    }
+
+    #[test]
+    fn version_mismatch_starts_fresh() {
+        let mut entries = HashMap::new();
+        entries.insert(
+            "a".to_string(),
+            Entry {
+                expires: 1,
+                etag: None,
+                data: flexbuffers::to_vec("value").unwrap(),
+                accessed: AtomicU64::new(0),
+            },
+        );
+
+        let bytes = Store::<NeverExpires>::encode(&entries, 7).unwrap();
+        assert_eq!(Store::<NeverExpires>::decode(&bytes, 7).unwrap().len(), 1);
+        assert!(Store::<NeverExpires>::decode(&bytes, 8).unwrap().is_empty());
+    }
+
+    #[test]
+    fn unchanged_stores_are_not_rewritten() {
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let static_path = dir.join(format!("eve-mapper-static-{}.dat", id));
+        let dynamic_path = dir.join(format!("eve-mapper-dynamic-{}.dat", id));
+        let image_path = dir.join(format!("eve-mapper-image-{}.dat", id));
+
+        let cache = block_on(Cache::new(&static_path, &dynamic_path, &image_path)).unwrap();
+        let expires = SystemTime::now() + Duration::from_secs(60);
+        block_on(cache.store("a", CacheKind::Static, "static", None, expires)).unwrap();
+        block_on(cache.save()).unwrap();
+        let static_modified = std::fs::metadata(&static_path).unwrap().modified().unwrap();
+
+        std::thread::sleep(Duration::from_millis(20));
+        block_on(cache.store("b", CacheKind::Dynamic, "dynamic", None, expires)).unwrap();
+        block_on(cache.save()).unwrap();
+
+        let static_unchanged =
+            std::fs::metadata(&static_path).unwrap().modified().unwrap() == static_modified;
+        let dynamic_written = dynamic_path.exists();
+        let image_written = image_path.exists();
+
+        for path in &[&static_path, &dynamic_path, &image_path] {
+            let _ = std::fs::remove_file(path);
+        }
+
+        assert!(static_unchanged);
+        assert!(dynamic_written);
+        assert!(!image_written);
+    }
+
+    #[test]
+    fn keeps_entries_under_cap() {
+        let store = test_store(1024 * 1024);
+        for key in &["a", "b", "c", "d"] {
+            block_on(store.store(key, vec![1u8; 100], None, 0)).unwrap();
+        }
+        for key in &["a", "b", "c", "d"] {
+            assert!(contains(&store, key));
+        }
+    }
 }
@@ -4,6 +4,9 @@ pub enum Error {
     Io(std::io::Error),
     SerdeJson(serde_json::Error),
     OauthVerify,
+    OauthAuthorize,
+    UnknownProfile(i32),
+    OfflineProfileMissing,
 }
 
 impl From<reqwest::Error> for Error {
@@ -4,14 +4,22 @@ use futures_intrusive::sync::Semaphore;
 use reqwest::{header, Method, Response, Url};
 use serde::{Deserialize, Serialize};
 
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use crate::cache::{Cache, CacheError, CacheKind};
 use crate::oauth::{self, Profile};
-use crate::platform::time::{Instant, SystemTime};
-use crate::platform::{parse_http_date, spawn, ESI_IMAGE_SERVER, USER_AGENT};
-
-pub const ALWAYS_CACHE: bool = false;
+use crate::paths::Paths;
+use crate::platform::time::{Instant, SystemTime, UNIX_EPOCH};
+use crate::platform::{create_dir_all, parse_http_date, spawn, ESI_IMAGE_SERVER, USER_AGENT};
+
+// Every failed request counts against ESI's error limit regardless of how many
+// are in flight, so raising this mostly helps fast connections finish the
+// initial galaxy load sooner. Timeouts from an oversaturated connection are
+// retried and can burn through the limit, which pauses all requests.
+pub const DEFAULT_CONCURRENCY: usize = 5;
+pub const DEFAULT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+const PAGE_FETCH_WINDOW: usize = 4;
 
 #[derive(Copy, Clone, Debug)]
 enum EsiEndpoint {
@@ -28,14 +36,166 @@ impl EsiEndpoint {
     }
 }
 
+const ERROR_LIMIT_POLL: std::time::Duration = std::time::Duration::from_millis(100);
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[derive(Clone)]
+struct ErrorLimit {
+    blocked_until: Arc<AtomicU64>,
+    clock: fn() -> u64,
+}
+
+impl Default for ErrorLimit {
+    fn default() -> Self {
+        ErrorLimit::with_clock(unix_millis)
+    }
+}
+
+impl ErrorLimit {
+    fn with_clock(clock: fn() -> u64) -> Self {
+        ErrorLimit {
+            blocked_until: Arc::new(AtomicU64::new(0)),
+            clock,
+        }
+    }
+
+    fn now(&self) -> u64 {
+        (self.clock)()
+    }
+
+    fn update(&self, headers: &header::HeaderMap, now: u64) {
+        let header_value = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+        let remain = header_value("X-Esi-Error-Limit-Remain");
+        let reset = header_value("X-Esi-Error-Limit-Reset");
+
+        if let (Some(0), Some(reset)) = (remain, reset) {
+            log::warn!("error limit reached, blocking requests for {}s", reset);
+            self.blocked_until
+                .fetch_max(now + reset * 1000, Ordering::SeqCst);
+        }
+    }
+
+    fn blocked_for(&self, now: u64) -> Option<std::time::Duration> {
+        let blocked_until = self.blocked_until.load(Ordering::SeqCst);
+        if blocked_until > now {
+            Some(std::time::Duration::from_millis(blocked_until - now))
+        } else {
+            None
+        }
+    }
+
+    async fn wait(&self) {
+        while let Some(duration) = self.blocked_for(self.now()) {
+            log::info!("waiting {}ms for error limit", duration.as_millis());
+            sleep(duration.min(ERROR_LIMIT_POLL)).await;
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn http_client(timeout: std::time::Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .unwrap_or_else(|error| {
+            log::error!("unable to build http client with timeout: {:?}", error);
+            reqwest::Client::new()
+        })
+}
+
+#[cfg(target_arch = "wasm32")]
+fn http_client(_timeout: std::time::Duration) -> reqwest::Client {
+    reqwest::Client::new()
+}
+
+fn is_retryable(error: &reqwest::Error) -> bool {
+    error.is_timeout()
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Page<T> {
+    items: Vec<T>,
+    pages: Option<i32>,
+}
+
+async fn fetch_all_pages<T, F, Fut>(fetch: F) -> Result<Vec<T>, Error>
+where
+    F: Fn(i32) -> Fut,
+    Fut: std::future::Future<Output = Result<(Vec<T>, Option<i32>), Error>>,
+{
+    use futures::stream::StreamExt;
+
+    let (mut items, pages) = fetch(1).await?;
+    let remaining: Vec<_> = futures::stream::iter(2..=pages.unwrap_or(1))
+        .map(&fetch)
+        .buffered(PAGE_FETCH_WINDOW)
+        .collect()
+        .await;
+
+    for page in remaining {
+        items.extend(page?.0);
+    }
+
+    Ok(items)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatusClass {
+    Success,
+    Retryable,
+    Fatal,
+}
+
+fn classify_status(status: reqwest::StatusCode) -> StatusClass {
+    match status.as_u16() {
+        420 | 429 => StatusClass::Retryable,
+        _ if status.is_server_error() => StatusClass::Retryable,
+        _ if status.is_client_error() => StatusClass::Fatal,
+        _ => StatusClass::Success,
+    }
+}
+
+fn cached_lookup<T>(
+    cache_kind: CacheKind,
+    lookup: Result<T, CacheError<T>>,
+    offline: bool,
+) -> Result<T, (Option<String>, Option<T>)> {
+    match (cache_kind, lookup) {
+        (CacheKind::None, _) => Err((None, None)),
+        (_, Ok(value)) => Ok(value),
+        (_, Err(CacheError::Expired(_, value))) if offline => Ok(value),
+        (_, Err(CacheError::Expired(etag, value))) => Err((etag, Some(value))),
+        (_, Err(_)) => Err((None, None)),
+    }
+}
+
+fn request_limiter(concurrency: usize) -> Semaphore {
+    Semaphore::new(true, concurrency.max(1))
+}
+
 #[derive(Clone)]
 pub struct Client {
-    endpoint: EsiEndpoint,
-    image_endpoint: EsiEndpoint,
+    endpoint: Url,
+    image_endpoint: Url,
     client: reqwest::Client,
     profile: Arc<RwLock<Profile>>,
     cache: Arc<Cache>,
     limiter: Arc<Semaphore>,
+    error_limit: ErrorLimit,
+    offline: bool,
+    paths: Paths,
+    token_url: String,
 }
 
 impl std::fmt::Debug for Client {
@@ -54,24 +214,46 @@ pub enum Error {
     ResponseDeserialize(serde_json::Error),
     CannotExecuteRequest(reqwest::Error),
     CannotRetrieveRequestBody(reqwest::Error),
-    InvalidEsiLimitHeader(String),
     RetriesExhausted,
+    Http {
+        status: reqwest::StatusCode,
+        url: reqwest::Url,
+    },
+    Offline,
 }
 
 impl Client {
-    pub async fn new(profile: Profile) -> Client {
+    pub async fn new(
+        profile: Profile,
+        paths: Paths,
+        concurrency: usize,
+        timeout: std::time::Duration,
+        offline: bool,
+    ) -> Client {
+        if let Err(error) = create_dir_all(&paths.dir).await {
+            log::error!(
+                "unable to create data directory {:?}: {:?}",
+                paths.dir,
+                error
+            );
+        }
+
         let cache = Arc::new(
-            Cache::new("eve-static.dat", "eve-dynamic.dat", "eve-images.dat")
-                .await
-                .unwrap(),
+            Cache::new(
+                paths.static_cache_path(),
+                paths.dynamic_cache_path(),
+                paths.image_cache_path(),
+            )
+            .await
+            .unwrap(),
         );
 
         let inner_cache = cache.clone();
 
         spawn(async move {
             loop {
-                sleep(std::time::Duration::from_secs(120)).await;
-                let save_res = inner_cache.save().await;
+                sleep(std::time::Duration::from_secs(5)).await;
+                let save_res = inner_cache.save_due().await;
                 match save_res {
                     Err(error) => log::error!("cache save error: {:?}", error),
                     _ => (),
@@ -79,15 +261,119 @@ impl Client {
             }
         });
         Client {
-            endpoint: EsiEndpoint::Latest,
-            image_endpoint: EsiEndpoint::Images,
-            client: reqwest::Client::new(),
+            endpoint: EsiEndpoint::Latest.as_url_base(),
+            image_endpoint: EsiEndpoint::Images.as_url_base(),
+            client: http_client(timeout),
             profile: Arc::new(RwLock::new(profile)),
             cache,
-            limiter: Arc::new(Semaphore::new(true, 5)),
+            limiter: Arc::new(request_limiter(concurrency)),
+            error_limit: ErrorLimit::default(),
+            offline,
+            paths,
+            token_url: oauth::OAUTH_TOKEN.to_string(),
+        }
+    }
+
+    pub async fn replace_profile(&self, profile: Profile) {
+        log::info!(
+            "switching to character {}",
+            profile.character.character_name
+        );
+        *self.profile.write().await = profile;
+    }
+
+    pub async fn save_cache(&self) -> Result<(), crate::cache::Error> {
+        self.cache.save().await
+    }
+
+    pub async fn clear_cache(&self) {
+        self.cache.clear().await;
+    }
+
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    #[cfg(test)]
+    pub fn mock(
+        esi: &crate::mock_http::MockServer,
+        token: &crate::mock_http::MockServer,
+        concurrency: usize,
+        timeout: std::time::Duration,
+    ) -> Client {
+        static CLIENTS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+        let profile: Profile = serde_json::from_str(
+            r#"{
+                "character": {"CharacterID": 1, "CharacterName": "Test"},
+                "token": {
+                    "access_token": "access",
+                    "expires_in": 1200,
+                    "token_type": "Bearer",
+                    "refresh_token": "refresh"
+                }
+            }"#,
+        )
+        .unwrap();
+        let dir = std::env::temp_dir().join(format!(
+            "eve-mapper-esi-{}-{}",
+            std::process::id(),
+            CLIENTS.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        let mut client = async_std::task::block_on(Client::new(
+            profile,
+            Paths::new(dir),
+            concurrency,
+            timeout,
+            false,
+        ));
+        client.endpoint = Url::parse(&esi.url).unwrap();
+        client.token_url = format!("{}v2/oauth/token/", token.url);
+        client
+    }
+
+    pub fn error_limited(&self) -> bool {
+        self.error_limit
+            .blocked_for(self.error_limit.now())
+            .is_some()
+    }
+
+    pub fn paths(&self) -> &Paths {
+        &self.paths
+    }
+
+    pub async fn load_snapshot<T: serde::de::DeserializeOwned>(&self, name: &str) -> Option<T> {
+        let key = self.snapshot_key(name).await;
+        let cached = self.cache.get(&key, CacheKind::Dynamic).await;
+        cached_lookup(CacheKind::Dynamic, cached, self.offline).ok()
+    }
+
+    pub async fn store_snapshot<T: serde::Serialize>(
+        &self,
+        name: &str,
+        value: &T,
+        lifetime: std::time::Duration,
+    ) {
+        let key = self.snapshot_key(name).await;
+        let expires = SystemTime::now() + lifetime;
+        let stored = self
+            .cache
+            .store(&key, CacheKind::Dynamic, value, None, expires)
+            .await;
+        if let Err(error) = stored {
+            log::error!("unable to store {} snapshot: {:?}", name, error);
         }
     }
 
+    async fn snapshot_key(&self, name: &str) -> String {
+        format!("snapshot/{}/{}", name, self.character_id().await)
+    }
+
+    pub async fn character_id(&self) -> i32 {
+        self.profile.read().await.character.character_id
+    }
+
     async fn get<S: AsRef<str>, T: serde::de::DeserializeOwned + serde::Serialize>(
         &self,
         path: S,
@@ -120,14 +406,38 @@ impl Client {
         .await
     }
 
+    async fn get_auth<S: AsRef<str>, T: serde::de::DeserializeOwned + serde::Serialize>(
+        &self,
+        path: S,
+    ) -> Result<T, Error> {
+        {
+            let mut profile = self.profile.write().await;
+            if !self.offline && profile.token.expired() {
+                if let Ok(new_profile) = oauth::refresh(&self.paths, profile.clone()).await {
+                    *profile = new_profile;
+                }
+            }
+        }
+        self.execute(
+            Method::GET,
+            &self.endpoint,
+            path,
+            true,
+            CacheKind::Static,
+            |bytes| serde_json::from_slice(bytes).map_err(Error::ResponseDeserialize),
+            |d, _| d,
+        )
+        .await
+    }
+
     async fn get_auth_no_cache<S: AsRef<str>, T: serde::de::DeserializeOwned + serde::Serialize>(
         &self,
         path: S,
     ) -> Result<T, Error> {
         {
             let mut profile = self.profile.write().await;
-            if profile.token.expired() {
-                if let Ok(new_profile) = oauth::refresh(profile.clone()).await {
+            if !self.offline && profile.token.expired() {
+                if let Ok(new_profile) = oauth::refresh(&self.paths, profile.clone()).await {
                     *profile = new_profile;
                 }
             }
@@ -156,8 +466,8 @@ impl Client {
     ) -> Result<TCache, Error> {
         {
             let mut profile = self.profile.write().await;
-            if profile.token.expired() {
-                if let Ok(new_profile) = oauth::refresh(profile.clone()).await {
+            if !self.offline && profile.token.expired() {
+                if let Ok(new_profile) = oauth::refresh(&self.paths, profile.clone()).await {
                     *profile = new_profile;
                 }
             }
@@ -177,8 +487,8 @@ impl Client {
     async fn post_auth<S: AsRef<str>>(&self, path: S) -> Result<(), Error> {
         {
             let mut profile = self.profile.write().await;
-            if profile.token.expired() {
-                if let Ok(new_profile) = oauth::refresh(profile.clone()).await {
+            if !self.offline && profile.token.expired() {
+                if let Ok(new_profile) = oauth::refresh(&self.paths, profile.clone()).await {
                     *profile = new_profile;
                 }
             }
@@ -219,7 +529,7 @@ impl Client {
     >(
         &self,
         method: Method,
-        endpoint: &EsiEndpoint,
+        endpoint: &Url,
         path: S,
         auth: bool,
         cache_kind: CacheKind,
@@ -228,10 +538,10 @@ impl Client {
     ) -> Result<TCache, Error> {
         let uuid = uuid::Uuid::new_v4();
         let mut retry_count: u32 = 0;
+        let mut reauthed = false;
         while retry_count < 5 {
             let path = path.as_ref();
             let url = endpoint
-                .as_url_base()
                 .join(path)
                 .map_err(|_e| Error::InvalidUrlPath(path.to_string()))?;
 
@@ -245,6 +555,7 @@ impl Client {
             }
 
             let (response, request_start, cached_value) = {
+                self.error_limit.wait().await;
                 let _permit = self.limiter.acquire(1).await;
 
                 if auth {
@@ -253,17 +564,16 @@ impl Client {
                 }
 
                 log::debug!("looking up url in cache: {}", &url);
-                let (etag, cached_value) =
-                    match (cache_kind, self.cache.get(&path_hash, cache_kind).await) {
-                        (CacheKind::None, _) => (None, None),
-                        (_, Ok(value)) => return Ok(value),
-                        (_, Err(CacheError::Expired(_, value))) if ALWAYS_CACHE => {
-                            log::info!("returning expired data: {}", &url);
-                            return Ok(value);
-                        }
-                        (_, Err(CacheError::Expired(etag, value))) => (etag, Some(value)),
-                        (_, Err(_)) => (None, None),
-                    };
+                let cached = self.cache.get(&path_hash, cache_kind).await;
+                let (etag, cached_value) = match cached_lookup(cache_kind, cached, self.offline) {
+                    Ok(value) => return Ok(value),
+                    Err(stale) => stale,
+                };
+
+                if self.offline {
+                    log::warn!("no cached data available offline: {}", &url);
+                    return Err(Error::Offline);
+                }
 
                 if let Some(etag) = etag {
                     request = request.header(header::IF_NONE_MATCH, etag)
@@ -271,10 +581,25 @@ impl Client {
 
                 log::info!("request {}: {}", uuid, url);
                 let start = Instant::now();
-                let response = request.send().await.map_err(Error::CannotExecuteRequest)?;
+                let response = match request.send().await {
+                    Ok(response) => response,
+                    Err(error) if is_retryable(&error) => {
+                        retry_count += 1;
+                        log::error!(
+                            "request timed out {} retrying attempt {}",
+                            uuid,
+                            retry_count
+                        );
+                        continue;
+                    }
+                    Err(error) => return Err(Error::CannotExecuteRequest(error)),
+                };
                 (response, start, cached_value)
             };
 
+            self.error_limit
+                .update(response.headers(), self.error_limit.now());
+
             let status_code = response.status().as_u16();
             log::info!(
                 "response {}: {} after {}ms",
@@ -291,16 +616,29 @@ impl Client {
                 log::warn!("warning in header {}: {}", uuid, warning);
             }
 
-            let reauth = auth && status_code == 401 || status_code == 403;
-            let retry = response.status().is_server_error() || response.status().is_client_error();
-            let limit = response.headers().get("X-Esi-Error-Limit-Reset");
+            let reauth = auth && status_code == 401 && !reauthed;
+            let retry = match classify_status(response.status()) {
+                StatusClass::Success => false,
+                StatusClass::Retryable => true,
+                StatusClass::Fatal if reauth => true,
+                StatusClass::Fatal => {
+                    log::error!("request failed {} with status {}", uuid, status_code);
+                    return Err(Error::Http {
+                        status: response.status(),
+                        url,
+                    });
+                }
+            };
             let expires = response.headers().get(header::EXPIRES).cloned();
 
             if reauth {
                 log::info!("refreshing authentication token {}", uuid);
+                reauthed = true;
                 let reauth_start = Instant::now();
                 let mut profile = self.profile.write().await;
-                if let Ok(new_profile) = oauth::refresh(profile.clone()).await {
+                let refreshed =
+                    oauth::refresh_with(&self.paths, &self.token_url, profile.clone()).await;
+                if let Ok(new_profile) = refreshed {
                     *profile = new_profile;
                     log::info!(
                         "refreshed authentication token {} after {}ms",
@@ -310,17 +648,6 @@ impl Client {
                 }
             }
 
-            if let (Some(limit), true) = (limit, retry) {
-                let dur = limit
-                    .to_str()
-                    .map_err(|_| Error::InvalidEsiLimitHeader(format!("{:?}", limit.as_bytes())))?
-                    .parse::<u64>()
-                    .map_err(|_| Error::InvalidEsiLimitHeader(format!("{:?}", limit.as_bytes())))?
-                    * 1000;
-                log::warn!("error limit header found {} delaying for {}ms", uuid, dur);
-                sleep(std::time::Duration::from_millis(dur)).await;
-            }
-
             if !retry {
                 let parsed_expires = expires
                     .as_ref()
@@ -338,10 +665,19 @@ impl Client {
                 ) {
                     value
                 } else {
-                    let bytes = response
-                        .bytes()
-                        .await
-                        .map_err(Error::CannotRetrieveRequestBody)?;
+                    let bytes = match response.bytes().await {
+                        Ok(bytes) => bytes,
+                        Err(error) if is_retryable(&error) => {
+                            retry_count += 1;
+                            log::error!(
+                                "response body timed out {} retrying attempt {}",
+                                uuid,
+                                retry_count
+                            );
+                            continue;
+                        }
+                        Err(error) => return Err(Error::CannotRetrieveRequestBody(error)),
+                    };
                     let value = map_value(&bytes)?;
                     map_headers(value, &headers)
                 };
@@ -436,6 +772,16 @@ impl Client {
         self.get_auth_no_cache(&url).await
     }
 
+    pub async fn get_station(&self, station_id: i64) -> Result<GetUniverseStation, Error> {
+        let url = format!("universe/stations/{}/", station_id);
+        self.get(&url).await
+    }
+
+    pub async fn get_structure(&self, structure_id: i64) -> Result<GetUniverseStructure, Error> {
+        let url = format!("universe/structures/{}/", structure_id);
+        self.get_auth(&url).await
+    }
+
     pub async fn get_character_self(&self) -> Result<GetCharacter, Error> {
         let character = self.profile.read().await.character.character_id;
         let url = format!("characters/{}/", character);
@@ -460,52 +806,47 @@ impl Client {
         res
     }
 
-    pub async fn get_alliance_contacts(
+    pub async fn get_all_pages<T: serde::de::DeserializeOwned + serde::Serialize>(
         &self,
-        alliance_id: i32,
-        page: i32,
-    ) -> Result<GetAllianceContacts, Error> {
-        let url = format!("alliances/{}/contacts/?page={}", alliance_id, page);
-        self.get_auth_no_cache_with_headers(&url, |contacts: Vec<GetAllianceContact>, headers| {
-            let pages = headers
-                .get("x-pages")
-                .and_then(|n| n.to_str().ok())
-                .and_then(|n| n.parse().ok());
-            GetAllianceContacts { contacts, pages }
+        base_path: &str,
+    ) -> Result<Vec<T>, Error> {
+        fetch_all_pages(|page| {
+            let path = format!("{}?page={}", base_path, page);
+            async move {
+                self.get_auth_no_cache_with_headers(&path, |items: Vec<T>, headers| {
+                    let pages = headers
+                        .get("x-pages")
+                        .and_then(|n| n.to_str().ok())
+                        .and_then(|n| n.parse().ok());
+                    Page { items, pages }
+                })
+                .await
+                .map(|page| (page.items, page.pages))
+            }
         })
         .await
     }
 
+    pub async fn get_alliance_contacts(
+        &self,
+        alliance_id: i32,
+    ) -> Result<Vec<GetAllianceContact>, Error> {
+        self.get_all_pages(&format!("alliances/{}/contacts/", alliance_id))
+            .await
+    }
+
     pub async fn get_corporation_contacts(
         &self,
         corporation_id: i32,
-        page: i32,
-    ) -> Result<GetCorporationContacts, Error> {
-        let url = format!("corporations/{}/contacts/?page={}", corporation_id, page);
-        self.get_auth_no_cache_with_headers(
-            &url,
-            |contacts: Vec<GetCorporationContact>, headers| {
-                let pages = headers
-                    .get("x-pages")
-                    .and_then(|n| n.to_str().ok())
-                    .and_then(|n| n.parse().ok());
-                GetCorporationContacts { contacts, pages }
-            },
-        )
-        .await
+    ) -> Result<Vec<GetCorporationContact>, Error> {
+        self.get_all_pages(&format!("corporations/{}/contacts/", corporation_id))
+            .await
     }
 
-    pub async fn get_character_contacts(&self, page: i32) -> Result<GetCharacterContacts, Error> {
+    pub async fn get_character_contacts(&self) -> Result<Vec<GetCharacterContact>, Error> {
         let character = self.profile.read().await.character.character_id;
-        let url = format!("characters/{}/contacts/?page={}", character, page);
-        self.get_auth_no_cache_with_headers(&url, |contacts: Vec<GetCharacterContact>, headers| {
-            let pages = headers
-                .get("x-pages")
-                .and_then(|n| n.to_str().ok())
-                .and_then(|n| n.parse().ok());
-            GetCharacterContacts { contacts, pages }
-        })
-        .await
+        self.get_all_pages(&format!("characters/{}/contacts/", character))
+            .await
     }
 
     pub async fn get_sovereignty_map(&self) -> Result<Vec<GetSovereigntyMap>, Error> {
@@ -513,6 +854,14 @@ impl Client {
         self.get_no_cache(&url).await
     }
 
+    pub async fn get_incursions(&self) -> Result<Vec<GetIncursion>, Error> {
+        self.get_no_cache("incursions/").await
+    }
+
+    pub async fn get_fw_systems(&self) -> Result<Vec<GetFactionWarfareSystem>, Error> {
+        self.get_no_cache("fw/systems/").await
+    }
+
     pub async fn get_alliance_logo(&self, alliance_id: i32, size: u32) -> Result<Vec<u8>, Error> {
         let url = format!("alliances/{}/logo?size={}", alliance_id, size);
         self.get_image(&url).await
@@ -619,6 +968,25 @@ pub struct GetUniverseConstellation {
     pub systems: Option<Vec<i32>>,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GetIncursion {
+    pub constellation_id: i32,
+    pub staging_solar_system_id: i32,
+    pub infested_solar_systems: Vec<i32>,
+    pub has_boss: bool,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GetFactionWarfareSystem {
+    pub solar_system_id: i32,
+    pub owner_faction_id: i32,
+    pub occupier_faction_id: i32,
+    pub contested: String,
+    pub victory_points: i32,
+    pub victory_points_threshold: i32,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct GetUniverseSystemKills {
     pub npc_kills: i32,
@@ -641,28 +1009,32 @@ pub struct GetCharacterLocation {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct GetAllianceContact {
-    pub contact_id: i32,
-    pub contact_type: String,
-    pub standing: f64,
+pub struct GetUniverseStation {
+    pub station_id: i64,
+    pub name: String,
+    pub system_id: i32,
+    pub type_id: i32,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct GetAllianceContacts {
-    pub contacts: Vec<GetAllianceContact>,
-    pub pages: Option<i32>,
+pub struct GetUniverseStructure {
+    pub name: String,
+    pub solar_system_id: i32,
+    pub owner_id: i32,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct GetCorporationContact {
+pub struct GetAllianceContact {
     pub contact_id: i32,
     pub contact_type: String,
     pub standing: f64,
 }
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct GetCorporationContacts {
-    pub contacts: Vec<GetCorporationContact>,
-    pub pages: Option<i32>,
+pub struct GetCorporationContact {
+    pub contact_id: i32,
+    pub contact_type: String,
+    pub standing: f64,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -674,12 +1046,6 @@ pub struct GetCharacterContact {
     pub standing: f64,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct GetCharacterContacts {
-    pub contacts: Vec<GetCharacterContact>,
-    pub pages: Option<i32>,
-}
-
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct GetCharacter {
     pub alliance_id: Option<i32>,
@@ -726,3 +1092,251 @@ pub struct GetCharacterOnline {
     pub logins: Option<i32>,
     pub online: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_http::{mock_server, Reply};
+
+    #[test]
+    fn deserializes_incursions() {
+        let incursions: Vec<GetIncursion> = serde_json::from_str(
+            r#"[{
+                "constellation_id": 20000607,
+                "faction_id": 500019,
+                "has_boss": true,
+                "infested_solar_systems": [30004148, 30004149, 30004150],
+                "influence": 0.9,
+                "staging_solar_system_id": 30004148,
+                "state": "established",
+                "type": "Incursion"
+            }]"#,
+        )
+        .unwrap();
+        assert_eq!(incursions.len(), 1);
+        assert_eq!(incursions[0].staging_solar_system_id, 30004148);
+        assert_eq!(incursions[0].infested_solar_systems.len(), 3);
+        assert!(incursions[0].has_boss);
+
+        let empty: Vec<GetIncursion> = serde_json::from_str("[]").unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn classifies_response_statuses() {
+        use reqwest::StatusCode;
+
+        let class = |status: u16| classify_status(StatusCode::from_u16(status).unwrap());
+        assert_eq!(class(200), StatusClass::Success);
+        assert_eq!(class(304), StatusClass::Success);
+        for status in [400, 401, 403, 404] {
+            assert_eq!(class(status), StatusClass::Fatal);
+        }
+        for status in [420, 429, 500, 502, 503, 504] {
+            assert_eq!(class(status), StatusClass::Retryable);
+        }
+    }
+
+    fn status(status: u16) -> Reply {
+        match status {
+            200 => Reply::Respond(200, "", "[1,2,3]"),
+            status => Reply::Respond(status, "", "{}"),
+        }
+    }
+
+    fn run<T: Send + 'static>(request: impl std::future::Future<Output = T> + Send + 'static) -> T {
+        async_std::task::block_on(async_std::task::spawn(request))
+    }
+
+    #[test]
+    fn fatal_statuses_are_not_retried() {
+        let token = mock_server(Vec::new());
+
+        let esi = mock_server(vec![status(404)]);
+        let client = Client::mock(&esi, &token, 1, DEFAULT_TIMEOUT);
+        let result = run(async move { client.get_universe_systems().await });
+        assert!(matches!(result, Err(Error::Http { status, .. }) if status.as_u16() == 404));
+        assert_eq!(esi.requests(), 1);
+
+        let esi = mock_server(vec![status(403)]);
+        let client = Client::mock(&esi, &token, 1, DEFAULT_TIMEOUT);
+        let result = run(async move { client.get_character_location().await });
+        assert!(matches!(result, Err(Error::Http { status, .. }) if status.as_u16() == 403));
+        assert_eq!(esi.requests(), 1);
+        assert_eq!(token.requests(), 0);
+    }
+
+    #[test]
+    fn retryable_statuses_are_retried() {
+        let token = mock_server(Vec::new());
+
+        for code in [420, 429, 500, 503] {
+            let esi = mock_server(vec![status(code), status(200)]);
+            let client = Client::mock(&esi, &token, 1, DEFAULT_TIMEOUT);
+            let result = run(async move { client.get_universe_systems().await });
+            assert_eq!(result.unwrap(), vec![1, 2, 3]);
+            assert_eq!(esi.requests(), 2);
+        }
+    }
+
+    #[test]
+    fn unauthorized_requests_reauth_once() {
+        let token = mock_server(vec![Reply::Respond(
+            200,
+            "Content-Type: application/json\r\n",
+            r#"{"access_token":"new","expires_in":1200,"token_type":"Bearer","refresh_token":"refresh"}"#,
+        )]);
+        let esi = mock_server(vec![status(401), status(401), status(401)]);
+        let client = Client::mock(&esi, &token, 1, DEFAULT_TIMEOUT);
+
+        let result = run(async move { client.get_character_location().await });
+        assert!(matches!(result, Err(Error::Http { status, .. }) if status.as_u16() == 401));
+        assert_eq!(esi.requests(), 2);
+        assert_eq!(token.requests(), 1);
+    }
+
+    #[test]
+    fn fetches_every_page() {
+        use std::cell::RefCell;
+
+        let requested = RefCell::new(Vec::new());
+        let items = crate::platform::block_on(fetch_all_pages(|page| {
+            requested.borrow_mut().push(page);
+            futures::future::ready(Ok((vec![page * 10, page * 10 + 1], Some(3))))
+        }))
+        .unwrap();
+
+        assert_eq!(items, vec![10, 11, 20, 21, 30, 31]);
+        assert_eq!(*requested.borrow(), vec![1, 2, 3]);
+
+        let failed = crate::platform::block_on(fetch_all_pages(|page| {
+            futures::future::ready(match page {
+                2 => Err(Error::RetriesExhausted),
+                _ => Ok((vec![page], Some(3))),
+            })
+        }));
+        assert!(matches!(failed, Err(Error::RetriesExhausted)));
+    }
+
+    #[test]
+    fn offline_lookups_serve_expired_cache() {
+        let expired = || CacheError::Expired(Some(String::from("etag")), 5);
+
+        assert_eq!(cached_lookup(CacheKind::Static, Ok(1), false).ok(), Some(1));
+        assert_eq!(
+            cached_lookup(CacheKind::Dynamic, Err(expired()), true).ok(),
+            Some(5)
+        );
+        assert_eq!(
+            cached_lookup(CacheKind::Dynamic, Err(expired()), false).err(),
+            Some((Some(String::from("etag")), Some(5)))
+        );
+        assert_eq!(
+            cached_lookup::<i32>(CacheKind::Static, Err(CacheError::NonExistant), true).err(),
+            Some((None, None))
+        );
+        assert_eq!(
+            cached_lookup(CacheKind::None, Ok(1), true).err(),
+            Some((None, None))
+        );
+    }
+
+    #[test]
+    fn request_limiter_caps_in_flight_requests() {
+        const SYSTEM: &str = r#"{"system_id": 1, "name": "Jita", "position": {"x": 0, "y": 0, "z": 0},
+            "security_status": 0.9, "constellation_id": 1}"#;
+        let delay = std::time::Duration::from_millis(50);
+
+        let token = mock_server(Vec::new());
+        let esi = mock_server(
+            (0..12)
+                .map(|_| Reply::Delayed(delay, 200, "", SYSTEM))
+                .collect(),
+        );
+        let client = Client::mock(&esi, &token, 3, DEFAULT_TIMEOUT);
+
+        let systems = run(async move {
+            let requests = (1..=12).map(|system_id| client.get_universe_system(system_id));
+            futures::future::join_all(requests).await
+        });
+
+        assert!(systems.iter().all(Result::is_ok));
+        assert_eq!(esi.requests(), 12);
+        assert_eq!(esi.max_open(), 3);
+        assert_eq!(request_limiter(0).permits(), 1);
+    }
+
+    #[test]
+    fn timed_out_requests_are_retryable() {
+        let token = mock_server(Vec::new());
+        let esi = mock_server(vec![Reply::Stall, status(200)]);
+        let client = Client::mock(&esi, &token, 1, std::time::Duration::from_millis(100));
+
+        let result = run(async move { client.get_universe_systems().await });
+        assert_eq!(result.unwrap(), vec![1, 2, 3]);
+        assert_eq!(esi.requests(), 2);
+    }
+
+    fn limit_headers(remain: &str, reset: &str) -> header::HeaderMap {
+        let mut headers = header::HeaderMap::new();
+        headers.insert("X-Esi-Error-Limit-Remain", remain.parse().unwrap());
+        headers.insert("X-Esi-Error-Limit-Reset", reset.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn exhausted_error_limit_blocks_requests() {
+        let limit = ErrorLimit::default();
+        let shared = limit.clone();
+
+        limit.update(&limit_headers("50", "30"), 1000);
+        assert_eq!(shared.blocked_for(1000), None);
+
+        limit.update(&limit_headers("0", "30"), 1000);
+        assert_eq!(
+            shared.blocked_for(1000),
+            Some(std::time::Duration::from_secs(30))
+        );
+        assert_eq!(
+            shared.blocked_for(21000),
+            Some(std::time::Duration::from_secs(10))
+        );
+        assert_eq!(shared.blocked_for(31000), None);
+    }
+
+    #[test]
+    fn error_limit_holds_requests_until_reset() {
+        static NOW: AtomicU64 = AtomicU64::new(1000);
+        fn clock() -> u64 {
+            NOW.load(Ordering::SeqCst)
+        }
+
+        let token = mock_server(Vec::new());
+        let esi = mock_server(vec![
+            Reply::Respond(
+                200,
+                "X-Esi-Error-Limit-Remain: 0\r\nX-Esi-Error-Limit-Reset: 30\r\n",
+                "[1,2,3]",
+            ),
+            status(200),
+        ]);
+        let mut client = Client::mock(&esi, &token, 1, DEFAULT_TIMEOUT);
+        client.error_limit = ErrorLimit::with_clock(clock);
+
+        let first = client.clone();
+        let result = run(async move { first.get_universe_systems().await });
+        assert_eq!(result.unwrap(), vec![1, 2, 3]);
+        assert!(client.error_limited());
+
+        let second = client.clone();
+        let blocked = async_std::task::spawn(async move { second.get_universe_systems().await });
+        std::thread::sleep(ERROR_LIMIT_POLL * 3);
+        assert_eq!(esi.requests(), 1);
+
+        NOW.fetch_add(30_000, Ordering::SeqCst);
+        let result = async_std::task::block_on(blocked);
+        assert_eq!(result.unwrap(), vec![1, 2, 3]);
+        assert_eq!(esi.requests(), 2);
+        assert!(!client.error_limited());
+    }
+}
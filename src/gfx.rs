@@ -1,4 +1,5 @@
 use ahash::AHashSet as HashSet;
+use serde::{Deserialize, Serialize};
 use winit::event::{MouseButton, VirtualKeyCode};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::WindowBuilder;
@@ -8,61 +9,185 @@ use std::rc::Rc;
 use std::time::Duration;
 
 use crate::math;
+use crate::paths::{Paths, UI_SCALE_FILE};
 use crate::platform::time::Instant;
-use crate::platform::{create_event_proxy, spawn, Frame, GraphicsBackend, DEFAULT_CONTROL_FLOW};
-use crate::world::{Galaxy, JumpType, World};
+use crate::platform::{
+    block_on, create_event_proxy, file_exists, read_file, set_clipboard, spawn, wait_until,
+    write_file, Frame, GraphicsBackend, DEFAULT_CONTROL_FLOW,
+};
+use crate::replay::{EventRecorder, EventReplay};
+use crate::settings::Settings;
+use crate::world::{
+    Galaxy, JumpType, RouteError, RoutePreference, SecurityClass, World, DEFAULT_ROUTE,
+};
 
 pub mod font;
 pub mod images;
 
 pub use crate::input::{InputState, UserEventReceiver, UserEventSender};
 
+mod character_picker;
+use character_picker::CharacterPicker;
+
+mod command;
+use command::Command;
+
+mod context_menu;
+use context_menu::ContextMenu;
+
+mod loading;
+use loading::LoadingProgress;
+
 mod map;
 use map::Map;
 
 mod info;
 use info::InfoBox;
 
+mod minimap;
+use minimap::Minimap;
+
 mod route;
 use route::RouteBox;
 
-#[derive(Clone, Debug)]
+mod palette;
+use palette::CommandPalette;
+
+mod search_results;
+use search_results::SearchResults;
+
+mod shader_error;
+use shader_error::ShaderErrorBox;
+
+mod toast;
+use toast::Toasts;
+
+mod tooltip;
+use tooltip::SystemTooltip;
+
+const REGION_FILTER_PREFIX: &str = "region:";
+const CONSTELLATION_FILTER_PREFIX: &str = "constellation:";
+const ROUTE_PREFIX: &str = "route:";
+const TOUR_PREFIX: &str = "tour:";
+const MIN_UI_SCALE_FACTOR: f32 = 0.5;
+const MAX_UI_SCALE_FACTOR: f32 = 2.0;
+const UI_SCALE_STEP: f32 = 0.1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum UserEvent {
     DataEvent(DataEvent),
     MapEvent(MapEvent),
     QueryEvent(QueryEvent),
     RouteEvent(RouteEvent),
+    MenuEvent(MenuEvent),
+    Command(Command),
+    Notice(String, Severity),
     FrameDrawn,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum DataEvent {
     CharacterLocationChanged(Option<i32>),
     SovStandingsChanged,
     SystemStatsChanged,
+    SystemMetricsChanged,
+    IncursionsChanged,
+    FactionWarfareChanged,
+    DockedLocationChanged,
+    LoadProgress {
+        stage: LoadStage,
+        done: usize,
+        total: usize,
+    },
+    ProfilesLoaded,
     ImageLoaded,
+    #[serde(skip)]
     GalaxyLoaded(Galaxy),
+    GalaxyLoadFailed(String),
     GalaxyImported,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum MapEvent {
     SelectedSystemChanged(Option<i32>),
+    SystemContextMenu(i32),
+    CenterOnSystem(i32),
+    ViewChanged {
+        zoom: f32,
+        offset_x: f32,
+        offset_y: f32,
+    },
+    CenterOn {
+        x: f32,
+        y: f32,
+    },
+    HiddenJumpsChanged(HashSet<JumpType>),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum MenuEvent {
+    SetRouteStart(i32),
+    SetRouteEnd(i32),
+    SetDestination(i32),
+    CopyName(i32),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum RouteEvent {
     SelectedSystemChanged(Option<i32>),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LoadStage {
+    Regions,
+    Constellations,
+    Systems,
+    Stargates,
+}
+
+impl LoadStage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LoadStage::Regions => "Regions",
+            LoadStage::Constellations => "Constellations",
+            LoadStage::Systems => "Systems",
+            LoadStage::Stargates => "Stargates",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RegionOrConstellation {
+    Region(i32),
+    Constellation(i32),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum QueryEvent {
     SystemsFocused(HashSet<i32>),
+    SystemsMatched(Vec<i32>),
+    FilterChanged(Option<RegionOrConstellation>),
     RouteChanged,
+    RouteFailed(RouteError),
+    SystemsUnresolved(Vec<String>),
 }
 
 struct UserState {
     window_size: math::V2<f32>,
+    selected_system: Option<i32>,
+    focused_systems: HashSet<i32>,
+    compare_routes: bool,
+    hidden_jumps: HashSet<JumpType>,
+    route_hidden_jumps: bool,
+    route_start: Option<i32>,
+    route_end: Option<i32>,
     query_string: String,
     text_nodes: Vec<font::PositionedTextSpan>,
 }
@@ -74,13 +199,52 @@ pub struct GraphicsContext {
     pub symbol_font: font::FontId,
     pub font_cache: font::FontCache,
     pub images: images::Images,
+    pub settings: Settings,
+    pub paths: Paths,
     ui_scale: Cell<f32>,
+    ui_scale_factor: Cell<f32>,
+    frame_interval: Option<Duration>,
+    last_frame: Cell<Instant>,
+    redraw_pending: Cell<bool>,
+    animating: Cell<bool>,
+    wake_at: Cell<Option<Instant>>,
 }
 
 impl GraphicsContext {
     pub fn request_redraw(&self, cause: &'static str) {
         log::debug!("requested redraw: {}", cause);
-        self.display.request_redraw()
+        if self.frame_interval.is_some() {
+            self.redraw_pending.set(true);
+        } else {
+            self.display.request_redraw()
+        }
+    }
+
+    pub fn request_animation_frame(&self, cause: &'static str) {
+        self.animating.set(true);
+        self.request_redraw(cause);
+    }
+
+    // Runs another update at the given time without drawing every frame until then
+    pub fn request_wake(&self, at: Instant) {
+        let wake_at = self.wake_at.get().map_or(at, |wake_at| wake_at.min(at));
+        self.wake_at.set(Some(wake_at));
+    }
+
+    fn pace_frame(&self) -> Option<Instant> {
+        let frame_interval = self.frame_interval?;
+        if !self.redraw_pending.get() {
+            return None;
+        }
+
+        let next_frame = self.last_frame.get() + frame_interval;
+        if Instant::now() >= next_frame {
+            self.redraw_pending.set(false);
+            self.display.request_redraw();
+            None
+        } else {
+            Some(next_frame)
+        }
     }
 
     pub fn set_ui_scale(&self, window_size: math::V2<f32>) {
@@ -88,7 +252,59 @@ impl GraphicsContext {
     }
 
     pub fn ui_scale(&self) -> f32 {
-        self.ui_scale.get()
+        self.ui_scale.get() * self.ui_scale_factor.get()
+    }
+
+    pub fn set_ui_scale_factor(&self, factor: f32) {
+        self.ui_scale_factor
+            .set(factor.clamp(MIN_UI_SCALE_FACTOR, MAX_UI_SCALE_FACTOR));
+        log::info!("ui scale factor: {:.1}", self.ui_scale_factor.get());
+    }
+
+    fn adjust_ui_scale(&self, input_state: &InputState) -> bool {
+        if !(input_state.is_key_down(VirtualKeyCode::LControl)
+            || input_state.is_key_down(VirtualKeyCode::RControl))
+        {
+            return false;
+        }
+
+        let factor = self.ui_scale_factor.get();
+        if input_state.was_key_down(VirtualKeyCode::Equals)
+            || input_state.was_key_down(VirtualKeyCode::NumpadAdd)
+        {
+            self.set_ui_scale_factor(factor + UI_SCALE_STEP);
+        } else if input_state.was_key_down(VirtualKeyCode::Minus)
+            || input_state.was_key_down(VirtualKeyCode::NumpadSubtract)
+        {
+            self.set_ui_scale_factor(factor - UI_SCALE_STEP);
+        } else if input_state.was_key_down(VirtualKeyCode::Key0) {
+            self.set_ui_scale_factor(1.0);
+        }
+
+        factor != self.ui_scale_factor.get()
+    }
+
+    async fn load_ui_scale_factor(paths: &Paths) -> f32 {
+        let path = paths.ui_scale_path();
+        if !file_exists(&path) {
+            return 1.0;
+        }
+
+        match read_file(&path).await {
+            Ok(bytes) => parse_ui_scale_factor(&bytes),
+            Err(error) => {
+                log::error!("unable to read {}: {:?}", path.display(), error);
+                1.0
+            }
+        }
+    }
+
+    pub fn save_ui_scale_factor(&self) {
+        let bytes = serde_json::to_vec(&self.ui_scale_factor.get()).unwrap();
+        let path = self.paths.ui_scale_path();
+        if let Err(error) = block_on(write_file(&path, bytes)) {
+            log::error!("unable to save {}: {:?}", path.display(), error);
+        }
     }
 
     pub fn window_size(&self) -> math::V2<f32> {
@@ -104,19 +320,38 @@ pub struct Window {
 
 impl Window {
     pub fn new(width: u32, height: u32) -> Self {
+        let mut settings = block_on(Settings::load());
+        settings.apply_args(std::env::args().skip(1));
+
         let event_loop = EventLoop::with_user_event();
         let w_builder = WindowBuilder::new()
             .with_inner_size(winit::dpi::LogicalSize::new(width, height))
             .with_transparent(false)
             .with_title("EVE Mapper");
-        let display = GraphicsBackend::new(w_builder, &event_loop, width, height);
+        let display = GraphicsBackend::new(w_builder, &event_loop, width, height, &settings);
 
         let mut font_cache = font::FontCache::new(&display, 1024, 1024);
-        let ui_font = font_cache.load::<font::EveSansNeue>().unwrap();
+        let ui_font = settings
+            .ui_font
+            .as_ref()
+            .and_then(|path| block_on(font_cache.load_from_path(path)))
+            .or_else(|| font_cache.load::<font::EveSansNeue>())
+            .unwrap();
         let title_font = font_cache.load::<font::EveSansNeueBold>().unwrap();
         let symbol_font = font_cache.load::<font::NanumGothic>().unwrap();
 
-        let images = images::Images::new(&display, 4096, 4096);
+        let atlas_size = settings
+            .image_atlas_size
+            .unwrap_or(4096)
+            .min(display.max_texture_size());
+        let images = images::Images::new(&display, atlas_size, atlas_size);
+
+        let paths = settings
+            .data_dir
+            .as_ref()
+            .map(Paths::new)
+            .unwrap_or_default();
+        let ui_scale_factor = block_on(GraphicsContext::load_ui_scale_factor(&paths));
 
         let graphics_context = Rc::new(GraphicsContext {
             display,
@@ -125,13 +360,31 @@ impl Window {
             symbol_font,
             font_cache,
             images,
+            frame_interval: settings
+                .max_frame_rate
+                .filter(|rate| *rate > 0)
+                .map(|rate| Duration::from_secs(1) / rate),
+            last_frame: Cell::new(Instant::now()),
+            redraw_pending: Cell::new(false),
+            animating: Cell::new(false),
+            wake_at: Cell::new(None),
+            settings,
+            paths,
             ui_scale: Cell::new(1.0),
+            ui_scale_factor: Cell::new(ui_scale_factor),
         });
 
         graphics_context.set_ui_scale(math::v2(width, height).as_f32());
 
         let user_state = UserState {
             query_string: String::new(),
+            compare_routes: false,
+            hidden_jumps: HashSet::new(),
+            route_hidden_jumps: true,
+            route_start: None,
+            route_end: None,
+            selected_system: None,
+            focused_systems: HashSet::new(),
             window_size: math::v2(1024.0, 1024.0),
             text_nodes: Vec::new(),
         };
@@ -147,12 +400,41 @@ impl Window {
         let (event_sender, event_receiver) = create_event_proxy(&self.event_loop);
 
         let mut world = World::new(event_sender.clone());
+        world.set_kill_penalty(self.graphics_context.settings.route_kill_penalty);
+        if let Some(trade_hubs) = self.graphics_context.settings.trade_hubs.clone() {
+            world.set_trade_hubs(trade_hubs);
+        }
+        world.set_game_log_dir(self.graphics_context.settings.game_log_dir.clone());
+        world.set_system_metrics_path(self.graphics_context.settings.system_metrics.clone());
         spawn({
             let event_sender = event_sender.clone();
+            let concurrency = self
+                .graphics_context
+                .settings
+                .esi_concurrency
+                .unwrap_or(crate::esi::DEFAULT_CONCURRENCY);
+            let offline = self.graphics_context.settings.offline;
+            let paths = self.graphics_context.paths.clone();
+            let timeout = self
+                .graphics_context
+                .settings
+                .esi_timeout_seconds
+                .map(std::time::Duration::from_secs)
+                .unwrap_or(crate::esi::DEFAULT_TIMEOUT);
             async move {
-                let galaxy = crate::world::Galaxy::load().await;
-                let _ = event_sender
-                    .send_user_event(UserEvent::DataEvent(DataEvent::GalaxyLoaded(galaxy)));
+                let event = match crate::world::Galaxy::load(
+                    event_sender.clone(),
+                    paths,
+                    concurrency,
+                    timeout,
+                    offline,
+                )
+                .await
+                {
+                    Ok(galaxy) => DataEvent::GalaxyLoaded(galaxy),
+                    Err(error) => DataEvent::GalaxyLoadFailed(error),
+                };
+                let _ = event_sender.send_user_event(UserEvent::DataEvent(event));
             }
         });
 
@@ -162,6 +444,15 @@ impl Window {
         let mut map = Map::new(graphics_context.clone());
         let mut info_box = InfoBox::new(graphics_context.clone());
         let mut route_box = RouteBox::new(graphics_context.clone());
+        let mut minimap = Minimap::new(graphics_context.clone());
+        let mut search_results = SearchResults::new(graphics_context.clone());
+        let mut loading_progress = LoadingProgress::new(graphics_context.clone());
+        let mut shader_error_box = ShaderErrorBox::new(graphics_context.clone());
+        let mut command_palette = CommandPalette::new(graphics_context.clone());
+        let mut character_picker = CharacterPicker::new(graphics_context.clone());
+        let mut context_menu = ContextMenu::new(graphics_context.clone());
+        let mut system_tooltip = SystemTooltip::new(graphics_context.clone());
+        let mut toasts = Toasts::new(graphics_context.clone());
 
         let window_size = math::v2(
             graphics_context.window_size().x as u32,
@@ -169,6 +460,23 @@ impl Window {
         );
         let mut input_state = InputState::new(event_sender, event_receiver, window_size);
 
+        if let Some(path) = graphics_context.settings.record_events.as_ref() {
+            log::info!("recording events to {}", path);
+            input_state.record_events(EventRecorder::new(path));
+        }
+
+        let mut replay = graphics_context
+            .settings
+            .replay_events
+            .as_ref()
+            .and_then(|path| match block_on(EventReplay::load(path)) {
+                Ok(replay) => Some(replay),
+                Err(error) => {
+                    log::error!("unable to load event replay: {:?}", error);
+                    None
+                }
+            });
+
         let mut frame_time = Instant::now();
 
         self.event_loop.run(move |event, _window, control_flow| {
@@ -186,8 +494,16 @@ impl Window {
                         }
                     }
 
+                    if let Some(replay) = replay.as_mut() {
+                        replay.update(&mut input_state);
+                    }
+
                     let dt = frame_time.elapsed();
 
+                    if graphics_context.adjust_ui_scale(&input_state) {
+                        input_state.invalidate_layout();
+                    }
+
                     if let Some(window_size) = input_state.window_resized() {
                         graphics_context.set_ui_scale(window_size.as_f32());
                         graphics_context
@@ -195,21 +511,65 @@ impl Window {
                             .update_window_size(window_size.as_f32());
                     }
 
+                    command_palette.set_query_empty(user_state.query_string.is_empty());
+                    command_palette.update(dt, &input_state, &world);
+                    character_picker.update(dt, &input_state, &world);
+                    let capturing_input = command_palette.capturing_input()
+                        || character_picker.capturing_input()
+                        || context_menu.capturing_input();
+                    input_state.set_keys_captured(capturing_input);
+                    let measuring = map.measuring();
                     Window::update(
                         dt,
                         &input_state,
                         &mut world,
                         &graphics_context,
                         &mut user_state,
+                        capturing_input,
+                        measuring,
                     );
+                    context_menu.update(dt, &input_state, &world);
                     info_box.update(dt, &input_state, &world);
                     route_box.update(dt, &input_state, &world);
+                    minimap.update(dt, &input_state, &world);
+                    search_results.update(dt, &input_state, &world);
+                    loading_progress.update(dt, &input_state, &world);
+                    map.set_capturing_input(capturing_input);
+                    map.set_capturing_pointer(
+                        minimap.capturing_pointer()
+                            || search_results.capturing_pointer()
+                            || context_menu.capturing_pointer(),
+                    );
                     map.update(dt, &input_state, &world);
+                    system_tooltip.set_capturing_input(capturing_input);
+                    system_tooltip.update(dt, &input_state, &world);
+                    shader_error_box.update(dt, &input_state, &world);
+                    toasts.update(dt, &input_state, &world);
 
                     frame_time = Instant::now();
 
+                    let next_frame = match (
+                        graphics_context.pace_frame(),
+                        graphics_context.wake_at.take(),
+                    ) {
+                        (Some(frame), Some(wake)) => Some(frame.min(wake)),
+                        (frame, wake) => frame.or(wake),
+                    };
+
                     *control_flow = if input_state.closed() {
+                        map.save_view();
+                        graphics_context.save_ui_scale_factor();
+                        world.save_cache();
+                        if let Some(recorder) = input_state.take_recorder() {
+                            if let Err(error) = block_on(recorder.save()) {
+                                log::error!("unable to save event recording: {:?}", error);
+                            }
+                        }
                         ControlFlow::Exit
+                    } else if replay.as_ref().map(|r| !r.finished()).unwrap_or(false) {
+                        ControlFlow::Poll
+                    } else if let Some(next_frame) = next_frame {
+                        wait_until(next_frame)
                     } else {
                         DEFAULT_CONTROL_FLOW
                     };
@@ -217,6 +577,7 @@ impl Window {
                     input_state.reset();
                 }
                 Event::RedrawRequested(..) => {
+                    graphics_context.last_frame.set(Instant::now());
                     let mut frame = graphics_context.display.begin();
                     frame.clear_color(math::v4(0.0, 0.0, 0.0, 1.0));
                     frame.clear_depth(0.0);
@@ -226,15 +587,27 @@ impl Window {
                         .fill_glyph_cache(&graphics_context.display);
 
                     map.draw(&mut frame);
+                    minimap.draw(&mut frame);
                     route_box.draw(&mut frame);
                     info_box.draw(&mut frame);
+                    search_results.draw(&mut frame);
+                    loading_progress.draw(&mut frame);
+                    system_tooltip.draw(&mut frame);
+                    toasts.draw(&mut frame);
+
+                    context_menu.draw(&mut frame);
 
                     Window::draw(&mut frame, &graphics_context, &user_state);
+                    command_palette.draw(&mut frame);
+                    character_picker.draw(&mut frame);
+                    shader_error_box.draw(&mut frame);
 
                     graphics_context.display.end(frame);
 
                     //Send this event to ensure we run the updates for the next frame to continue any animations that may be ongoing
-                    input_state.send_user_event(UserEvent::FrameDrawn);
+                    if graphics_context.animating.replace(false) {
+                        input_state.send_user_event(UserEvent::FrameDrawn);
+                    }
                 }
                 Event::UserEvent(UserEvent::DataEvent(DataEvent::GalaxyLoaded(galaxy))) => {
                     world.import(galaxy);
@@ -252,42 +625,188 @@ impl Window {
         world: &mut World,
         graphics_context: &GraphicsContext,
         user_state: &mut UserState,
+        capturing_input: bool,
+        measuring: bool,
     ) {
         let mut query_changed = false;
 
-        if input_state.text().len() > 0 {
-            user_state.query_string.push_str(input_state.text());
+        for event in input_state.user_events() {
+            match event {
+                UserEvent::MapEvent(MapEvent::SelectedSystemChanged(system)) => {
+                    user_state.selected_system = *system;
+                }
+                UserEvent::QueryEvent(QueryEvent::SystemsFocused(systems)) => {
+                    user_state.focused_systems = systems.clone();
+                }
+                UserEvent::MapEvent(MapEvent::HiddenJumpsChanged(hidden)) => {
+                    user_state.hidden_jumps = hidden.clone();
+                    if !user_state.route_hidden_jumps {
+                        Window::update_excluded_jumps(input_state, world, user_state);
+                    }
+                }
+                UserEvent::DataEvent(DataEvent::CharacterLocationChanged(Some(location)))
+                    if graphics_context.settings.clear_route_on_arrival
+                        && world.clear_arrived_routes(*location) =>
+                {
+                    input_state.send_user_event(UserEvent::QueryEvent(QueryEvent::RouteChanged))
+                }
+                UserEvent::DataEvent(DataEvent::GalaxyImported) => {
+                    let avoided = graphics_context
+                        .settings
+                        .avoided_systems
+                        .iter()
+                        .filter_map(|name| {
+                            let system = world.system_by_name(name);
+                            if system.is_none() {
+                                log::warn!("unknown avoided system: {}", name);
+                            }
+                            system.map(|s| s.system_id)
+                        })
+                        .collect();
+                    world.set_avoided_systems(avoided);
+                }
+                UserEvent::MenuEvent(MenuEvent::SetRouteStart(system_id)) => {
+                    user_state.route_start = Some(*system_id);
+                    Window::update_menu_route(input_state, world, user_state);
+                }
+                UserEvent::MenuEvent(MenuEvent::SetRouteEnd(system_id)) => {
+                    user_state.route_end = Some(*system_id);
+                    Window::update_menu_route(input_state, world, user_state);
+                }
+                UserEvent::MenuEvent(MenuEvent::SetDestination(system_id)) => {
+                    world.set_destination(*system_id);
+                }
+                UserEvent::MenuEvent(MenuEvent::CopyName(system_id)) => {
+                    if let Some(system) = world.system(*system_id) {
+                        set_clipboard(system.name.clone());
+                    }
+                }
+                UserEvent::DataEvent(DataEvent::SovStandingsChanged)
+                    if world.refresh_bridge_access() =>
+                {
+                    input_state.send_user_event(UserEvent::QueryEvent(QueryEvent::RouteChanged))
+                }
+                UserEvent::DataEvent(DataEvent::SystemStatsChanged)
+                    if world.kill_penalty() > 0.0 =>
+                {
+                    world.refresh_routes();
+                    input_state.send_user_event(UserEvent::QueryEvent(QueryEvent::RouteChanged))
+                }
+                _ => (),
+            }
+        }
+
+        if input_state.focus_gained() {
+            world.request_refresh();
+        }
+
+        if push_query_text(&mut user_state.query_string, input_state, capturing_input) {
             query_changed = true;
             graphics_context.request_redraw("query text");
         }
 
-        if input_state.was_key_down(VirtualKeyCode::Return) {
+        if input_state.was_key_down(VirtualKeyCode::Return) && !capturing_input {
             let parts: Vec<_> = user_state.query_string.split(' ').collect();
 
-            if user_state.query_string.len() == 0 {
+            if let Some(name) = user_state.query_string.strip_prefix(REGION_FILTER_PREFIX) {
+                match world.region_by_name(name) {
+                    Some(region) => input_state.send_user_event(UserEvent::QueryEvent(
+                        QueryEvent::FilterChanged(Some(RegionOrConstellation::Region(
+                            region.region_id,
+                        ))),
+                    )),
+                    None => log::warn!("unknown region: {}", name),
+                }
+            } else if let Some(name) = user_state
+                .query_string
+                .strip_prefix(CONSTELLATION_FILTER_PREFIX)
+            {
+                match world.constellation_by_name(name) {
+                    Some(constellation) => input_state.send_user_event(UserEvent::QueryEvent(
+                        QueryEvent::FilterChanged(Some(RegionOrConstellation::Constellation(
+                            constellation.constellation_id,
+                        ))),
+                    )),
+                    None => log::warn!("unknown constellation: {}", name),
+                }
+            } else if user_state.query_string.starts_with(TOUR_PREFIX) {
+                let systems: Vec<_> = user_state.focused_systems.iter().copied().collect();
+                if systems.len() >= 2 {
+                    match world.create_tour(&systems) {
+                        Ok(()) => input_state
+                            .send_user_event(UserEvent::QueryEvent(QueryEvent::RouteChanged)),
+                        Err(error) => input_state
+                            .send_user_event(UserEvent::QueryEvent(QueryEvent::RouteFailed(error))),
+                    }
+                } else {
+                    log::warn!("tour needs at least two focused systems");
+                }
+            } else if let Some(list) = user_state
+                .query_string
+                .strip_prefix(ROUTE_PREFIX)
+                .or_else(|| Some(user_state.query_string.as_str()).filter(|q| q.contains(',')))
+            {
+                let (waypoints, unresolved) = world.resolve_waypoints(list);
+                if waypoints.len() >= 2 {
+                    match world.create_waypoint_route(DEFAULT_ROUTE, &waypoints) {
+                        Ok(()) => input_state
+                            .send_user_event(UserEvent::QueryEvent(QueryEvent::RouteChanged)),
+                        Err(error) => input_state
+                            .send_user_event(UserEvent::QueryEvent(QueryEvent::RouteFailed(error))),
+                    }
+                }
+                if !unresolved.is_empty() {
+                    log::warn!("unknown systems in route: {:?}", unresolved);
+                    input_state.send_user_event(UserEvent::QueryEvent(
+                        QueryEvent::SystemsUnresolved(unresolved),
+                    ));
+                }
+            } else if user_state.query_string.len() == 0 {
                 input_state.send_user_event(UserEvent::QueryEvent(QueryEvent::SystemsFocused(
                     HashSet::new(),
+                )));
+                input_state.send_user_event(UserEvent::QueryEvent(QueryEvent::SystemsMatched(
+                    Vec::new(),
                 )))
-            } else if parts.len() == 2 {
-                let from = world.match_system(parts[0]).into_iter().next();
-                let to = world.match_system(parts[1]).into_iter().next();
+            } else if parts.len() == 2 || parts.len() == 3 {
+                let (name, from, to) = if parts.len() == 3 {
+                    (parts[0], parts[1], parts[2])
+                } else {
+                    (DEFAULT_ROUTE, parts[0], parts[1])
+                };
+                let from = world.match_system(from).into_iter().next();
+                let to = world.match_system(to).into_iter().next();
 
-                match (from, to) {
-                    (Some(from), Some(to)) => {
-                        world.create_route(from, to);
+                let result = match (from, to) {
+                    (Some(from), Some(to)) if user_state.compare_routes && parts.len() == 2 => {
+                        Some(world.compare_routes(from, to))
+                    }
+                    (Some(from), Some(to)) => Some(world.create_route(name, from, to).map(|_| {
                         if input_state.is_key_down(VirtualKeyCode::LShift)
                             | input_state.is_key_down(VirtualKeyCode::RShift)
                         {
-                            world.send_route_to_client();
+                            world.send_route_to_client(name);
                         }
+                    })),
+                    _ => None,
+                };
+
+                match result {
+                    Some(Ok(())) => {
                         input_state.send_user_event(UserEvent::QueryEvent(QueryEvent::RouteChanged))
                     }
-                    _ => (),
+                    Some(Err(error)) => input_state
+                        .send_user_event(UserEvent::QueryEvent(QueryEvent::RouteFailed(error))),
+                    None => (),
                 }
             } else if parts.len() == 1 {
-                let focus_systems = world.match_system(parts[0]).into_iter().collect();
+                let matched_systems = world.match_system(parts[0]);
+                let focus_systems = matched_systems.iter().cloned().collect();
                 input_state.send_user_event(UserEvent::QueryEvent(QueryEvent::SystemsFocused(
                     focus_systems,
+                )));
+                input_state.send_user_event(UserEvent::QueryEvent(QueryEvent::SystemsMatched(
+                    matched_systems,
                 )))
             }
             user_state.query_string = String::new();
@@ -295,22 +814,94 @@ impl Window {
             graphics_context.request_redraw("query return");
         }
 
-        if input_state.was_key_down(VirtualKeyCode::Back) {
-            user_state.query_string.pop();
+        if input_state.was_key_down(VirtualKeyCode::C)
+            && (input_state.is_key_down(VirtualKeyCode::LControl)
+                || input_state.is_key_down(VirtualKeyCode::RControl))
+        {
+            let route = world.route_as_text();
+            if !route.is_empty() {
+                set_clipboard(route);
+            }
+        }
+
+        if input_state.was_key_down(VirtualKeyCode::Back) && !capturing_input {
+            crate::input::pop_grapheme(&mut user_state.query_string);
             query_changed = true;
             graphics_context.request_redraw("query back");
         }
 
-        if input_state.was_key_down(VirtualKeyCode::Escape) {
-            world.clear_route();
+        if Command::ReloadShaders.triggered(input_state) && graphics_context.settings.dev_mode {
+            log::info!("reloading shaders");
+            graphics_context.display.reload_shaders();
+        }
+
+        if Command::WarmRegion.triggered(input_state) {
+            let region = user_state
+                .selected_system
+                .and_then(|s| world.system(s))
+                .and_then(|s| world.constellation(s.constellation_id))
+                .map(|c| c.region_id);
+            if let Some(region) = region {
+                world.warm_region(region);
+            }
+        }
+
+        if Command::Logout.triggered(input_state)
+            && Command::Logout.available(&graphics_context.settings)
+        {
+            log::info!("logging out");
+            world.logout();
+        }
+
+        if Command::ClearCache.triggered(input_state)
+            && Command::ClearCache.available(&graphics_context.settings)
+        {
+            world.clear_cache();
+        }
+
+        if Command::ToggleHiddenJumpRouting.triggered(input_state) {
+            user_state.route_hidden_jumps = !user_state.route_hidden_jumps;
+            log::info!(
+                "route through hidden jumps: {}",
+                user_state.route_hidden_jumps
+            );
+            Window::update_excluded_jumps(input_state, world, user_state);
+        }
+
+        if Command::ToggleRouteComparison.triggered(input_state) {
+            user_state.compare_routes = !user_state.compare_routes;
+            log::info!("route comparison: {}", user_state.compare_routes);
+        }
+
+        if Command::CycleRoutePreference.triggered(input_state) {
+            let preference = match world.route_preference() {
+                RoutePreference::Shortest => RoutePreference::Safer,
+                RoutePreference::Safer => RoutePreference::LessSecure,
+                RoutePreference::LessSecure => RoutePreference::Shortest,
+            };
+            world.set_route_preference(preference);
+            log::info!("route preference: {:?}", preference);
+        }
+
+        // Escape clears an active measurement first, the map handles that
+        let clearing_measurement = measuring && input_state.was_key_down(VirtualKeyCode::Escape);
+        if Command::ClearRoutes.triggered(input_state) && !clearing_measurement {
+            world.clear_routes();
             input_state.send_user_event(UserEvent::QueryEvent(QueryEvent::SystemsFocused(
                 HashSet::new(),
             )));
+            input_state.send_user_event(UserEvent::QueryEvent(QueryEvent::SystemsMatched(
+                Vec::new(),
+            )));
+            input_state.send_user_event(UserEvent::QueryEvent(QueryEvent::FilterChanged(None)));
             input_state.send_user_event(UserEvent::QueryEvent(QueryEvent::RouteChanged))
         }
 
         if let Some(window_size) = input_state.window_resized() {
             user_state.window_size = window_size.as_f32();
+            graphics_context
+                .display
+                .set_ime_position(math::v2(5.0, user_state.window_size.y - 30.0));
             query_changed = true;
         }
 
@@ -331,6 +922,28 @@ impl Window {
         }
     }
 
+    fn update_excluded_jumps(input_state: &InputState, world: &mut World, user_state: &UserState) {
+        let excluded = if user_state.route_hidden_jumps {
+            HashSet::new()
+        } else {
+            user_state.hidden_jumps.clone()
+        };
+        world.set_excluded_jumps(excluded);
+        input_state.send_user_event(UserEvent::QueryEvent(QueryEvent::RouteChanged));
+    }
+
+    fn update_menu_route(input_state: &InputState, world: &mut World, user_state: &UserState) {
+        if let (Some(from), Some(to)) = (user_state.route_start, user_state.route_end) {
+            match world.create_route(DEFAULT_ROUTE, from, to) {
+                Ok(()) => {
+                    input_state.send_user_event(UserEvent::QueryEvent(QueryEvent::RouteChanged))
+                }
+                Err(error) => input_state
+                    .send_user_event(UserEvent::QueryEvent(QueryEvent::RouteFailed(error))),
+            }
+        }
+    }
+
     fn draw(frame: &mut Frame, graphics_context: &GraphicsContext, user_state: &UserState) {
         if user_state.text_nodes.len() > 0 {
             graphics_context.display.draw_text(
@@ -348,6 +961,36 @@ trait Widget {
     fn draw(&mut self, frame: &mut Frame);
 }
 
+fn parse_ui_scale_factor(bytes: &[u8]) -> f32 {
+    match serde_json::from_slice::<f32>(bytes) {
+        Ok(factor) if factor.is_finite() => factor.clamp(MIN_UI_SCALE_FACTOR, MAX_UI_SCALE_FACTOR),
+        Ok(factor) => {
+            log::error!("ignoring invalid ui scale factor: {}", factor);
+            1.0
+        }
+        Err(error) => {
+            log::error!("unable to parse {}: {:?}", UI_SCALE_FILE, error);
+            1.0
+        }
+    }
+}
+
+fn push_query_text(
+    query_string: &mut String,
+    input_state: &InputState,
+    capturing_input: bool,
+) -> bool {
+    // X11 also reports text for control shortcuts like the ui scale keys
+    let control = input_state.is_key_down(VirtualKeyCode::LControl)
+        || input_state.is_key_down(VirtualKeyCode::RControl);
+    if input_state.text().is_empty() || capturing_input || control {
+        return false;
+    }
+
+    query_string.push_str(input_state.text());
+    true
+}
+
 fn sec_status_color(sec: f64) -> math::V3<f32> {
     let sec_status = sec.max(0.0).min(1.0) as f32;
     let blue = if sec_status >= 0.9 { 1.0 } else { 0.0 };
@@ -360,6 +1003,19 @@ fn sec_status_color(sec: f64) -> math::V3<f32> {
     math::v3(red, green, blue)
 }
 
+fn security_class_color(class: SecurityClass) -> math::V3<f32> {
+    match class {
+        SecurityClass::High => sec_status_color(1.0),
+        SecurityClass::Low => sec_status_color(0.3),
+        SecurityClass::Null => sec_status_color(0.0),
+        SecurityClass::Wormhole => math::v3(0.6, 0.4, 1.0),
+    }
+}
+
+fn highlight_color(color: math::V3<f32>) -> math::V3<f32> {
+    (color + math::V3::fill(0.1)).clamp(math::V3::fill(0.0), math::V3::fill(1.0))
+}
+
 fn standing_color(standing: f64) -> math::V3<f32> {
     if standing == 0.0 {
         math::v3(0.5, 0.5, 0.5)
@@ -374,6 +1030,15 @@ fn standing_color(standing: f64) -> math::V3<f32> {
     }
 }
 
+fn route_color(route: usize) -> math::V3<f32> {
+    match route % 4 {
+        0 => math::v3(1.0, 0.85, 0.0),
+        1 => math::v3(0.0, 0.9, 0.9),
+        2 => math::v3(1.0, 0.3, 0.8),
+        _ => math::v3(0.6, 1.0, 0.3),
+    }
+}
+
 fn jump_type_color(jump: &JumpType) -> math::V3<f32> {
     match jump {
         JumpType::System => math::v3(0.0, 0.0, 1.0),
@@ -418,3 +1083,91 @@ pub struct TextVertex {
     pub uv: math::V2<f32>,
     pub color: math::V4<f32>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // mirrors the frame loop: the palette sees the text before the query box
+    fn type_query(text: &str) -> (String, bool) {
+        let mut input_state = InputState::detached(math::v2(1024, 1024));
+        let mut query_string = String::new();
+        let mut palette_opened = false;
+
+        for c in text.chars() {
+            input_state.process(winit::event::Event::WindowEvent {
+                window_id: unsafe { winit::window::WindowId::dummy() },
+                event: winit::event::WindowEvent::ReceivedCharacter(c),
+            });
+
+            palette_opened |=
+                palette::palette_search(query_string.is_empty(), input_state.text()).is_some();
+            push_query_text(&mut query_string, &input_state, palette_opened);
+            input_state.reset();
+        }
+
+        (query_string, palette_opened)
+    }
+
+    #[test]
+    fn filter_prefixes_reach_the_query_box() {
+        assert_eq!(type_query("region:Foo"), ("region:Foo".to_string(), false));
+        assert_eq!(
+            type_query("constellation:Kimotoro"),
+            ("constellation:Kimotoro".to_string(), false)
+        );
+        assert_eq!(type_query(":clear"), (String::new(), true));
+    }
+
+    #[test]
+    fn route_prefix_reaches_the_query_box() {
+        let (query_string, palette_opened) = type_query("route:Jita,Amarr");
+        assert!(!palette_opened);
+        assert_eq!(query_string.strip_prefix(ROUTE_PREFIX), Some("Jita,Amarr"));
+    }
+
+    #[test]
+    fn tour_prefix_reaches_the_query_box() {
+        let (query_string, palette_opened) = type_query("tour:");
+        assert!(!palette_opened);
+        assert!(query_string.starts_with(TOUR_PREFIX));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn control_shortcuts_skip_the_query_box() {
+        use winit::event::{DeviceId, ElementState, KeyboardInput, WindowEvent};
+
+        let mut input_state = InputState::detached(math::v2(1024, 1024));
+        let window_id = unsafe { winit::window::WindowId::dummy() };
+        input_state.process(winit::event::Event::WindowEvent {
+            window_id,
+            event: WindowEvent::KeyboardInput {
+                device_id: unsafe { DeviceId::dummy() },
+                input: KeyboardInput {
+                    scancode: 0,
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::LControl),
+                    modifiers: Default::default(),
+                },
+                is_synthetic: false,
+            },
+        });
+        input_state.process(winit::event::Event::WindowEvent {
+            window_id,
+            event: WindowEvent::ReceivedCharacter('='),
+        });
+
+        let mut query_string = String::new();
+        assert!(!push_query_text(&mut query_string, &input_state, false));
+        assert!(query_string.is_empty());
+    }
+
+    #[test]
+    fn ui_scale_factor_is_clamped() {
+        assert_eq!(parse_ui_scale_factor(b"1.5"), 1.5);
+        assert_eq!(parse_ui_scale_factor(b"10.0"), MAX_UI_SCALE_FACTOR);
+        assert_eq!(parse_ui_scale_factor(b"0.1"), MIN_UI_SCALE_FACTOR);
+        assert_eq!(parse_ui_scale_factor(b"garbage"), 1.0);
+    }
+}
@@ -0,0 +1,197 @@
+use std::rc::Rc;
+
+use winit::event::VirtualKeyCode;
+
+use super::{font, Command, DataEvent, GraphicsContext, InputState, UserEvent, Widget};
+use crate::math;
+use crate::oauth::Character;
+use crate::platform::Frame;
+
+use font::TextAnchor;
+
+pub struct CharacterPicker {
+    context: Rc<GraphicsContext>,
+    window_size: math::V2<f32>,
+    open: bool,
+    capturing_input: bool,
+    characters: Vec<Character>,
+    selected: usize,
+    text_spans: Vec<font::PositionedTextSpan>,
+    background_rect: Option<math::Rect<f32>>,
+    selected_rect: Option<math::Rect<f32>>,
+    dirty: bool,
+}
+
+impl CharacterPicker {
+    pub fn new(context: Rc<GraphicsContext>) -> Self {
+        CharacterPicker {
+            context,
+            window_size: math::v2(1024.0, 1024.0),
+            open: false,
+            capturing_input: false,
+            characters: Vec::new(),
+            selected: 0,
+            text_spans: Vec::new(),
+            background_rect: None,
+            selected_rect: None,
+            dirty: false,
+        }
+    }
+
+    pub fn capturing_input(&self) -> bool {
+        self.capturing_input
+    }
+}
+
+impl Widget for CharacterPicker {
+    fn update(
+        &mut self,
+        _dt: std::time::Duration,
+        input_state: &InputState,
+        world: &crate::world::World,
+    ) {
+        self.capturing_input = self.open;
+
+        if let Some(new_size) = input_state.window_resized() {
+            self.window_size = new_size.as_f32();
+            self.dirty = true;
+        }
+
+        for event in input_state.user_events() {
+            if let UserEvent::DataEvent(DataEvent::ProfilesLoaded) = event {
+                self.characters = world.profiles();
+                self.selected = self.selected.min(self.characters.len().saturating_sub(1));
+                self.dirty = true;
+            }
+        }
+
+        if self.open {
+            if input_state.was_key_down(VirtualKeyCode::Down) {
+                self.selected = (self.selected + 1).min(self.characters.len().saturating_sub(1));
+                self.dirty = true;
+            }
+
+            if input_state.was_key_down(VirtualKeyCode::Up) {
+                self.selected = self.selected.saturating_sub(1);
+                self.dirty = true;
+            }
+
+            if input_state.was_key_down(VirtualKeyCode::Return) {
+                if let Some(character) = self.characters.get(self.selected) {
+                    world.switch_profile(character.character_id);
+                }
+                self.open = false;
+                self.dirty = true;
+            }
+
+            if input_state.was_key_down(VirtualKeyCode::Escape)
+                || Command::SwitchCharacter.triggered(input_state)
+            {
+                self.open = false;
+                self.dirty = true;
+            }
+        } else if Command::SwitchCharacter.triggered(input_state) {
+            world.request_profiles();
+            self.open = true;
+            self.capturing_input = true;
+            self.selected = 0;
+            self.dirty = true;
+        }
+
+        if !self.dirty {
+            return;
+        }
+
+        self.text_spans.clear();
+        self.background_rect = None;
+        self.selected_rect = None;
+
+        if self.open {
+            let ui_scale = self.context.ui_scale();
+            let padding = 30.0 * ui_scale;
+            let width = 700.0 * ui_scale;
+            let white = math::V4::fill(1.0);
+            let gray = math::v4(0.5, 0.5, 0.5, 1.0);
+
+            let mut background_rect = math::Rect::new(
+                math::v2((self.window_size.x - width) / 2.0, padding),
+                math::v2((self.window_size.x + width) / 2.0, padding),
+            );
+            let mut cursor = background_rect.min + math::V2::fill(padding);
+
+            let mut title = font::TextSpan::new(40.0 * ui_scale, self.context.title_font, gray);
+            title.push("Switch Character");
+            let title = self
+                .context
+                .font_cache
+                .layout(title, TextAnchor::TopLeft, cursor, false);
+            cursor.y = title.bounds.max.y as f32 + padding / 2.0;
+            self.text_spans.push(title);
+
+            if self.characters.is_empty() {
+                let mut text = font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, gray);
+                text.push("No saved characters");
+                let text = self
+                    .context
+                    .font_cache
+                    .layout(text, TextAnchor::TopLeft, cursor, false);
+                cursor.y = text.bounds.max.y as f32;
+                self.text_spans.push(text);
+            }
+
+            for (index, character) in self.characters.iter().enumerate() {
+                let mut text = font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, white);
+                text.push(character.character_name.as_str());
+                let text = self
+                    .context
+                    .font_cache
+                    .layout(text, TextAnchor::TopLeft, cursor, false);
+
+                if index == self.selected {
+                    self.selected_rect = Some(math::Rect::new(
+                        math::v2(background_rect.min.x, cursor.y),
+                        math::v2(background_rect.max.x, text.bounds.max.y as f32),
+                    ));
+                }
+
+                cursor.y = text.bounds.max.y as f32;
+                self.text_spans.push(text);
+            }
+
+            background_rect.max.y = cursor.y + padding;
+            self.background_rect = Some(background_rect);
+        }
+
+        self.context.request_redraw("character picker dirty");
+        self.dirty = false;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        if let Some(background) = self.background_rect {
+            self.context.display.draw_quad(
+                frame,
+                &self.context.images,
+                math::v4(0.1, 0.1, 0.1, 0.95),
+                background,
+            );
+
+            if let Some(selected) = self.selected_rect {
+                self.context.display.draw_quad(
+                    frame,
+                    &self.context.images,
+                    math::v4(0.25, 0.25, 0.25, 1.0),
+                    selected,
+                );
+            }
+
+            if !self.text_spans.is_empty() {
+                self.context.display.draw_text(
+                    frame,
+                    &self.context.font_cache,
+                    &self.text_spans,
+                    self.context.ui_scale(),
+                );
+            }
+        }
+    }
+}
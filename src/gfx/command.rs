@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+use winit::event::VirtualKeyCode;
+
+use super::{InputState, UserEvent};
+use crate::settings::Settings;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Command {
+    Recenter,
+    ToggleFollowPlayer,
+    ClearRoutes,
+    TogglePockets,
+    ToggleRouteComparison,
+    CycleRoutePreference,
+    WarmRegion,
+    ToggleDistanceMetric,
+    ToggleMetrics,
+    CycleOverlay,
+    ToggleMeasure,
+    ToggleJumpRange,
+    ToggleRegionOutlines,
+    ToggleSystemNames,
+    ToggleJumpBridges,
+    ToggleWormholes,
+    ToggleHiddenJumpRouting,
+    SwitchCharacter,
+    Logout,
+    ClearCache,
+    ReloadShaders,
+}
+
+impl Command {
+    pub const ALL: &'static [Command] = &[
+        Command::Recenter,
+        Command::ToggleFollowPlayer,
+        Command::ClearRoutes,
+        Command::TogglePockets,
+        Command::ToggleRouteComparison,
+        Command::CycleRoutePreference,
+        Command::WarmRegion,
+        Command::ToggleDistanceMetric,
+        Command::ToggleMetrics,
+        Command::CycleOverlay,
+        Command::ToggleMeasure,
+        Command::ToggleJumpRange,
+        Command::ToggleRegionOutlines,
+        Command::ToggleSystemNames,
+        Command::ToggleJumpBridges,
+        Command::ToggleWormholes,
+        Command::ToggleHiddenJumpRouting,
+        Command::SwitchCharacter,
+        Command::Logout,
+        Command::ClearCache,
+        Command::ReloadShaders,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Recenter => "Center on Character",
+            Command::ToggleFollowPlayer => "Toggle Follow Character",
+            Command::ClearRoutes => "Clear Routes",
+            Command::TogglePockets => "Toggle Pockets",
+            Command::ToggleRouteComparison => "Toggle Route Comparison",
+            Command::CycleRoutePreference => "Cycle Route Preference",
+            Command::WarmRegion => "Warm Selected Region",
+            Command::ToggleDistanceMetric => "Toggle Distance Jumps/Light Years",
+            Command::ToggleMetrics => "Toggle System Metrics",
+            Command::CycleOverlay => "Cycle Map Overlay",
+            Command::ToggleMeasure => "Toggle Measure Tool",
+            Command::ToggleJumpRange => "Toggle Jump Range",
+            Command::ToggleRegionOutlines => "Toggle Region Outlines",
+            Command::ToggleSystemNames => "Toggle Always Show System Names",
+            Command::ToggleJumpBridges => "Toggle Jump Bridges",
+            Command::ToggleWormholes => "Toggle Wormholes",
+            Command::ToggleHiddenJumpRouting => "Toggle Routing Through Hidden Jumps",
+            Command::SwitchCharacter => "Switch Character",
+            Command::Logout => "Log Out and Re-authorize",
+            Command::ClearCache => "Clear ESI Cache",
+            Command::ReloadShaders => "Reload Shaders",
+        }
+    }
+
+    pub fn key(&self) -> VirtualKeyCode {
+        match self {
+            Command::Recenter => VirtualKeyCode::Home,
+            Command::ToggleFollowPlayer => VirtualKeyCode::Pause,
+            Command::ClearRoutes => VirtualKeyCode::Escape,
+            Command::TogglePockets => VirtualKeyCode::F2,
+            Command::ToggleRouteComparison => VirtualKeyCode::F3,
+            Command::CycleRoutePreference => VirtualKeyCode::F8,
+            Command::WarmRegion => VirtualKeyCode::F4,
+            Command::ToggleDistanceMetric => VirtualKeyCode::F7,
+            Command::ToggleMetrics => VirtualKeyCode::F9,
+            Command::CycleOverlay => VirtualKeyCode::F1,
+            Command::ToggleMeasure => VirtualKeyCode::F10,
+            Command::ToggleJumpRange => VirtualKeyCode::Insert,
+            Command::ToggleRegionOutlines => VirtualKeyCode::End,
+            Command::ToggleSystemNames => VirtualKeyCode::Tab,
+            Command::ToggleJumpBridges => VirtualKeyCode::PageUp,
+            Command::ToggleWormholes => VirtualKeyCode::PageDown,
+            Command::ToggleHiddenJumpRouting => VirtualKeyCode::Delete,
+            Command::SwitchCharacter => VirtualKeyCode::F11,
+            Command::Logout => VirtualKeyCode::F12,
+            Command::ClearCache => VirtualKeyCode::F5,
+            Command::ReloadShaders => VirtualKeyCode::F6,
+        }
+    }
+
+    pub fn available(&self, settings: &Settings) -> bool {
+        match self {
+            Command::ReloadShaders => settings.dev_mode,
+            Command::Logout | Command::ClearCache => !cfg!(target_arch = "wasm32"),
+            _ => true,
+        }
+    }
+
+    pub fn triggered(&self, input_state: &InputState) -> bool {
+        (!input_state.keys_captured() && input_state.was_key_down(self.key()))
+            || input_state
+                .user_events()
+                .any(|e| matches!(e, UserEvent::Command(c) if c == self))
+    }
+}
+
+pub fn fuzzy_score(search: &str, name: &str) -> Option<i32> {
+    let mut score = 0;
+    let mut last_match: Option<usize> = None;
+    let mut name_chars = name.chars().flat_map(char::to_lowercase).enumerate();
+
+    for search_char in search.chars().flat_map(char::to_lowercase) {
+        if search_char.is_whitespace() {
+            continue;
+        }
+
+        let (index, _) = name_chars.find(|(_, c)| *c == search_char)?;
+        score += match last_match {
+            Some(last) if last + 1 == index => 5,
+            None if index == 0 => 5,
+            _ => 1,
+        };
+        last_match = Some(index);
+    }
+
+    Some(score - last_match.unwrap_or(0) as i32 / 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_matches_subsequences() {
+        assert!(fuzzy_score("tp", "Toggle Pockets").is_some());
+        assert!(fuzzy_score("clear", "Clear Routes").is_some());
+        assert!(fuzzy_score("xyz", "Clear Routes").is_none());
+        assert!(fuzzy_score("clear", "Clear Routes") > fuzzy_score("cr", "Clear Routes"));
+        assert!(fuzzy_score("route", "Toggle Routing Through Hidden Jumps").is_some());
+        assert!(
+            fuzzy_score("route", "Clear Routes")
+                > fuzzy_score("route", "Toggle Routing Through Hidden Jumps")
+        );
+    }
+
+    fn key_press(input_state: &mut InputState, key: VirtualKeyCode) {
+        use winit::event::{DeviceId, ElementState, Event, KeyboardInput, WindowEvent};
+        use winit::window::WindowId;
+
+        for state in [ElementState::Pressed, ElementState::Released] {
+            #[allow(deprecated)]
+            input_state.process(Event::WindowEvent {
+                window_id: unsafe { WindowId::dummy() },
+                event: WindowEvent::KeyboardInput {
+                    device_id: unsafe { DeviceId::dummy() },
+                    input: KeyboardInput {
+                        scancode: 0,
+                        state,
+                        virtual_keycode: Some(key),
+                        modifiers: Default::default(),
+                    },
+                    is_synthetic: false,
+                },
+            });
+        }
+    }
+
+    #[test]
+    fn captured_keys_only_block_hotkeys() {
+        let mut input_state = InputState::detached(crate::math::v2(1024, 1024));
+        key_press(&mut input_state, VirtualKeyCode::Tab);
+        assert!(Command::ToggleSystemNames.triggered(&input_state));
+
+        input_state.set_keys_captured(true);
+        assert!(!Command::ToggleSystemNames.triggered(&input_state));
+
+        input_state.push_user_event(UserEvent::Command(Command::ToggleSystemNames));
+        assert!(Command::ToggleSystemNames.triggered(&input_state));
+    }
+}
@@ -0,0 +1,244 @@
+use std::rc::Rc;
+
+use winit::event::{MouseButton, VirtualKeyCode};
+
+use super::{font, GraphicsContext, InputState, MapEvent, MenuEvent, UserEvent, Widget};
+use crate::math;
+use crate::platform::Frame;
+
+use font::TextAnchor;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum MenuAction {
+    SetRouteStart,
+    SetRouteEnd,
+    SetDestination,
+    CopyName,
+}
+
+impl MenuAction {
+    const ALL: &'static [MenuAction] = &[
+        MenuAction::SetRouteStart,
+        MenuAction::SetRouteEnd,
+        MenuAction::SetDestination,
+        MenuAction::CopyName,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            MenuAction::SetRouteStart => "Set as route start",
+            MenuAction::SetRouteEnd => "Set as route end",
+            MenuAction::SetDestination => "Set destination in-game",
+            MenuAction::CopyName => "Copy name",
+        }
+    }
+
+    fn event(&self, system_id: i32) -> MenuEvent {
+        match self {
+            MenuAction::SetRouteStart => MenuEvent::SetRouteStart(system_id),
+            MenuAction::SetRouteEnd => MenuEvent::SetRouteEnd(system_id),
+            MenuAction::SetDestination => MenuEvent::SetDestination(system_id),
+            MenuAction::CopyName => MenuEvent::CopyName(system_id),
+        }
+    }
+}
+
+pub struct ContextMenu {
+    context: Rc<GraphicsContext>,
+    window_size: math::V2<f32>,
+    system: Option<(i32, math::V2<f32>)>,
+    hovered: Option<usize>,
+    rows: Vec<math::Rect<f32>>,
+    text_spans: Vec<font::PositionedTextSpan>,
+    background_rect: Option<math::Rect<f32>>,
+    capturing_pointer: bool,
+    dirty: bool,
+}
+
+impl ContextMenu {
+    pub fn new(context: Rc<GraphicsContext>) -> Self {
+        ContextMenu {
+            context,
+            window_size: math::v2(1024.0, 1024.0),
+            system: None,
+            hovered: None,
+            rows: Vec::new(),
+            text_spans: Vec::new(),
+            background_rect: None,
+            capturing_pointer: false,
+            dirty: false,
+        }
+    }
+
+    pub fn capturing_input(&self) -> bool {
+        self.system.is_some()
+    }
+
+    pub fn capturing_pointer(&self) -> bool {
+        self.capturing_pointer
+    }
+
+    fn close(&mut self) {
+        self.system = None;
+        self.hovered = None;
+        self.dirty = true;
+    }
+
+    fn layout(&mut self, name: &str, position: math::V2<f32>) -> math::Rect<f32> {
+        self.text_spans.clear();
+
+        let ui_scale = self.context.ui_scale();
+        let padding = 15.0 * ui_scale;
+        let white = math::V4::fill(1.0);
+        let gray = math::v4(0.6, 0.6, 0.6, 1.0);
+
+        let mut cursor = position + math::V2::fill(padding);
+        let mut title = font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, gray);
+        title.push(name);
+        let title = self
+            .context
+            .font_cache
+            .layout(title, TextAnchor::TopLeft, cursor, false);
+        let mut width = title.bounds.width() as f32;
+        cursor.y = title.bounds.max.y as f32 + padding / 2.0;
+        self.text_spans.push(title);
+
+        let mut row_tops = Vec::new();
+        for action in MenuAction::ALL {
+            let mut text = font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, white);
+            text.push(action.label());
+            let text = self
+                .context
+                .font_cache
+                .layout(text, TextAnchor::TopLeft, cursor, false);
+            width = width.max(text.bounds.width() as f32);
+            row_tops.push((cursor.y, text.bounds.max.y as f32));
+            cursor.y = text.bounds.max.y as f32;
+            self.text_spans.push(text);
+        }
+
+        let background_rect = math::Rect::new(
+            position,
+            math::v2(position.x + width + padding * 2.0, cursor.y + padding),
+        );
+        self.rows = row_tops
+            .into_iter()
+            .map(|(top, bottom)| {
+                math::Rect::new(
+                    math::v2(background_rect.min.x, top),
+                    math::v2(background_rect.max.x, bottom),
+                )
+            })
+            .collect();
+
+        background_rect
+    }
+}
+
+impl Widget for ContextMenu {
+    fn update(
+        &mut self,
+        _dt: std::time::Duration,
+        input_state: &InputState,
+        world: &crate::world::World,
+    ) {
+        let was_open = self.system.is_some();
+
+        if let Some(new_size) = input_state.window_resized() {
+            self.window_size = new_size.as_f32();
+            self.close();
+        }
+
+        if let Some((system_id, _)) = self.system {
+            if input_state.was_mouse_clicked(MouseButton::Left) {
+                if let Some(action) = self.hovered.and_then(|i| MenuAction::ALL.get(i)) {
+                    input_state.send_user_event(UserEvent::MenuEvent(action.event(system_id)));
+                }
+                self.close();
+            } else if input_state.was_key_down(VirtualKeyCode::Escape)
+                || input_state.was_mouse_clicked(MouseButton::Right)
+                || input_state.scroll() != 0.0
+                || input_state.scroll_pan() != math::V2::fill(0.0)
+            {
+                self.close();
+            } else if input_state.mouse_move_delta() != math::V2::fill(0.0) {
+                let mouse_position = input_state.mouse_position();
+                let hovered = self.rows.iter().position(|r| r.contains(mouse_position));
+                if hovered != self.hovered {
+                    self.hovered = hovered;
+                    self.dirty = true;
+                }
+            }
+        }
+
+        for event in input_state.user_events() {
+            if let UserEvent::MapEvent(MapEvent::SystemContextMenu(system_id)) = event {
+                self.system = Some((*system_id, input_state.mouse_position()));
+                self.hovered = None;
+                self.dirty = true;
+            }
+        }
+
+        // The click that closes the menu shouldn't also land on the map
+        self.capturing_pointer = was_open || self.system.is_some();
+
+        if !self.dirty {
+            return;
+        }
+
+        self.text_spans.clear();
+        self.rows.clear();
+        self.background_rect = None;
+
+        let menu = self
+            .system
+            .and_then(|(id, position)| world.system(id).map(|s| (s, position)));
+        if let Some((system, position)) = menu {
+            let name = system.name.clone();
+            let mut background_rect = self.layout(&name, position);
+
+            let overflow = background_rect.max - self.window_size;
+            if overflow.x > 0.0 || overflow.y > 0.0 {
+                let shifted = math::v2(
+                    position.x - overflow.x.max(0.0),
+                    position.y - overflow.y.max(0.0),
+                );
+                background_rect = self.layout(&name, shifted);
+            }
+
+            self.background_rect = Some(background_rect);
+        }
+
+        self.context.request_redraw("context menu dirty");
+        self.dirty = false;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        if let Some(background) = self.background_rect {
+            self.context.display.draw_quad(
+                frame,
+                &self.context.images,
+                math::v4(0.1, 0.1, 0.1, 0.95),
+                background,
+            );
+
+            if let Some(row) = self.hovered.and_then(|i| self.rows.get(i)) {
+                self.context.display.draw_quad(
+                    frame,
+                    &self.context.images,
+                    math::v4(0.25, 0.25, 0.25, 1.0),
+                    *row,
+                );
+            }
+
+            if !self.text_spans.is_empty() {
+                self.context.display.draw_text(
+                    frame,
+                    &self.context.font_cache,
+                    &self.text_spans,
+                    self.context.ui_scale(),
+                );
+            }
+        }
+    }
+}
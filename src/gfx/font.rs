@@ -3,7 +3,7 @@ use std::cell::{Cell, RefCell};
 
 use crate::gfx::TextVertex;
 use crate::math;
-use crate::platform::{GraphicsBackend, RgbTexture, U8};
+use crate::platform::{read_file, GraphicsBackend, RgbTexture, U8};
 
 use ahash::{AHashMap as HashMap, AHashSet as HashSet};
 use fontdue::layout::GlyphRasterConfig;
@@ -211,14 +211,18 @@ impl FontCache {
         }
     }
 
+    fn font_settings() -> fontdue::FontSettings {
+        let mut font_settings = fontdue::FontSettings::default();
+        font_settings.scale = 40.0;
+        font_settings
+    }
+
     pub fn load<F: FontData>(&mut self) -> Option<FontId> {
         let type_id = TypeId::of::<F>();
         if let Some(&font_id) = self.font_ids.get(&type_id) {
             Some(font_id)
         } else {
-            let mut font_settings = fontdue::FontSettings::default();
-            font_settings.scale = 40.0;
-            let font = Font::from_bytes(F::DATA, font_settings).ok()?;
+            let font = Font::from_bytes(F::DATA, Self::font_settings()).ok()?;
             let font_id = self.fonts.len();
             self.fonts.push(font);
             self.font_ids.insert(type_id, FontId(font_id));
@@ -227,6 +231,30 @@ impl FontCache {
         }
     }
 
+    pub async fn load_from_path<P: AsRef<std::path::Path>>(&mut self, path: P) -> Option<FontId> {
+        let path = path.as_ref();
+        let data = match read_file(path).await {
+            Ok(data) => data,
+            Err(error) => {
+                log::error!("unable to read font {}: {:?}", path.display(), error);
+                return None;
+            }
+        };
+
+        let font = match Font::from_bytes(data, Self::font_settings()) {
+            Ok(font) => font,
+            Err(error) => {
+                log::error!("unable to load font {}: {}", path.display(), error);
+                return None;
+            }
+        };
+        let font_id = self.fonts.len();
+        self.fonts.push(font);
+
+        log::info!("loaded font {}", path.display());
+        Some(FontId(font_id))
+    }
+
     pub fn texture(&self) -> &RgbTexture<U8> {
         &self.cache_texture
     }
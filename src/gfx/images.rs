@@ -1,3 +1,4 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Mutex, RwLock};
 
 use super::QuadVertex;
@@ -6,17 +7,38 @@ use crate::platform::{GraphicsBackend, SrgbTexture, U8U8U8U8};
 
 use ahash::AHashMap as HashMap;
 
+// The atlas only keeps this many mip levels, slots are padded so none of them
+// blend neighbouring images together
+pub const MIPMAP_LEVELS: u32 = 3;
+const IMAGE_GUTTER: u32 = 1 << MIPMAP_LEVELS;
+
+fn padded(len: u32) -> u32 {
+    len.div_ceil(IMAGE_GUTTER) * IMAGE_GUTTER + IMAGE_GUTTER
+}
+
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
 pub enum Image {
     AllianceLogo(i32),
 }
 
+struct Slot {
+    rect: math::Rect<u32>,
+    last_used: AtomicU64,
+}
+
+struct Cursor {
+    position: math::V2<u32>,
+    row_height: u32,
+}
+
 pub struct Images {
     cache_width: u32,
     cache_height: u32,
     cache_texture: SrgbTexture<U8U8U8U8>,
-    slots: RwLock<HashMap<Image, math::Rect<u32>>>,
-    cursor: Mutex<math::V2<u32>>,
+    slots: RwLock<HashMap<Image, Slot>>,
+    cursor: Mutex<Cursor>,
+    use_counter: AtomicU64,
+    mipmaps_stale: AtomicBool,
 }
 
 impl Images {
@@ -28,7 +50,12 @@ impl Images {
             cache_height,
             cache_texture,
             slots: RwLock::new(HashMap::new()),
-            cursor: Mutex::new(math::V2::fill(0)),
+            cursor: Mutex::new(Cursor {
+                position: math::V2::fill(0),
+                row_height: 0,
+            }),
+            use_counter: AtomicU64::new(0),
+            mipmaps_stale: AtomicBool::new(false),
         }
     }
 
@@ -36,10 +63,62 @@ impl Images {
         &self.cache_texture
     }
 
+    pub fn generate_mipmaps(&self) {
+        if self.mipmaps_stale.swap(false, Ordering::Relaxed) {
+            self.cache_texture.generate_mipmaps();
+        }
+    }
+
     pub fn contains(&self, image: Image) -> bool {
         self.slots.read().unwrap().contains_key(&image)
     }
 
+    fn allocate(
+        &self,
+        slots: &mut HashMap<Image, Slot>,
+        cursor: &mut Cursor,
+        size: math::V2<u32>,
+    ) -> Option<math::Rect<u32>> {
+        let footprint = math::v2(padded(size.x), padded(size.y));
+        if footprint.x > self.cache_width || footprint.y > self.cache_height {
+            return None;
+        }
+
+        if cursor.position.x + footprint.x > self.cache_width {
+            cursor.position.x = 0;
+            cursor.position.y += cursor.row_height;
+            cursor.row_height = 0;
+        }
+
+        if cursor.position.y + footprint.y <= self.cache_height {
+            let min = cursor.position;
+            cursor.position.x += footprint.x;
+            cursor.row_height = cursor.row_height.max(footprint.y);
+            return Some(math::Rect::new(min, min + size));
+        }
+
+        let evict = slots
+            .iter()
+            .filter(|(_, slot)| {
+                padded(slot.rect.width()) >= footprint.x
+                    && padded(slot.rect.height()) >= footprint.y
+            })
+            .min_by_key(|(_, slot)| slot.last_used.load(Ordering::Relaxed))
+            .map(|(image, _)| *image);
+
+        if let Some(evict) = evict {
+            log::debug!("image cache full, evicting {:?}", evict);
+            let slot = slots.remove(&evict).unwrap();
+            return Some(math::Rect::new(slot.rect.min, slot.rect.min + size));
+        }
+
+        log::warn!("image cache full, clearing cache");
+        slots.clear();
+        cursor.position = math::v2(footprint.x, 0);
+        cursor.row_height = footprint.y;
+        Some(math::Rect::new(math::V2::fill(0), size))
+    }
+
     pub fn load(
         &self,
         display: &GraphicsBackend,
@@ -96,37 +175,32 @@ impl Images {
         };
 
         let mut cursor = self.cursor.lock().unwrap();
-        if cursor.x + width > self.cache_width {
-            if cursor.y + height > self.cache_height {
-                Err("cache full")?;
-            } else {
-                cursor.x = 0;
-                cursor.y += height;
-            }
-        }
-
-        {
-            let cursor = cursor.clone();
-            display.update_texture(
-                self.texture(),
-                math::Rect::new(cursor, cursor + math::v2(width, height)),
-                &image_data,
-            );
-
-            let mut slots = self.slots.write().unwrap();
-            slots.insert(
-                image,
-                math::Rect::new(cursor.clone(), cursor.clone() + math::v2(width, height)),
-            );
-        }
-
-        cursor.x += width;
+        let mut slots = self.slots.write().unwrap();
+        let rect = self
+            .allocate(&mut slots, &mut cursor, math::v2(width, height))
+            .ok_or("image larger than cache")?;
+
+        display.update_texture(self.texture(), rect, &image_data);
+        self.mipmaps_stale.store(true, Ordering::Relaxed);
+        slots.insert(
+            image,
+            Slot {
+                rect,
+                last_used: AtomicU64::new(self.use_counter.fetch_add(1, Ordering::Relaxed)),
+            },
+        );
 
         Ok(())
     }
 
     pub fn draw(&self, vertex_buf: &mut Vec<QuadVertex>, image: Image, position: math::Rect<f32>) {
-        if let Some(uv_rect) = self.slots.read().unwrap().get(&image).cloned() {
+        let slots = self.slots.read().unwrap();
+        if let Some(slot) = slots.get(&image) {
+            slot.last_used.store(
+                self.use_counter.fetch_add(1, Ordering::Relaxed),
+                Ordering::Relaxed,
+            );
+            let uv_rect = slot.rect;
             for (position, uv) in position
                 .triangle_list_iter()
                 .zip(uv_rect.triangle_list_iter())
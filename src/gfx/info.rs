@@ -1,4 +1,6 @@
+use ahash::AHashMap as HashMap;
 use std::rc::Rc;
+use std::sync::Arc;
 
 use super::{
     font, images, DataEvent, GraphicsContext, InputState, MapEvent, RouteEvent, UserEvent, Widget,
@@ -16,6 +18,7 @@ pub struct InfoBox {
     text_spans: Vec<font::PositionedTextSpan>,
     background_rect: Option<math::Rect<f32>>,
     image: Option<(images::Image, math::Rect<f32>)>,
+    player_distances: Option<(i32, Arc<HashMap<i32, u32>>)>,
     dirty: bool,
 }
 
@@ -29,6 +32,7 @@ impl InfoBox {
             text_spans: Vec::new(),
             background_rect: None,
             image: None,
+            player_distances: None,
             dirty: true,
         }
     }
@@ -57,6 +61,13 @@ impl Widget for InfoBox {
                 UserEvent::DataEvent(DataEvent::ImageLoaded) => {
                     self.dirty = true;
                 }
+                UserEvent::DataEvent(DataEvent::DockedLocationChanged) => {
+                    self.dirty = true;
+                }
+                UserEvent::DataEvent(DataEvent::CharacterLocationChanged(_)) => {
+                    self.player_distances = None;
+                    self.dirty = true;
+                }
                 _ => (),
             }
         }
@@ -118,6 +129,7 @@ impl Widget for InfoBox {
             };
 
             let system_sec_color = super::sec_status_color(system.security_status).expand(1.0);
+            let security_class = world.security_class(system.system_id);
 
             let mut background_rect = math::Rect::new(
                 math::v2(self.window_size.x - padding - (650.0 * ui_scale), padding),
@@ -152,6 +164,10 @@ impl Widget for InfoBox {
                 .color(system_sec_color)
                 .push(format!("{:.2}", system.security_status))
                 .color(white)
+                .push(" ")
+                .color(super::security_class_color(security_class).expand(1.0))
+                .push(security_class.name())
+                .color(white)
                 .push(")");
             let system_sec = self.context.font_cache.layout(
                 system_sec,
@@ -240,6 +256,81 @@ impl Widget for InfoBox {
                 None
             };
 
+            let docked_location = world
+                .docked_location()
+                .filter(|_| world.location() == Some(system.system_id));
+            let docked_name = if let Some(docked_location) = docked_location {
+                let mut docked_span =
+                    font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, white);
+                docked_span.push(format!("Docked: {}", docked_location));
+                let docked =
+                    self.context
+                        .font_cache
+                        .layout(docked_span, TextAnchor::TopLeft, cursor, false);
+
+                cursor.y = docked.bounds.max.y as f32;
+
+                Some(docked)
+            } else {
+                None
+            };
+
+            let player_system = world.location().and_then(|id| world.system(id));
+            let jumps_from_player = if let Some(player_system) = player_system {
+                let distances = match self.player_distances.as_ref() {
+                    Some((id, distances)) if *id == player_system.system_id => distances.clone(),
+                    _ => {
+                        let distances = world.distances_from(player_system.system_id);
+                        self.player_distances = Some((player_system.system_id, distances.clone()));
+                        distances
+                    }
+                };
+
+                let mut jumps_span =
+                    font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, white);
+                match distances.get(&system.system_id) {
+                    Some(1) => jumps_span.push(format!("1 jump from {}", player_system.name)),
+                    Some(jumps) => {
+                        jumps_span.push(format!("{} jumps from {}", jumps, player_system.name))
+                    }
+                    None => jumps_span.push(format!("Unreachable from {}", player_system.name)),
+                };
+                let jumps =
+                    self.context
+                        .font_cache
+                        .layout(jumps_span, TextAnchor::TopLeft, cursor, false);
+
+                cursor.y = jumps.bounds.max.y as f32;
+
+                Some(jumps)
+            } else {
+                None
+            };
+
+            let nearest_hub = world
+                .nearest_hub(system.system_id)
+                .and_then(|(hub, jumps)| Some((world.system(hub)?, jumps)))
+                .map(|(hub, jumps)| {
+                    let mut hub_span =
+                        font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, white);
+                    match jumps {
+                        1 => hub_span.push(format!("Nearest hub: {}, 1 jump", hub.name)),
+                        jumps => {
+                            hub_span.push(format!("Nearest hub: {}, {} jumps", hub.name, jumps))
+                        }
+                    };
+                    let hub = self.context.font_cache.layout(
+                        hub_span,
+                        TextAnchor::TopLeft,
+                        cursor,
+                        false,
+                    );
+
+                    cursor.y = hub.bounds.max.y as f32;
+
+                    hub
+                });
+
             let stats = if let Some(stats) = stats {
                 cursor.y = cursor.y + padding;
                 let mut jumps = font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, white);
@@ -301,6 +392,15 @@ impl Widget for InfoBox {
             if let Some(corporation) = corporation_name {
                 self.text_spans.push(corporation);
             };
+            if let Some(docked) = docked_name {
+                self.text_spans.push(docked);
+            };
+            if let Some(jumps) = jumps_from_player {
+                self.text_spans.push(jumps);
+            };
+            if let Some(hub) = nearest_hub {
+                self.text_spans.push(hub);
+            };
             for stat in stats {
                 self.text_spans.push(stat);
             }
@@ -0,0 +1,210 @@
+use std::rc::Rc;
+
+use super::{font, DataEvent, GraphicsContext, InputState, LoadStage, UserEvent, Widget};
+use crate::math;
+use crate::platform::Frame;
+
+use font::TextAnchor;
+
+const LOADING_WIDTH: f32 = 700.0;
+const LOADING_BAR_HEIGHT: f32 = 12.0;
+
+fn progress_fraction(done: usize, total: usize) -> f32 {
+    if total == 0 {
+        1.0
+    } else {
+        (done as f32 / total as f32).min(1.0)
+    }
+}
+
+pub struct LoadingProgress {
+    context: Rc<GraphicsContext>,
+    window_size: math::V2<f32>,
+    stages: Vec<(LoadStage, usize, usize)>,
+    loaded: bool,
+    error: Option<String>,
+    text_spans: Vec<font::PositionedTextSpan>,
+    bars: Vec<(math::Rect<f32>, math::Rect<f32>)>,
+    background_rect: Option<math::Rect<f32>>,
+    dirty: bool,
+}
+
+impl LoadingProgress {
+    pub fn new(context: Rc<GraphicsContext>) -> Self {
+        LoadingProgress {
+            context,
+            window_size: math::v2(1024.0, 1024.0),
+            stages: Vec::new(),
+            loaded: false,
+            error: None,
+            text_spans: Vec::new(),
+            bars: Vec::new(),
+            background_rect: None,
+            dirty: true,
+        }
+    }
+}
+
+impl Widget for LoadingProgress {
+    fn update(
+        &mut self,
+        _dt: std::time::Duration,
+        input_state: &InputState,
+        _world: &crate::world::World,
+    ) {
+        for event in input_state.user_events() {
+            match event {
+                UserEvent::DataEvent(DataEvent::LoadProgress { stage, done, total }) => {
+                    match self.stages.iter_mut().find(|(s, _, _)| s == stage) {
+                        Some(progress) => *progress = (*stage, *done, *total),
+                        None => self.stages.push((*stage, *done, *total)),
+                    }
+                    self.dirty = true;
+                }
+                UserEvent::DataEvent(DataEvent::GalaxyLoadFailed(error)) => {
+                    self.error = Some(error.clone());
+                    self.stages.clear();
+                    self.dirty = true;
+                }
+                UserEvent::DataEvent(DataEvent::GalaxyImported) => {
+                    self.loaded = true;
+                    self.stages.clear();
+                    self.dirty = true;
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(new_size) = input_state.window_resized() {
+            self.window_size = new_size.as_f32();
+            self.dirty = true;
+        }
+
+        if !self.dirty {
+            return;
+        }
+
+        self.text_spans.clear();
+        self.bars.clear();
+        self.background_rect = None;
+
+        if !self.loaded {
+            let ui_scale = self.context.ui_scale();
+            let padding = 30.0 * ui_scale;
+            let width = LOADING_WIDTH * ui_scale;
+            let bar_height = LOADING_BAR_HEIGHT * ui_scale;
+            let white = math::V4::fill(1.0);
+
+            let left = (self.window_size.x - width) / 2.0;
+            let top = self.window_size.y / 3.0;
+            let mut cursor = math::v2(left + padding, top + padding);
+
+            let mut title = font::TextSpan::new(40.0 * ui_scale, self.context.ui_font, white);
+            if self.error.is_some() {
+                title.push("Unable to Load Galaxy");
+            } else {
+                title.push("Loading Galaxy");
+            }
+            let title = self
+                .context
+                .font_cache
+                .layout(title, TextAnchor::TopLeft, cursor, false);
+            cursor.y = title.bounds.max.y as f32;
+            self.text_spans.push(title);
+
+            if let Some(error) = self.error.as_ref() {
+                cursor.y += padding / 2.0;
+                let mut text = font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, white);
+                text.push(error);
+                let text = self
+                    .context
+                    .font_cache
+                    .layout(text, TextAnchor::TopLeft, cursor, false);
+                cursor.y = text.bounds.max.y as f32;
+                self.text_spans.push(text);
+            }
+
+            for (stage, done, total) in self.stages.iter() {
+                cursor.y += padding / 2.0;
+                let mut text = font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, white);
+                text.push(format!("{} {} / {}", stage.name(), done, total));
+                let text = self
+                    .context
+                    .font_cache
+                    .layout(text, TextAnchor::TopLeft, cursor, false);
+                cursor.y = text.bounds.max.y as f32 + padding / 4.0;
+                self.text_spans.push(text);
+
+                let bar_width = width - padding * 2.0;
+                let track = math::Rect::new(
+                    cursor,
+                    math::v2(cursor.x + bar_width, cursor.y + bar_height),
+                );
+                let fill = math::Rect::new(
+                    cursor,
+                    math::v2(
+                        cursor.x + bar_width * progress_fraction(*done, *total),
+                        cursor.y + bar_height,
+                    ),
+                );
+                self.bars.push((track, fill));
+                cursor.y += bar_height;
+            }
+
+            self.background_rect = Some(math::Rect::new(
+                math::v2(left, top),
+                math::v2(left + width, cursor.y + padding),
+            ));
+        }
+
+        self.context.request_redraw("loading progress dirty");
+        self.dirty = false;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        if let Some(background) = self.background_rect {
+            self.context.display.draw_quad(
+                frame,
+                &self.context.images,
+                math::v4(0.1, 0.1, 0.1, 0.85),
+                background,
+            );
+
+            for (track, fill) in self.bars.iter() {
+                self.context.display.draw_quad(
+                    frame,
+                    &self.context.images,
+                    math::v4(0.25, 0.25, 0.25, 1.0),
+                    *track,
+                );
+                self.context.display.draw_quad(
+                    frame,
+                    &self.context.images,
+                    math::v4(0.3, 0.6, 1.0, 1.0),
+                    *fill,
+                );
+            }
+
+            if !self.text_spans.is_empty() {
+                self.context.display.draw_text(
+                    frame,
+                    &self.context.font_cache,
+                    &self.text_spans,
+                    self.context.ui_scale(),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_fraction_is_clamped() {
+        assert_eq!(progress_fraction(0, 0), 1.0);
+        assert_eq!(progress_fraction(50, 200), 0.25);
+        assert_eq!(progress_fraction(300, 200), 1.0);
+    }
+}
@@ -1,16 +1,98 @@
 use crate::math;
-use crate::platform::{Buffer, Frame};
-use crate::world::{JumpType, World};
+use crate::paths::{Paths, MAP_VIEW_FILE};
+use crate::platform::{block_on, file_exists, read_file, write_file, Buffer, Frame};
+use crate::world::{FocusSummary, JumpType, Stats, World, METERS_PER_LIGHT_YEAR};
 
 use super::{
-    font, CircleVertex, DataEvent, GraphicsContext, InputState, LineVertex, MapEvent, MouseButton,
-    QueryEvent, SystemData, UserEvent, VirtualKeyCode, Widget,
+    font, CircleVertex, Command, DataEvent, GraphicsContext, InputState, LineVertex, MapEvent,
+    MouseButton, QueryEvent, RegionOrConstellation, Severity, SystemData, UserEvent,
+    VirtualKeyCode, Widget,
 };
 
+use std::borrow::Cow;
 use std::rc::Rc;
 use std::time::Duration;
 
 use ahash::{AHashMap as HashMap, AHashSet as HashSet};
+use serde::{Deserialize, Serialize};
+
+const FULL_SYSTEM_NAME_ZOOM: f32 = 20.0;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 100.0;
+const KEY_PAN_SPEED: f32 = 1.5;
+const CONSTELLATION_NAME_MIN_ZOOM: f32 = 3.0;
+const CONSTELLATION_NAME_MAX_ZOOM: f32 = 9.0;
+const CONSTELLATION_NAME_FADE: f32 = 1.0;
+const ROUTE_LABEL_MIN_ZOOM: f32 = 4.0;
+const ROUTE_LABEL_FULL_ZOOM: f32 = 8.0;
+const DEFAULT_JUMP_RANGE: f64 = 7.0;
+const MIN_JUMP_RANGE: f64 = 0.5;
+const MAX_JUMP_RANGE: f64 = 10.0;
+const JUMP_RANGE_STEP: f64 = 0.5;
+const JUMP_RANGE_SEGMENTS: usize = 64;
+const LOCATION_PING_DURATION: f32 = 1.0;
+const LOCATION_PING_SCALE: f32 = 24.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct MapView {
+    zoom: f32,
+    offset_x: f32,
+    offset_y: f32,
+}
+
+impl MapView {
+    fn parse(bytes: &[u8]) -> Option<MapView> {
+        let view: MapView = match serde_json::from_slice(bytes) {
+            Ok(view) => view,
+            Err(error) => {
+                log::error!("unable to parse {}: {:?}", MAP_VIEW_FILE, error);
+                return None;
+            }
+        };
+
+        let valid = view.zoom >= MIN_ZOOM
+            && view.zoom <= MAX_ZOOM
+            && view.offset_x.is_finite()
+            && view.offset_y.is_finite();
+
+        if valid {
+            Some(view)
+        } else {
+            log::error!("ignoring invalid map view: {:?}", view);
+            None
+        }
+    }
+
+    async fn load(paths: &Paths) -> Option<MapView> {
+        let path = paths.map_view_path();
+        if !file_exists(&path) {
+            return None;
+        }
+
+        match read_file(&path).await {
+            Ok(bytes) => MapView::parse(&bytes),
+            Err(error) => {
+                log::error!("unable to read {}: {:?}", path.display(), error);
+                None
+            }
+        }
+    }
+
+    async fn save(&self, paths: &Paths) {
+        let bytes = match serde_json::to_vec(self) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                log::error!("unable to serialize map view: {:?}", error);
+                return;
+            }
+        };
+
+        let path = paths.map_view_path();
+        if let Err(error) = write_file(&path, bytes).await {
+            log::error!("unable to save {}: {:?}", path.display(), error);
+        }
+    }
+}
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 enum RegionNamesLayer {
@@ -18,6 +100,231 @@ enum RegionNamesLayer {
     Background,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum DistanceMetric {
+    Jumps,
+    LightYears,
+}
+
+impl DistanceMetric {
+    fn max_distance(&self) -> f64 {
+        match self {
+            DistanceMetric::Jumps => 20.0,
+            DistanceMetric::LightYears => 10.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum MapOverlay {
+    None,
+    ShipKills,
+    PodKills,
+    NpcKills,
+    Jumps,
+    Security,
+    Sovereignty,
+    Standings,
+    Chokepoints,
+    Incursions,
+    FactionWarfare,
+}
+
+impl MapOverlay {
+    fn next(&self) -> MapOverlay {
+        match self {
+            MapOverlay::None => MapOverlay::ShipKills,
+            MapOverlay::ShipKills => MapOverlay::PodKills,
+            MapOverlay::PodKills => MapOverlay::NpcKills,
+            MapOverlay::NpcKills => MapOverlay::Jumps,
+            MapOverlay::Jumps => MapOverlay::Security,
+            MapOverlay::Security => MapOverlay::Sovereignty,
+            MapOverlay::Sovereignty => MapOverlay::Standings,
+            MapOverlay::Standings => MapOverlay::Chokepoints,
+            MapOverlay::Chokepoints => MapOverlay::Incursions,
+            MapOverlay::Incursions => MapOverlay::FactionWarfare,
+            MapOverlay::FactionWarfare => MapOverlay::None,
+        }
+    }
+
+    fn stat(&self, stats: &Stats) -> Option<i32> {
+        match self {
+            MapOverlay::ShipKills => Some(stats.ship_kills),
+            MapOverlay::PodKills => Some(stats.pod_kills),
+            MapOverlay::NpcKills => Some(stats.npc_kills),
+            MapOverlay::Jumps => Some(stats.jumps),
+            _ => None,
+        }
+    }
+
+    fn uses_stats(&self) -> bool {
+        matches!(
+            self,
+            MapOverlay::ShipKills | MapOverlay::PodKills | MapOverlay::NpcKills | MapOverlay::Jumps
+        )
+    }
+}
+
+fn faction_color(faction_id: i32) -> math::V3<f32> {
+    match faction_id {
+        500001 => math::v3(0.2, 0.5, 1.0),
+        500002 => math::v3(0.9, 0.2, 0.1),
+        500003 => math::v3(1.0, 0.8, 0.2),
+        500004 => math::v3(0.2, 0.8, 0.3),
+        _ => math::V3::fill(0.6),
+    }
+}
+
+fn fw_system_color(occupier_faction_id: i32, victory_points: i32, threshold: i32) -> math::V3<f32> {
+    let contested = if threshold > 0 {
+        (victory_points as f32 / threshold as f32).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let color = faction_color(occupier_faction_id);
+    color + (math::V3::fill(1.0) - color) * (contested * 0.7)
+}
+
+fn log_scale(value: i32, max: i32) -> f64 {
+    if value <= 0 || max <= 0 {
+        0.0
+    } else {
+        ((value as f64).ln_1p() / (max as f64).ln_1p()).min(1.0)
+    }
+}
+
+fn constellation_name_alpha(zoom: f32) -> f32 {
+    let fade_in = (zoom - CONSTELLATION_NAME_MIN_ZOOM) / CONSTELLATION_NAME_FADE;
+    let fade_out = (CONSTELLATION_NAME_MAX_ZOOM - zoom) / (CONSTELLATION_NAME_FADE * 2.0);
+    fade_in.min(fade_out).clamp(0.0, 1.0)
+}
+
+fn line_quad(left: math::V2<f32>, right: math::V2<f32>, color: math::V3<f32>) -> [LineVertex; 4] {
+    let left_norm = math::v2(-(left.y - right.y), left.x - right.x).normalize();
+    let right_norm = math::v2(left.y - right.y, -(left.x - right.x)).normalize();
+    let left = left.expand(1.0);
+    let right = right.expand(1.0);
+
+    [
+        LineVertex {
+            position: left,
+            color,
+            normal: left_norm,
+        },
+        LineVertex {
+            position: right,
+            color,
+            normal: right_norm,
+        },
+        LineVertex {
+            position: left,
+            color,
+            normal: right_norm,
+        },
+        LineVertex {
+            position: right,
+            color,
+            normal: left_norm,
+        },
+    ]
+}
+
+fn location_ping_style(elapsed: f32) -> Option<(f32, f32)> {
+    let progress = elapsed / LOCATION_PING_DURATION;
+    if progress >= 1.0 {
+        return None;
+    }
+
+    let scale = 4.0 + (LOCATION_PING_SCALE - 4.0) * progress.sqrt();
+    let alpha = (1.0 - progress).powi(2);
+    Some((scale, alpha))
+}
+
+fn ring_vertexes(center: math::V2<f32>, radius: f32, color: math::V3<f32>) -> Vec<LineVertex> {
+    let point = |i: usize| {
+        let n = ((2.0 * std::f32::consts::PI) / JUMP_RANGE_SEGMENTS as f32) * i as f32;
+        center + math::v2(n.sin(), n.cos()) * radius
+    };
+
+    (0..JUMP_RANGE_SEGMENTS)
+        .flat_map(|i| line_quad(point(i), point(i + 1), color))
+        .collect()
+}
+
+fn convex_hull(mut points: Vec<math::V2<f32>>) -> Vec<math::V2<f32>> {
+    points.sort_by(|a, b| {
+        a.x.partial_cmp(&b.x)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.y.partial_cmp(&b.y).unwrap_or(std::cmp::Ordering::Equal))
+    });
+    points.dedup();
+
+    if points.len() < 3 {
+        return points;
+    }
+
+    let push_hull = |hull: &mut Vec<math::V2<f32>>, point: math::V2<f32>| {
+        while let [.., o, a] = hull[..] {
+            if (a.x - o.x) * (point.y - o.y) - (a.y - o.y) * (point.x - o.x) > 0.0 {
+                break;
+            }
+            hull.pop();
+        }
+        hull.push(point);
+    };
+
+    let mut lower = Vec::new();
+    for point in points.iter() {
+        push_hull(&mut lower, *point);
+    }
+
+    let mut upper = Vec::new();
+    for point in points.iter().rev() {
+        push_hull(&mut upper, *point);
+    }
+
+    lower.pop();
+    upper.pop();
+    lower.extend(upper);
+    lower
+}
+
+pub(super) fn window_scale(window_size: math::V2<f32>) -> math::V2<f32> {
+    if window_size.x > window_size.y {
+        math::v2(window_size.x / window_size.y, 1.0)
+    } else if window_size.y > window_size.x {
+        math::v2(1.0, window_size.y / window_size.x)
+    } else {
+        math::v2(1.0, 1.0)
+    }
+}
+
+fn screen_to_ndc(screen: math::V2<f32>, window_size: math::V2<f32>) -> math::V2<f32> {
+    let half_size = window_size / 2.0;
+    let ndc = (screen - half_size) / half_size;
+    math::v2(ndc.x, -ndc.y)
+}
+
+fn screen_to_map(
+    screen: math::V2<f32>,
+    window_size: math::V2<f32>,
+    zoom: f32,
+    offset: math::V2<f32>,
+) -> math::V2<f32> {
+    let ndc = screen_to_ndc(screen, window_size) * window_scale(window_size) / zoom;
+    math::v2(ndc.x + offset.x, ndc.y - offset.y)
+}
+
+fn anchored_offset(
+    map_point: math::V2<f32>,
+    screen: math::V2<f32>,
+    window_size: math::V2<f32>,
+    zoom: f32,
+) -> math::V2<f32> {
+    let ndc = screen_to_ndc(screen, window_size) * window_scale(window_size) / zoom;
+    math::v2(map_point.x - ndc.x, ndc.y - map_point.y)
+}
+
 struct MapSystem {
     system_id: i32,
     name: String,
@@ -26,11 +333,19 @@ struct MapSystem {
     sovereignty_standing: Option<f64>,
 }
 
+struct Measurement {
+    from: i32,
+    to: Option<i32>,
+    jumps: Option<u32>,
+    light_years: Option<f64>,
+}
+
 struct MapJump {
     left_system_id: i32,
     right_system_id: i32,
     jump_type: JumpType,
-    on_route: bool,
+    restricted: bool,
+    route: Option<usize>,
 }
 
 pub struct Map {
@@ -41,22 +356,58 @@ pub struct Map {
     jump_vertexes: Option<Vec<LineVertex>>,
     selected_system: Option<i32>,
     focused_systems: HashSet<i32>,
+    filter: Option<RegionOrConstellation>,
+    filtered_systems: Option<HashSet<i32>>,
+    focus_summary: Option<FocusSummary>,
+    focus_marker_rect: Option<math::Rect<f32>>,
+    focus_summary_text: Vec<font::PositionedTextSpan>,
+    show_pockets: bool,
+    show_metrics: bool,
+    force_system_names: bool,
+    measure_mode: bool,
+    measurement: Option<Measurement>,
+    measure_vertex_buffer: Option<Buffer<LineVertex>>,
+    measure_text: Vec<font::PositionedTextSpan>,
+    show_region_outlines: bool,
+    hidden_jumps: HashSet<JumpType>,
+    region_outlines: Option<HashMap<i32, Vec<math::V2<f32>>>>,
+    region_outline_buffer: Option<Buffer<LineVertex>>,
+    jump_range_mode: bool,
+    jump_range_ly: f64,
+    jump_range: Option<(i32, f64, HashSet<i32>)>,
+    jump_range_vertex_buffer: Option<Buffer<LineVertex>>,
+    jump_range_text: Vec<font::PositionedTextSpan>,
     systems_vertex_buffer: Option<Buffer<SystemData>>,
     jumps_vertex_buffer: Option<Buffer<LineVertex>>,
+    location_ping: Option<f32>,
+    location_ping_buffer: Option<Buffer<SystemData>>,
     current_zoom: f32,
     target_zoom: f32,
     scale_matrix: math::M3<f32>,
     view_matrix: math::M3<f32>,
     window_size: math::V2<f32>,
     map_offset: math::V2<f32>,
+    target_offset: math::V2<f32>,
+    follow_player: bool,
+    capturing_input: bool,
+    capturing_pointer: bool,
+    zoom_anchor: Option<(math::V2<f32>, math::V2<f32>)>,
+    reported_view: Option<(f32, math::V2<f32>)>,
+    selection_pinned: bool,
     system_magnitude: f64,
     region_names: Vec<font::PositionedTextSpan>,
     region_names_layer: Option<RegionNamesLayer>,
+    constellation_names: Vec<font::PositionedTextSpan>,
+    route_labels: Vec<font::PositionedTextSpan>,
     system_names: Vec<font::PositionedTextSpan>,
+    scale_bar_rect: Option<math::Rect<f32>>,
+    scale_bar_text: Vec<font::PositionedTextSpan>,
     player_location: Option<i32>,
     sov_vertexes: Option<Vec<SystemData>>,
     sov_vertex_buffer: Option<Buffer<SystemData>>,
-    distance_map: Option<(i32, HashMap<i32, u32>)>,
+    overlay: MapOverlay,
+    distance_metric: DistanceMetric,
+    distance_map: Option<(i32, DistanceMetric, HashMap<i32, f64>)>,
     circle_buffer: Buffer<CircleVertex>,
 }
 
@@ -76,6 +427,13 @@ impl Map {
 
         let circle_buffer = context.display.fill_buffer(&circle_verts);
 
+        let view = block_on(MapView::load(&context.paths)).unwrap_or(MapView {
+            zoom: 1.0,
+            offset_x: 0.0,
+            offset_y: 0.0,
+        });
+        let offset = math::v2(view.offset_x, view.offset_y);
+
         Map {
             context,
             map_systems: None,
@@ -84,67 +442,202 @@ impl Map {
             jump_vertexes: None,
             selected_system: None,
             focused_systems: HashSet::new(),
+            filter: None,
+            filtered_systems: None,
+            focus_summary: None,
+            focus_marker_rect: None,
+            focus_summary_text: Vec::new(),
+            show_pockets: false,
+            show_metrics: false,
+            force_system_names: false,
+            measure_mode: false,
+            measurement: None,
+            measure_vertex_buffer: None,
+            measure_text: Vec::new(),
+            show_region_outlines: false,
+            hidden_jumps: HashSet::new(),
+            region_outlines: None,
+            region_outline_buffer: None,
+            jump_range_mode: false,
+            jump_range_ly: DEFAULT_JUMP_RANGE,
+            jump_range: None,
+            jump_range_vertex_buffer: None,
+            jump_range_text: Vec::new(),
             systems_vertex_buffer: None,
             jumps_vertex_buffer: None,
-            current_zoom: 1.0,
-            target_zoom: 1.0,
+            location_ping: None,
+            location_ping_buffer: None,
+            current_zoom: view.zoom,
+            target_zoom: view.zoom,
             scale_matrix: math::M3::identity(),
             view_matrix: math::M3::identity(),
             window_size: math::v2(1024.0, 1024.0),
-            map_offset: math::V2::fill(0.0),
+            map_offset: offset,
+            target_offset: offset,
+            follow_player: false,
+            capturing_input: false,
+            capturing_pointer: false,
+            zoom_anchor: None,
+            reported_view: None,
+            selection_pinned: false,
             system_magnitude: 0.0,
             region_names: Vec::new(),
             region_names_layer: Some(RegionNamesLayer::Foreground),
+            constellation_names: Vec::new(),
+            route_labels: Vec::new(),
             system_names: Vec::new(),
+            scale_bar_rect: None,
+            scale_bar_text: Vec::new(),
             player_location: None,
             sov_vertexes: None,
             sov_vertex_buffer: None,
+            overlay: MapOverlay::Sovereignty,
+            distance_metric: DistanceMetric::Jumps,
             distance_map: None,
             circle_buffer,
         }
     }
+
+    pub fn set_capturing_input(&mut self, capturing_input: bool) {
+        self.capturing_input = capturing_input;
+    }
+
+    pub fn measuring(&self) -> bool {
+        self.measurement.is_some()
+    }
+
+    // Set while another widget is under the cursor so clicks and drags aren't handled twice
+    pub fn set_capturing_pointer(&mut self, capturing_pointer: bool) {
+        self.capturing_pointer = capturing_pointer;
+    }
+
+    fn is_visible(&self, system_id: i32) -> bool {
+        self.filtered_systems
+            .as_ref()
+            .map(|s| s.contains(&system_id))
+            .unwrap_or(true)
+    }
+
+    fn center_on_system(&mut self, system_id: i32) {
+        let system = self.map_systems.as_ref().and_then(|s| s.get(&system_id));
+        if let Some(system) = system {
+            self.target_offset = math::v2(system.position.x, -system.position.y);
+            self.target_zoom = self.target_zoom.max(FULL_SYSTEM_NAME_ZOOM);
+            self.zoom_anchor = None;
+        }
+    }
+
+    pub fn save_view(&self) {
+        let view = MapView {
+            zoom: self.target_zoom,
+            offset_x: self.target_offset.x,
+            offset_y: self.target_offset.y,
+        };
+        block_on(view.save(&self.context.paths));
+    }
 }
 
 impl Widget for Map {
-    fn update(&mut self, _dt: Duration, input_state: &InputState, world: &World) {
+    fn update(&mut self, dt: Duration, input_state: &InputState, world: &World) {
+        let mut labels_changed = false;
         for event in input_state.user_events() {
             match event {
                 UserEvent::DataEvent(DataEvent::CharacterLocationChanged(location)) => {
+                    if location.is_some() && *location != self.player_location {
+                        self.location_ping = Some(0.0);
+                    }
                     self.player_location = location.clone();
                     self.system_vertexes = None;
                 }
                 UserEvent::DataEvent(DataEvent::SovStandingsChanged) => {
                     self.map_systems = None;
+                    self.map_jumps = None;
+                }
+                UserEvent::DataEvent(DataEvent::SystemStatsChanged)
+                    if self.overlay.uses_stats() =>
+                {
+                    self.system_vertexes = None;
+                }
+                UserEvent::DataEvent(DataEvent::IncursionsChanged)
+                    if self.overlay == MapOverlay::Incursions =>
+                {
+                    self.system_vertexes = None;
+                }
+                UserEvent::DataEvent(DataEvent::FactionWarfareChanged)
+                    if self.overlay == MapOverlay::FactionWarfare =>
+                {
+                    self.system_vertexes = None;
+                }
+                UserEvent::DataEvent(DataEvent::SystemMetricsChanged) if self.show_metrics => {
+                    self.system_vertexes = None;
+                    labels_changed = true;
+                }
+                UserEvent::QueryEvent(QueryEvent::FilterChanged(filter)) => {
+                    self.filter = *filter;
+                    self.filtered_systems = None;
+                    self.system_vertexes = None;
+                    self.jump_vertexes = None;
+                    labels_changed = true;
                 }
                 UserEvent::QueryEvent(QueryEvent::RouteChanged) => {
                     self.map_jumps = None;
+                    labels_changed = true;
                 }
                 UserEvent::QueryEvent(QueryEvent::SystemsFocused(systems)) => {
                     self.focused_systems = systems.clone();
+                    self.focus_summary = if systems.len() > 1 {
+                        world.focus_summary(systems)
+                    } else {
+                        None
+                    };
                     self.system_vertexes = None;
+                    labels_changed = true;
+                }
+                UserEvent::MapEvent(MapEvent::SelectedSystemChanged(system))
+                    if *system != self.selected_system =>
+                {
+                    self.selected_system = *system;
+                    self.selection_pinned = true;
+                    self.system_vertexes = None;
+                    self.jump_vertexes = None;
+                }
+                UserEvent::MapEvent(MapEvent::CenterOnSystem(system_id)) => {
+                    self.center_on_system(*system_id);
+                }
+                UserEvent::MapEvent(MapEvent::CenterOn { x, y }) => {
+                    self.target_offset = math::v2(*x, -*y);
+                    self.zoom_anchor = None;
                 }
                 UserEvent::DataEvent(DataEvent::GalaxyImported) => {
                     self.map_systems = None;
                     self.map_jumps = None;
+                    self.region_outlines = None;
+                    self.region_outline_buffer = None;
+                    self.filtered_systems = None;
                 }
                 _ => (),
             }
         }
 
-        let mut text_dirty = false;
+        let mut text_dirty = labels_changed;
+
+        if let Some(filter) = self.filter.filter(|_| self.filtered_systems.is_none()) {
+            let systems = match filter {
+                RegionOrConstellation::Region(id) => world.systems_in_region(id),
+                RegionOrConstellation::Constellation(id) => world.systems_in_constellation(id),
+            };
+            self.filtered_systems = Some(systems.into_iter().collect());
+            self.system_vertexes = None;
+            self.jump_vertexes = None;
+            text_dirty = true;
+        }
 
         if let Some(new_size) = input_state.window_resized() {
             self.window_size = new_size.as_f32();
             text_dirty = true;
         }
 
-        let window_scale = if self.window_size.x > self.window_size.y {
-            math::v2(self.window_size.x / self.window_size.y, 1.0)
-        } else if self.window_size.y > self.window_size.x {
-            math::v2(1.0, self.window_size.y / self.window_size.x)
-        } else {
-            math::v2(1.0, 1.0)
-        };
+        let window_scale = window_scale(self.window_size);
 
         let window_ratio = if self.window_size.x > self.window_size.y {
             math::v2(self.window_size.y / self.window_size.x, 1.0)
@@ -154,11 +647,57 @@ impl Widget for Map {
             math::v2(1.0, 1.0)
         };
 
-        self.target_zoom += (self.target_zoom * input_state.scroll()) / -20.0;
-        if self.target_zoom < 0.25 {
-            self.target_zoom = 0.25;
-        } else if self.target_zoom > 100.0 {
-            self.target_zoom = 100.0;
+        if Command::Recenter.triggered(input_state) {
+            let player_system = world
+                .location()
+                .and_then(|id| self.map_systems.as_ref().and_then(|s| s.get(&id)));
+            if let Some(system) = player_system {
+                self.target_offset = math::v2(system.position.x, -system.position.y);
+                self.target_zoom = self.target_zoom.max(15.0);
+                self.zoom_anchor = None;
+            } else {
+                log::warn!("unable to center map, character location unknown");
+                input_state.send_user_event(UserEvent::Notice(
+                    "Character location unknown".to_string(),
+                    Severity::Warning,
+                ));
+            }
+        }
+
+        let adjusting_range = self.jump_range_mode
+            && (input_state.is_key_down(VirtualKeyCode::LControl)
+                || input_state.is_key_down(VirtualKeyCode::RControl));
+        let scroll = if self.capturing_pointer {
+            0.0
+        } else if adjusting_range {
+            if input_state.scroll() != 0.0 {
+                self.jump_range_ly = (self.jump_range_ly
+                    + JUMP_RANGE_STEP * input_state.scroll().signum() as f64)
+                    .clamp(MIN_JUMP_RANGE, MAX_JUMP_RANGE);
+            }
+            // control + touchpad scroll reads as a pinch, which shouldn't zoom while control
+            // is held for the range
+            0.0
+        } else {
+            input_state.scroll() + input_state.pinch_delta()
+        };
+
+        if scroll != 0.0 && !self.follow_player {
+            let mouse_position = input_state.mouse_position();
+            let map_point = screen_to_map(
+                mouse_position,
+                self.window_size,
+                self.current_zoom,
+                self.map_offset,
+            );
+            self.zoom_anchor = Some((map_point, mouse_position));
+        }
+
+        self.target_zoom += (self.target_zoom * scroll) / -20.0;
+        if self.target_zoom < MIN_ZOOM {
+            self.target_zoom = MIN_ZOOM;
+        } else if self.target_zoom > MAX_ZOOM {
+            self.target_zoom = MAX_ZOOM;
         }
 
         let zoom_diff = (self.current_zoom - self.target_zoom).abs() / 10.0;
@@ -168,20 +707,129 @@ impl Widget for Map {
             } else if self.target_zoom < self.current_zoom {
                 self.current_zoom -= zoom_diff.min(self.current_zoom / 20.0);
             }
+            self.context.request_animation_frame("map zoom");
             text_dirty = true;
         } else if self.current_zoom != self.target_zoom {
             self.current_zoom = self.target_zoom;
             text_dirty = true;
         }
 
-        if input_state.is_mouse_down(MouseButton::Left)
+        if let Some((map_point, screen)) = self.zoom_anchor {
+            self.map_offset =
+                anchored_offset(map_point, screen, self.window_size, self.current_zoom);
+            self.target_offset = self.map_offset;
+            if self.current_zoom == self.target_zoom {
+                self.zoom_anchor = None;
+            }
+        }
+
+        if !self.capturing_pointer
+            && input_state.is_mouse_down(MouseButton::Left)
             && input_state.mouse_move_delta() != math::V2::fill(0.0)
         {
             self.map_offset = self.map_offset
                 + ((input_state.mouse_move_delta() * 2.0) / self.window_size)
                     / window_ratio
                     / self.current_zoom;
+            self.target_offset = self.map_offset;
+            self.zoom_anchor = None;
+            self.follow_player = false;
+            text_dirty = true;
+        }
+
+        let scroll_pan = input_state.scroll_pan();
+        if !self.capturing_pointer && scroll_pan != math::V2::fill(0.0) {
+            self.map_offset = self.map_offset
+                - ((scroll_pan * 2.0) / self.window_size) / window_ratio / self.current_zoom;
+            self.target_offset = self.map_offset;
+            self.zoom_anchor = None;
+            self.follow_player = false;
+            text_dirty = true;
+        }
+
+        if !self.capturing_input {
+            let mut pan = math::V2::fill(0.0);
+            if input_state.is_key_down(VirtualKeyCode::Left) {
+                pan.x -= 1.0;
+            }
+            if input_state.is_key_down(VirtualKeyCode::Right) {
+                pan.x += 1.0;
+            }
+            if input_state.is_key_down(VirtualKeyCode::Up) {
+                pan.y -= 1.0;
+            }
+            if input_state.is_key_down(VirtualKeyCode::Down) {
+                pan.y += 1.0;
+            }
+
+            if pan != math::V2::fill(0.0) {
+                self.map_offset +=
+                    pan * (KEY_PAN_SPEED * dt.as_secs_f32()) / window_ratio / self.current_zoom;
+                self.target_offset = self.map_offset;
+                self.zoom_anchor = None;
+                self.follow_player = false;
+                self.context.request_animation_frame("map key pan");
+                text_dirty = true;
+            }
+        }
+
+        if Command::ToggleFollowPlayer.triggered(input_state) {
+            self.follow_player = !self.follow_player;
+            log::info!("follow character: {}", self.follow_player);
+        }
+
+        if self.follow_player {
+            let player_position = self
+                .player_location
+                .and_then(|id| self.map_systems.as_ref()?.get(&id))
+                .map(|system| system.position);
+            if let Some(position) = player_position {
+                self.target_offset = math::v2(position.x, -position.y);
+                self.zoom_anchor = None;
+            }
+        }
+
+        let offset_diff = self.target_offset - self.map_offset;
+        if offset_diff.magnitude() > 0.0001 / self.current_zoom {
+            self.map_offset += offset_diff / 5.0;
+            self.context.request_animation_frame("map pan");
+            text_dirty = true;
+        } else if self.map_offset != self.target_offset {
+            self.map_offset = self.target_offset;
+            text_dirty = true;
+        }
+
+        if Command::TogglePockets.triggered(input_state) {
+            self.show_pockets = !self.show_pockets;
+            self.system_vertexes = None;
+        }
+
+        if Command::CycleOverlay.triggered(input_state) {
+            self.overlay = self.overlay.next();
+            log::info!("map overlay: {:?}", self.overlay);
+            self.system_vertexes = None;
+            self.sov_vertexes = None;
+            self.sov_vertex_buffer = None;
+        }
+
+        if Command::ToggleSystemNames.triggered(input_state) {
+            self.force_system_names = !self.force_system_names;
+            text_dirty = true;
+        }
+
+        if Command::ToggleMetrics.triggered(input_state) {
+            self.show_metrics = !self.show_metrics;
+            self.system_vertexes = None;
+            text_dirty = true;
+        }
+
+        if Command::ToggleDistanceMetric.triggered(input_state) {
+            self.distance_metric = match self.distance_metric {
+                DistanceMetric::Jumps => DistanceMetric::LightYears,
+                DistanceMetric::LightYears => DistanceMetric::Jumps,
+            };
             text_dirty = true;
+            self.system_vertexes = None;
         }
 
         let mut show_distance = false;
@@ -189,8 +837,17 @@ impl Widget for Map {
             if input_state.is_key_down(VirtualKeyCode::LAlt)
                 || input_state.is_key_down(VirtualKeyCode::RAlt)
             {
-                if Some(system_id) != self.distance_map.as_ref().map(|(s, _d)| *s) {
-                    self.distance_map = Some((system_id, world.distances_from(system_id)));
+                let current = self.distance_map.as_ref().map(|(s, m, _d)| (*s, *m));
+                if Some((system_id, self.distance_metric)) != current {
+                    let distances = match self.distance_metric {
+                        DistanceMetric::Jumps => world
+                            .distances_from(system_id)
+                            .iter()
+                            .map(|(s, d)| (*s, *d as f64))
+                            .collect(),
+                        DistanceMetric::LightYears => world.light_years_from(system_id),
+                    };
+                    self.distance_map = Some((system_id, self.distance_metric, distances));
                 }
                 show_distance = true;
                 text_dirty = true;
@@ -205,6 +862,16 @@ impl Widget for Map {
             self.system_vertexes = None;
         }
 
+        let view = Some((self.current_zoom, self.map_offset));
+        if view != self.reported_view {
+            self.reported_view = view;
+            input_state.send_user_event(UserEvent::MapEvent(MapEvent::ViewChanged {
+                zoom: self.current_zoom,
+                offset_x: self.map_offset.x,
+                offset_y: self.map_offset.y,
+            }));
+        }
+
         self.view_matrix = math::M3::<f32>::identity();
         self.view_matrix.c0.x = self.current_zoom;
         self.view_matrix.c1.y = self.current_zoom;
@@ -235,12 +902,19 @@ impl Widget for Map {
 
         let text_scale = self.context.ui_scale();
 
-        if input_state.mouse_move_delta() != math::V2::fill(0.0) || text_dirty {
+        if input_state.mouse_move_delta() != math::V2::fill(0.0) {
+            self.selection_pinned = false;
+        }
+
+        if !self.capturing_pointer
+            && (input_state.mouse_move_delta() != math::V2::fill(0.0)
+                || (text_dirty && !self.selection_pinned))
+        {
             let mut selected_system = None;
 
             if let Some(systems) = &self.map_systems {
                 let mut closest_match: Option<(f32, i32)> = None;
-                for system in systems.values() {
+                for system in systems.values().filter(|s| self.is_visible(s.system_id)) {
                     let position = (text_transform * system.position.expand(1.0)).collapse();
                     let distance = position.distance_squared(&input_state.mouse_position());
 
@@ -268,10 +942,120 @@ impl Widget for Map {
             }
         }
 
+        if !self.capturing_pointer && !self.measure_mode && input_state.was_double_click() {
+            if let Some(system_id) = self.selected_system {
+                self.center_on_system(system_id);
+            }
+        }
+
+        if !self.capturing_pointer && input_state.was_mouse_clicked(MouseButton::Right) {
+            if let Some(system_id) = self.selected_system {
+                input_state
+                    .send_user_event(UserEvent::MapEvent(MapEvent::SystemContextMenu(system_id)));
+            }
+        }
+
+        let mut measure_changed = false;
+        if Command::ToggleMeasure.triggered(input_state) {
+            self.measure_mode = !self.measure_mode;
+            self.measurement = None;
+            measure_changed = true;
+        }
+
+        if !self.capturing_input
+            && input_state.was_key_down(VirtualKeyCode::Escape)
+            && self.measurement.is_some()
+        {
+            self.measurement = None;
+            measure_changed = true;
+        }
+
+        if !self.capturing_pointer
+            && self.measure_mode
+            && input_state.was_mouse_clicked(MouseButton::Left)
+        {
+            if let Some(system_id) = self.selected_system {
+                self.measurement = match self.measurement.take() {
+                    Some(Measurement { from, to: None, .. }) if from != system_id => {
+                        Some(Measurement {
+                            from,
+                            to: Some(system_id),
+                            jumps: world.jump_distance(from, system_id),
+                            light_years: world.light_year_distance(from, system_id),
+                        })
+                    }
+                    _ => Some(Measurement {
+                        from: system_id,
+                        to: None,
+                        jumps: None,
+                        light_years: None,
+                    }),
+                };
+                measure_changed = true;
+            }
+        }
+
+        if measure_changed {
+            self.measure_vertex_buffer = None;
+            text_dirty = true;
+        }
+
+        if Command::ToggleRegionOutlines.triggered(input_state) {
+            self.show_region_outlines = !self.show_region_outlines;
+            self.context.request_redraw("map region outlines");
+        }
+
+        let jump_toggles = [
+            (Command::ToggleJumpBridges, JumpType::JumpGate),
+            (Command::ToggleWormholes, JumpType::Wormhole),
+        ];
+        let mut hidden_jumps_changed = false;
+        for (command, jump_type) in jump_toggles.iter() {
+            if command.triggered(input_state) {
+                if !self.hidden_jumps.remove(jump_type) {
+                    self.hidden_jumps.insert(*jump_type);
+                }
+                hidden_jumps_changed = true;
+            }
+        }
+        if hidden_jumps_changed {
+            log::info!("hidden jumps: {:?}", self.hidden_jumps);
+            self.jump_vertexes = None;
+            input_state.send_user_event(UserEvent::MapEvent(MapEvent::HiddenJumpsChanged(
+                self.hidden_jumps.clone(),
+            )));
+        }
+
+        if Command::ToggleJumpRange.triggered(input_state) {
+            self.jump_range_mode = !self.jump_range_mode;
+            self.jump_range = None;
+            self.jump_range_vertex_buffer = None;
+            self.system_vertexes = None;
+            text_dirty = true;
+        }
+
+        if self.jump_range_mode {
+            let origin = self.selected_system.or(self.player_location);
+            let current = self.jump_range.as_ref().map(|(s, r, _)| (*s, *r));
+            let target = origin.map(|s| (s, self.jump_range_ly));
+            if target != current {
+                self.jump_range = target.map(|(system_id, light_years)| {
+                    let in_range = world
+                        .systems_within_range(system_id, light_years)
+                        .into_iter()
+                        .collect();
+                    (system_id, light_years, in_range)
+                });
+                self.jump_range_vertex_buffer = None;
+                self.system_vertexes = None;
+                text_dirty = true;
+            }
+        }
+
         if self.map_systems.is_none() {
             let max_magnitude = world
                 .systems()
-                .filter(|s| s.system_id < 30050000)
+                .filter(|s| world.is_known_space(s.system_id))
                 .map(|s| math::v3(s.position.x, s.position.z, s.position.y).magnitude())
                 .max_by(|a, b| {
                     if a > b {
@@ -282,9 +1066,9 @@ impl Widget for Map {
                 })
                 .unwrap_or(1.0);
 
-            let map_systems = world
+            let map_systems: HashMap<_, _> = world
                 .systems()
-                .filter(|s| s.system_id < 30050000)
+                .filter(|s| world.is_known_space(s.system_id))
                 .map(|s| {
                     let position = math::v2(s.position.x, s.position.z);
                     let position = (position / max_magnitude).as_f32();
@@ -303,6 +1087,7 @@ impl Widget for Map {
                 })
                 .collect();
 
+            log::info!("mapped {} known space systems", map_systems.len());
             self.system_magnitude = max_magnitude;
             self.map_systems = Some(map_systems);
             self.jump_vertexes = None;
@@ -316,13 +1101,15 @@ impl Widget for Map {
                 .jumps()
                 .iter()
                 .map(|j| {
-                    let on_route =
-                        world.is_on_route(j.left_system_id) && world.is_on_route(j.right_system_id);
+                    let route = world.routes().iter().position(|r| {
+                        r.contains(j.left_system_id) && r.contains(j.right_system_id)
+                    });
                     MapJump {
                         left_system_id: j.left_system_id,
                         right_system_id: j.right_system_id,
                         jump_type: j.jump_type,
-                        on_route,
+                        restricted: j.restricted,
+                        route,
                     }
                 })
                 .collect();
@@ -410,12 +1197,129 @@ impl Widget for Map {
                 }
             }
 
+            self.constellation_names.clear();
+            let alpha = constellation_name_alpha(self.current_zoom);
+            if alpha > 0.0 {
+                if let Some(systems) = self.map_systems.as_ref() {
+                    for constellation in world.constellations() {
+                        let (positions, count) = constellation
+                            .systems
+                            .iter()
+                            .flatten()
+                            .filter_map(|s| systems.get(s))
+                            .fold((math::V2::fill(0.0), 0), |acc, system| {
+                                (acc.0 + system.position, acc.1 + 1)
+                            });
+
+                        if count == 0 {
+                            continue;
+                        }
+
+                        let position = positions / (count as f32);
+                        let position = (text_transform * position.expand(1.0)).collapse();
+
+                        let min_corner = position - 200.0 * text_scale;
+                        let max_corner = position + 200.0 * text_scale;
+
+                        if max_corner.x < 0.0
+                            || max_corner.y < 0.0
+                            || min_corner.x > self.window_size.x
+                            || min_corner.y > self.window_size.y
+                        {
+                            continue;
+                        }
+
+                        let mut span = font::TextSpan::new(
+                            35.0 * text_scale,
+                            self.context.ui_font,
+                            math::v3(0.7, 0.75, 0.9).expand(alpha),
+                        );
+                        span.push(&constellation.name);
+                        let span = self.context.font_cache.layout(
+                            span,
+                            font::TextAnchor::Center,
+                            position,
+                            true,
+                        );
+
+                        self.constellation_names.push(span);
+                    }
+                }
+            }
+
+            self.route_labels.clear();
+            let routes = world.routes();
+            if self.current_zoom > ROUTE_LABEL_MIN_ZOOM && !routes.is_empty() {
+                let alpha = ((self.current_zoom - ROUTE_LABEL_MIN_ZOOM)
+                    / (ROUTE_LABEL_FULL_ZOOM - ROUTE_LABEL_MIN_ZOOM))
+                    .min(1.0);
+
+                if let Some(systems) = self.map_systems.as_ref() {
+                    for (route_index, route) in routes.iter().enumerate() {
+                        let color = if routes.len() > 1 {
+                            super::route_color(route_index)
+                        } else {
+                            math::V3::fill(1.0)
+                        };
+
+                        for (jump, nodes) in route.nodes().windows(2).enumerate() {
+                            let (left, right) = match (
+                                systems.get(&nodes[0].system_id),
+                                systems.get(&nodes[1].system_id),
+                            ) {
+                                (Some(left), Some(right)) => (left, right),
+                                _ => continue,
+                            };
+
+                            let midpoint = (left.position + right.position) / 2.0;
+                            let pos = (text_transform * midpoint.expand(1.0)).collapse();
+
+                            let min_corner = pos - 50.0 * text_scale;
+                            let max_corner = pos + 50.0 * text_scale;
+
+                            if max_corner.x < 0.0
+                                || max_corner.y < 0.0
+                                || min_corner.x > self.window_size.x
+                                || min_corner.y > self.window_size.y
+                            {
+                                continue;
+                            }
+
+                            let scale = (20.0 * text_scale).max(12.0);
+                            let mut span = font::TextSpan::new(
+                                scale,
+                                self.context.ui_font,
+                                color.expand(alpha),
+                            );
+                            span.push(format!("{}", jump + 1));
+                            let span = self.context.font_cache.layout(
+                                span,
+                                font::TextAnchor::Center,
+                                pos,
+                                true,
+                            );
+
+                            self.route_labels.push(span);
+                        }
+                    }
+                }
+            }
+
             self.system_names.clear();
-            if self.current_zoom > 6.0 {
-                let alpha = ((self.current_zoom - 6.0) / (13.0 - 6.0)).min(1.0);
+            if self.current_zoom > 6.0 || self.force_system_names {
+                let alpha = if self.force_system_names {
+                    1.0
+                } else {
+                    ((self.current_zoom - 6.0) / (13.0 - 6.0)).min(1.0)
+                };
 
+                let filtered_systems = self.filtered_systems.as_ref();
                 if let Some(systems) = self.map_systems.as_ref() {
                     for system in systems.values() {
+                        if filtered_systems.is_some_and(|f| !f.contains(&system.system_id)) {
+                            continue;
+                        }
+
                         let pos = (text_transform * system.position.expand(1.0)).collapse();
 
                         let min_corner = pos - 50.0 * text_scale;
@@ -436,18 +1340,38 @@ impl Widget for Map {
                         let scale = (25.0 * text_scale).max(14.0);
                         let mut span =
                             font::TextSpan::new(scale, self.context.ui_font, color.expand(alpha));
-                        span.push(&system.name);
+                        match self.context.settings.system_name_max_length {
+                            Some(max_length) if self.current_zoom < FULL_SYSTEM_NAME_ZOOM => {
+                                span.push(abbreviate_system_name(&system.name, max_length));
+                            }
+                            _ => {
+                                span.push(&system.name);
+                            }
+                        }
+
+                        if self.show_metrics && !show_distance {
+                            if let Some(value) = world.system_metric(system.system_id) {
+                                span.push(format!(" [{}]", format_metric(value)));
+                            }
+                        }
 
                         if show_distance {
-                            if let Some(distance) = self
+                            if let Some((metric, distance)) = self
                                 .distance_map
                                 .as_ref()
-                                .and_then(|d| d.1.get(&system.system_id).cloned())
+                                .and_then(|(_, m, d)| d.get(&system.system_id).map(|d| (*m, *d)))
                             {
-                                if distance == 1 {
-                                    span.push(format!(" ({} jump)", distance));
-                                } else if distance > 1 {
-                                    span.push(format!(" ({} jumps)", distance));
+                                match metric {
+                                    DistanceMetric::Jumps if distance as u32 == 1 => {
+                                        span.push(" (1 jump)");
+                                    }
+                                    DistanceMetric::Jumps if distance as u32 > 1 => {
+                                        span.push(format!(" ({} jumps)", distance as u32));
+                                    }
+                                    DistanceMetric::LightYears if distance > 0.0 => {
+                                        span.push(format!(" ({:.1} ly)", distance));
+                                    }
+                                    _ => (),
                                 }
                             }
                         }
@@ -464,6 +1388,130 @@ impl Widget for Map {
                 }
             }
 
+            self.scale_bar_rect = None;
+            self.scale_bar_text.clear();
+            if self.context.settings.show_scale_bar && self.system_magnitude > 0.0 {
+                let min_dimension = self.window_size.x.min(self.window_size.y) as f64;
+                let pixels_per_light_year = (self.current_zoom as f64 * min_dimension / 2.0)
+                    / self.system_magnitude
+                    * METERS_PER_LIGHT_YEAR;
+                let max_width = 300.0 * text_scale as f64;
+                let light_years = scale_bar_length(max_width / pixels_per_light_year);
+                let width = (light_years * pixels_per_light_year) as f32;
+
+                let padding = 30.0 * text_scale;
+                let height = 8.0 * text_scale;
+                let corner = self.window_size - padding;
+                self.scale_bar_rect = Some(math::Rect::new(
+                    math::v2(corner.x - width, corner.y - height),
+                    corner,
+                ));
+
+                let mut span = font::TextSpan::new(
+                    30.0 * text_scale,
+                    self.context.ui_font,
+                    math::V4::fill(1.0),
+                );
+                span.push(format!("{} ly", light_years));
+                let span = self.context.font_cache.layout(
+                    span,
+                    font::TextAnchor::BottomRight,
+                    math::v2(corner.x, corner.y - height - 5.0 * text_scale),
+                    true,
+                );
+                self.scale_bar_text.push(span);
+            }
+
+            self.measure_text.clear();
+            let measured_systems = self.measurement.as_ref().and_then(|m| {
+                let systems = self.map_systems.as_ref()?;
+                Some((m, systems.get(&m.from)?, systems.get(&m.to?)?))
+            });
+            if let Some((measurement, from, to)) = measured_systems {
+                let midpoint = (from.position + to.position) / 2.0;
+                let position = (text_transform * midpoint.expand(1.0)).collapse();
+
+                let mut span = font::TextSpan::new(
+                    (30.0 * text_scale).max(16.0),
+                    self.context.ui_font,
+                    math::V4::fill(1.0),
+                );
+                match measurement.jumps {
+                    Some(1) => span.push("1 jump"),
+                    Some(jumps) => span.push(format!("{} jumps", jumps)),
+                    None => span.push("no route"),
+                };
+                if let Some(light_years) = measurement.light_years {
+                    span.push(format!(" · {:.2} ly", light_years));
+                }
+                let span =
+                    self.context
+                        .font_cache
+                        .layout(span, font::TextAnchor::Center, position, true);
+                self.measure_text.push(span);
+            }
+
+            self.jump_range_text.clear();
+            let range_origin = self
+                .jump_range
+                .as_ref()
+                .and_then(|(id, light_years, in_range)| {
+                    let system = self.map_systems.as_ref()?.get(id)?;
+                    Some((system.position, *light_years, in_range.len()))
+                });
+            if let Some((center, light_years, count)) =
+                range_origin.filter(|_| self.system_magnitude > 0.0)
+            {
+                let radius = (light_years * METERS_PER_LIGHT_YEAR / self.system_magnitude) as f32;
+                let top = center + math::v2(0.0, radius);
+                let position = (text_transform * top.expand(1.0)).collapse();
+
+                let mut span = font::TextSpan::new(
+                    (30.0 * text_scale).max(16.0),
+                    self.context.ui_font,
+                    math::v4(0.7, 0.4, 1.0, 1.0),
+                );
+                span.push(format!("{:.1} ly · {} systems", light_years, count));
+                let span =
+                    self.context
+                        .font_cache
+                        .layout(span, font::TextAnchor::Center, position, true);
+                self.jump_range_text.push(span);
+            }
+
+            self.focus_marker_rect = None;
+            self.focus_summary_text.clear();
+            let focus_summary = self.focus_summary.filter(|_| self.system_magnitude > 0.0);
+            if let Some(summary) = focus_summary {
+                let position = math::v2(summary.position.x, summary.position.z);
+                let position = (position / self.system_magnitude).as_f32();
+                let position = (text_transform * position.expand(1.0)).collapse();
+
+                let size = 6.0 * text_scale.max(1.0);
+                self.focus_marker_rect = Some(math::Rect::new(
+                    position - math::V2::fill(size),
+                    position + math::V2::fill(size),
+                ));
+
+                let mut span = font::TextSpan::new(
+                    (25.0 * text_scale).max(14.0),
+                    self.context.ui_font,
+                    math::V4::fill(1.0),
+                );
+                span.push(format!("{} Systems · Avg Sec ", summary.system_count))
+                    .color(super::sec_status_color(summary.average_security).expand(1.0))
+                    .push(format!("{:.2}", summary.average_security))
+                    .color(math::V4::fill(1.0))
+                    .push(format!(" · {} Jumps", summary.total_jumps));
+                let span = self.context.font_cache.layout(
+                    span,
+                    font::TextAnchor::TopLeft,
+                    position + math::V2::fill(size * 1.5),
+                    true,
+                );
+                self.focus_summary_text.push(span);
+            }
+
             self.context.request_redraw("map text dirty")
         }
 
@@ -471,6 +1519,7 @@ impl Widget for Map {
             if let (Some(map_jumps), Some(map_systems)) =
                 (self.map_jumps.as_ref(), self.map_systems.as_ref())
             {
+                let route_count = world.routes().len();
                 let mut jump_vertexes = Vec::with_capacity(world.jumps().len() * 6);
                 for jump in map_jumps {
                     let left_system = map_systems.get(&jump.left_system_id);
@@ -483,27 +1532,45 @@ impl Widget for Map {
                     let left_system = left_system.unwrap();
                     let right_system = right_system.unwrap();
 
-                    let (mut left_color, mut right_color) = if jump.on_route {
-                        (
+                    if jump.route.is_none() && self.hidden_jumps.contains(&jump.jump_type) {
+                        continue;
+                    }
+
+                    let left_visible = self.is_visible(left_system.system_id);
+                    let right_visible = self.is_visible(right_system.system_id);
+                    if !left_visible && !right_visible {
+                        continue;
+                    }
+
+                    let (mut left_color, mut right_color) = match jump.route {
+                        Some(_) if route_count == 1 => (
                             super::sec_status_color(left_system.security_status),
                             super::sec_status_color(right_system.security_status),
-                        )
-                    } else {
-                        (
+                        ),
+                        Some(route) => (super::route_color(route), super::route_color(route)),
+                        None if jump.restricted => {
+                            (math::v3(0.35, 0.35, 0.35), math::v3(0.35, 0.35, 0.35))
+                        }
+                        None => (
                             super::jump_type_color(&jump.jump_type),
                             super::jump_type_color(&jump.jump_type),
-                        )
+                        ),
                     };
 
                     if Some(left_system.system_id) == self.selected_system {
-                        left_color = left_color + math::V3::fill(0.1);
+                        left_color = super::highlight_color(left_color);
                     }
 
                     if Some(right_system.system_id) == self.selected_system {
-                        right_color = right_color + math::V3::fill(0.1);
+                        right_color = super::highlight_color(right_color);
+                    }
+
+                    if !left_visible || !right_visible {
+                        left_color *= 0.3;
+                        right_color *= 0.3;
                     }
 
-                    let level = if jump.on_route { 1.0 } else { 0.5 };
+                    let level = if jump.route.is_some() { 1.0 } else { 0.5 };
 
                     let jump_left = left_system.position.expand(level);
                     let jump_right = right_system.position.expand(level);
@@ -547,6 +1614,51 @@ impl Widget for Map {
 
         if self.system_vertexes.is_none() {
             if let Some(systems) = self.map_systems.as_ref() {
+                let mut pocket_gateways = HashSet::new();
+                let mut selected_pocket = HashSet::new();
+                if self.show_pockets {
+                    for (gateway, pocket) in world.pockets() {
+                        pocket_gateways.insert(*gateway);
+                        if Some(*gateway) == self.selected_system {
+                            selected_pocket.extend(pocket.iter().cloned());
+                        }
+                    }
+                }
+
+                let metric_range = if self.show_metrics {
+                    world.system_metric_range()
+                } else {
+                    None
+                };
+
+                let in_jump_range = self
+                    .jump_range
+                    .as_ref()
+                    .map(|(_, _, in_range)| in_range.clone())
+                    .unwrap_or_default();
+
+                let chokepoints = if self.overlay == MapOverlay::Chokepoints {
+                    Some(world.chokepoints())
+                } else {
+                    None
+                };
+
+                let mut infested_systems = HashSet::new();
+                let mut staging_systems = HashSet::new();
+                if self.overlay == MapOverlay::Incursions {
+                    for incursion in world.incursions() {
+                        infested_systems.extend(incursion.infested_solar_systems);
+                        staging_systems.insert(incursion.staging_solar_system_id);
+                    }
+                }
+
+                let overlay_max = systems
+                    .keys()
+                    .filter_map(|id| world.stats(*id))
+                    .filter_map(|stats| self.overlay.stat(&stats))
+                    .max()
+                    .unwrap_or(0);
+
                 let system_vertexes = systems
                     .values()
                     .map(|system| {
@@ -557,12 +1669,23 @@ impl Widget for Map {
                             math::v4(0.0, 1.0, 1.0, 1.0)
                         } else if is_focused || is_selected {
                             math::v4(1.0, 1.0, 1.0, 1.0)
+                        } else if staging_systems.contains(&system.system_id) {
+                            math::v4(1.0, 1.0, 0.0, 1.0)
+                        } else if in_jump_range.contains(&system.system_id) {
+                            math::v4(0.7, 0.3, 1.0, 1.0)
+                        } else if pocket_gateways.contains(&system.system_id) {
+                            math::v4(1.0, 0.5, 0.0, 1.0)
+                        } else if selected_pocket.contains(&system.system_id) {
+                            math::v4(1.0, 0.5, 0.0, 0.5)
+                        } else if world.is_home_sov(system.system_id) {
+                            math::v4(0.2, 1.0, 0.4, 0.8)
                         } else {
                             math::V4::fill(0.0)
                         };
 
-                        let alpha = if self.focused_systems.len() == 0 || is_focused || is_selected
-                        {
+                        let alpha = if !self.is_visible(system.system_id) {
+                            0.1
+                        } else if self.focused_systems.len() == 0 || is_focused || is_selected {
                             1.0
                         } else {
                             0.1
@@ -576,19 +1699,69 @@ impl Widget for Map {
                             1.0
                         };
 
-                        let mut color = super::sec_status_color(system.security_status);
+                        let mut color = match self.overlay {
+                            MapOverlay::None => math::V3::fill(0.6),
+                            MapOverlay::Security | MapOverlay::Sovereignty => {
+                                super::sec_status_color(system.security_status)
+                            }
+                            MapOverlay::Standings => match system.sovereignty_standing {
+                                Some(standing) => super::standing_color(standing),
+                                None => {
+                                    let class = world.security_class(system.system_id);
+                                    super::security_class_color(class) * 0.3
+                                }
+                            },
+                            MapOverlay::Incursions
+                                if infested_systems.contains(&system.system_id) =>
+                            {
+                                math::v3(0.7, 0.2, 0.9)
+                            }
+                            MapOverlay::Incursions => math::V3::fill(0.2),
+                            MapOverlay::FactionWarfare => match world.fw_system(system.system_id) {
+                                Some(fw) => fw_system_color(
+                                    fw.occupier_faction_id,
+                                    fw.victory_points,
+                                    fw.victory_points_threshold,
+                                ),
+                                None => math::V3::fill(0.2),
+                            },
+                            MapOverlay::Chokepoints => match chokepoints.as_ref() {
+                                Some(c) if c.contains(&system.system_id) => math::v3(1.0, 0.3, 0.0),
+                                _ => math::V3::fill(0.2),
+                            },
+                            overlay => match world
+                                .stats(system.system_id)
+                                .and_then(|stats| overlay.stat(&stats))
+                            {
+                                Some(value) if value > 0 => {
+                                    super::sec_status_color(1.0 - log_scale(value, overlay_max))
+                                }
+                                _ => math::V3::fill(0.2),
+                            },
+                        };
+
+                        if let Some((min, max)) = metric_range {
+                            color = match world.system_metric(system.system_id) {
+                                Some(value) if max > min => {
+                                    super::sec_status_color((value - min) / (max - min))
+                                }
+                                Some(_) => super::sec_status_color(1.0),
+                                None => math::V3::fill(0.2),
+                            };
+                        }
 
                         if show_distance {
-                            if let Some(distance) = self
+                            if let Some((metric, distance)) = self
                                 .distance_map
                                 .as_ref()
-                                .and_then(|(_, d)| d.get(&system.system_id).cloned())
+                                .and_then(|(_, m, d)| d.get(&system.system_id).map(|d| (*m, *d)))
                             {
-                                color = if distance == 0 {
+                                color = if distance <= 0.0 {
                                     math::V3::fill(1.0)
                                 } else {
-                                    let distance = 20.0 - (distance as f64).min(20.0);
-                                    super::sec_status_color(distance / 20.0)
+                                    let max_distance = metric.max_distance();
+                                    let distance = max_distance - distance.min(max_distance);
+                                    super::sec_status_color(distance / max_distance)
                                 };
                             }
                         }
@@ -609,7 +1782,7 @@ impl Widget for Map {
             }
         }
 
-        if self.sov_vertexes.is_none() {
+        if self.overlay == MapOverlay::Sovereignty && self.sov_vertexes.is_none() {
             if let Some(systems) = self.map_systems.as_ref() {
                 let sov_systems = systems
                     .values()
@@ -651,6 +1824,77 @@ impl Widget for Map {
             }
         }
 
+        if self.measure_vertex_buffer.is_none() {
+            let measured_systems = self.measurement.as_ref().and_then(|m| {
+                let systems = self.map_systems.as_ref()?;
+                Some((systems.get(&m.from)?, systems.get(&m.to?)?))
+            });
+            if let Some((from, to)) = measured_systems {
+                let vertexes = line_quad(from.position, to.position, math::v3(1.0, 1.0, 0.0));
+
+                self.measure_vertex_buffer = Some(self.context.display.fill_buffer(&vertexes));
+                self.context.request_redraw("map measure buffer")
+            }
+        }
+
+        if self.show_region_outlines && self.region_outlines.is_none() {
+            if let Some(systems) = self.map_systems.as_ref() {
+                let outlines = world
+                    .regions()
+                    .map(|region| {
+                        let positions = region
+                            .constellations
+                            .iter()
+                            .flatten()
+                            .filter_map(|c| world.constellation(*c))
+                            .flat_map(|c| c.systems.iter().flatten())
+                            .filter_map(|s| systems.get(s))
+                            .map(|s| s.position)
+                            .collect();
+
+                        (region.region_id, convex_hull(positions))
+                    })
+                    .collect();
+
+                self.region_outlines = Some(outlines);
+                self.region_outline_buffer = None;
+            }
+        }
+
+        if self.show_region_outlines && self.region_outline_buffer.is_none() {
+            if let Some(outlines) = self.region_outlines.as_ref() {
+                let color = math::v3(0.3, 0.3, 0.45);
+                let vertexes: Vec<_> = outlines
+                    .values()
+                    .filter(|hull| hull.len() > 1)
+                    .flat_map(|hull| {
+                        hull.iter()
+                            .zip(hull.iter().cycle().skip(1))
+                            .flat_map(move |(left, right)| line_quad(*left, *right, color))
+                    })
+                    .collect();
+
+                self.region_outline_buffer = Some(self.context.display.fill_buffer(&vertexes));
+                self.context.request_redraw("map region outline buffer")
+            }
+        }
+
+        if self.jump_range_vertex_buffer.is_none() {
+            let range_origin = self.jump_range.as_ref().and_then(|(id, light_years, _)| {
+                let system = self.map_systems.as_ref()?.get(id)?;
+                Some((system.position, *light_years))
+            });
+            if let Some((center, light_years)) =
+                range_origin.filter(|_| self.system_magnitude > 0.0)
+            {
+                let radius = (light_years * METERS_PER_LIGHT_YEAR / self.system_magnitude) as f32;
+                let vertexes = ring_vertexes(center, radius, math::v3(0.35, 0.15, 0.5));
+
+                self.jump_range_vertex_buffer = Some(self.context.display.fill_buffer(&vertexes));
+                self.context.request_redraw("map jump range buffer")
+            }
+        }
+
         if self.sov_vertex_buffer.is_none() {
             if let Some(vertexes) = self.sov_vertexes.as_ref() {
                 self.sov_vertex_buffer = Some(self.context.display.fill_buffer(&vertexes));
@@ -658,6 +1902,32 @@ impl Widget for Map {
                 self.context.request_redraw("map sov buffer")
             }
         }
+
+        if let Some(elapsed) = self.location_ping {
+            let elapsed = elapsed + dt.as_secs_f32();
+            let ping = self
+                .player_location
+                .and_then(|id| self.map_systems.as_ref()?.get(&id))
+                .zip(location_ping_style(elapsed));
+
+            if let Some((system, (scale, alpha))) = ping {
+                let vertexes = [SystemData {
+                    center: system.position,
+                    color: math::v4(0.0, 1.0, 1.0, alpha * 0.5),
+                    highlight: math::v4(0.0, 1.0, 1.0, 1.0),
+                    system_id: system.system_id,
+                    scale,
+                    radius: 25.0,
+                }];
+                self.location_ping = Some(elapsed);
+                self.location_ping_buffer = Some(self.context.display.fill_buffer(&vertexes));
+                self.context.request_animation_frame("map location ping");
+            } else {
+                self.location_ping = None;
+                self.location_ping_buffer = None;
+                self.context.request_redraw("map location ping ended");
+            }
+        }
     }
 
     fn draw(&mut self, frame: &mut Frame) {
@@ -672,7 +1942,11 @@ impl Widget for Map {
             );
         }
 
-        if let Some(sov_data) = self.sov_vertex_buffer.as_ref() {
+        if let Some(sov_data) = self
+            .sov_vertex_buffer
+            .as_ref()
+            .filter(|_| self.overlay == MapOverlay::Sovereignty)
+        {
             self.context.display.draw_system(
                 frame,
                 &self.circle_buffer,
@@ -683,6 +1957,20 @@ impl Widget for Map {
             );
         }
 
+        if let Some(outline_data) = self
+            .region_outline_buffer
+            .as_ref()
+            .filter(|_| self.show_region_outlines)
+        {
+            self.context.display.draw_jump(
+                frame,
+                outline_data,
+                self.current_zoom,
+                self.scale_matrix,
+                self.view_matrix,
+            );
+        }
+
         if let Some(jump_data) = self.jumps_vertex_buffer.as_ref() {
             self.context.display.draw_jump(
                 frame,
@@ -693,6 +1981,37 @@ impl Widget for Map {
             );
         }
 
+        if let Some(range_data) = self.jump_range_vertex_buffer.as_ref() {
+            self.context.display.draw_jump(
+                frame,
+                range_data,
+                self.current_zoom,
+                self.scale_matrix,
+                self.view_matrix,
+            );
+        }
+
+        if let Some(measure_data) = self.measure_vertex_buffer.as_ref() {
+            self.context.display.draw_jump(
+                frame,
+                measure_data,
+                self.current_zoom,
+                self.scale_matrix,
+                self.view_matrix,
+            );
+        }
+
+        if let Some(ping_data) = self.location_ping_buffer.as_ref() {
+            self.context.display.draw_system(
+                frame,
+                &self.circle_buffer,
+                ping_data,
+                self.current_zoom,
+                self.scale_matrix,
+                self.view_matrix,
+            );
+        }
+
         if let Some(system_data) = self.systems_vertex_buffer.as_ref() {
             self.context.display.draw_system(
                 frame,
@@ -713,6 +2032,24 @@ impl Widget for Map {
             );
         }
 
+        if !self.route_labels.is_empty() {
+            self.context.display.draw_text(
+                frame,
+                &self.context.font_cache,
+                &self.route_labels,
+                self.context.ui_scale(),
+            );
+        }
+
+        if !self.constellation_names.is_empty() {
+            self.context.display.draw_text(
+                frame,
+                &self.context.font_cache,
+                &self.constellation_names,
+                self.context.ui_scale(),
+            );
+        }
+
         if self.region_names_layer == Some(RegionNamesLayer::Foreground)
             && self.region_names.len() > 0
         {
@@ -723,5 +2060,258 @@ impl Widget for Map {
                 self.context.ui_scale(),
             );
         }
+
+        if !self.measure_text.is_empty() {
+            self.context.display.draw_text(
+                frame,
+                &self.context.font_cache,
+                &self.measure_text,
+                self.context.ui_scale(),
+            );
+        }
+
+        if !self.jump_range_text.is_empty() {
+            self.context.display.draw_text(
+                frame,
+                &self.context.font_cache,
+                &self.jump_range_text,
+                self.context.ui_scale(),
+            );
+        }
+
+        if let Some(marker) = self.focus_marker_rect {
+            self.context.display.draw_quad(
+                frame,
+                &self.context.images,
+                math::v4(1.0, 0.5, 0.0, 1.0),
+                marker,
+            );
+
+            self.context.display.draw_text(
+                frame,
+                &self.context.font_cache,
+                &self.focus_summary_text,
+                self.context.ui_scale(),
+            );
+        }
+
+        if let Some(scale_bar) = self.scale_bar_rect {
+            self.context.display.draw_quad(
+                frame,
+                &self.context.images,
+                math::V4::fill(0.8),
+                scale_bar,
+            );
+
+            self.context.display.draw_text(
+                frame,
+                &self.context.font_cache,
+                &self.scale_bar_text,
+                self.context.ui_scale(),
+            );
+        }
+    }
+}
+
+fn abbreviate_system_name(name: &str, max_length: usize) -> Cow<'_, str> {
+    if name.chars().count() <= max_length {
+        Cow::Borrowed(name)
+    } else {
+        let mut short: String = name.chars().take(max_length.saturating_sub(1)).collect();
+        short.push('…');
+        Cow::Owned(short)
+    }
+}
+
+fn format_metric(value: f64) -> String {
+    let magnitude = value.abs();
+    if magnitude >= 1e9 {
+        format!("{:.1}b", value / 1e9)
+    } else if magnitude >= 1e6 {
+        format!("{:.1}m", value / 1e6)
+    } else if magnitude >= 1e3 {
+        format!("{:.1}k", value / 1e3)
+    } else {
+        format!("{:.1}", value)
+    }
+}
+
+fn scale_bar_length(max_length: f64) -> f64 {
+    let magnitude = 10f64.powf(max_length.log10().floor());
+    let leading = max_length / magnitude;
+    let nice = if leading >= 5.0 {
+        5.0
+    } else if leading >= 2.0 {
+        2.0
+    } else {
+        1.0
+    };
+    nice * magnitude
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_to_screen(
+        map_point: math::V2<f32>,
+        window_size: math::V2<f32>,
+        zoom: f32,
+        offset: math::V2<f32>,
+    ) -> math::V2<f32> {
+        let window_scale = window_scale(window_size);
+
+        let mut view_matrix = math::M3::<f32>::identity();
+        view_matrix.c0.x = zoom;
+        view_matrix.c1.y = zoom;
+        view_matrix.c2.x = -offset.x * zoom;
+        view_matrix.c2.y = offset.y * zoom;
+
+        let mut scale_matrix = math::M3::<f32>::identity();
+        scale_matrix.c0.x = 1.0 / window_scale.x;
+        scale_matrix.c1.y = 1.0 / window_scale.y;
+
+        let mut screen_matrix = math::M3::<f32>::identity();
+        screen_matrix.c0.x = window_size.x / 2.0;
+        screen_matrix.c1.y = -window_size.y / 2.0;
+        screen_matrix.c2.x = window_size.x / 2.0;
+        screen_matrix.c2.y = window_size.y / 2.0;
+
+        (screen_matrix * scale_matrix * view_matrix * map_point.expand(1.0)).collapse()
+    }
+
+    #[test]
+    fn screen_to_map_inverts_view_transform() {
+        let window_size = math::v2(1600.0, 900.0);
+        let offset = math::v2(0.2, -0.4);
+        let zoom = 7.5;
+
+        for point in &[math::v2(0.0, 0.0), math::v2(0.25, 0.4), math::v2(-0.6, 0.1)] {
+            let screen = map_to_screen(*point, window_size, zoom, offset);
+            let map_point = screen_to_map(screen, window_size, zoom, offset);
+            assert!(map_point.distance(point) < 0.0001);
+        }
+    }
+
+    #[test]
+    fn anchored_offset_keeps_point_under_cursor() {
+        let window_size = math::v2(900.0, 1600.0);
+        let screen = math::v2(120.0, 1300.0);
+        let map_point = screen_to_map(screen, window_size, 2.0, math::v2(0.1, 0.3));
+
+        let offset = anchored_offset(map_point, screen, window_size, 11.0);
+        let zoomed = map_to_screen(map_point, window_size, 11.0, offset);
+        assert!(zoomed.distance(&screen) < 0.01);
+    }
+
+    #[test]
+    fn location_ping_expands_and_ends() {
+        let (start_scale, start_alpha) = location_ping_style(0.0).unwrap();
+        let (mid_scale, mid_alpha) = location_ping_style(0.5).unwrap();
+        assert!(mid_scale > start_scale && mid_alpha < start_alpha);
+        assert!(location_ping_style(LOCATION_PING_DURATION).is_none());
+    }
+
+    #[test]
+    fn parses_valid_map_view() {
+        let view = MapView::parse(br#"{"zoom": 12.5, "offset_x": 0.25, "offset_y": -0.5}"#);
+        assert_eq!(
+            view,
+            Some(MapView {
+                zoom: 12.5,
+                offset_x: 0.25,
+                offset_y: -0.5,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_map_view() {
+        assert_eq!(MapView::parse(b"garbage"), None);
+        assert_eq!(
+            MapView::parse(br#"{"zoom": 500.0, "offset_x": 0.0, "offset_y": 0.0}"#),
+            None
+        );
+        assert_eq!(
+            MapView::parse(br#"{"zoom": 0.0, "offset_x": 0.0, "offset_y": 0.0}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn overlay_stats_use_log_scale() {
+        assert_eq!(log_scale(0, 100), 0.0);
+        assert_eq!(log_scale(100, 100), 1.0);
+        assert_eq!(log_scale(5, 0), 0.0);
+        assert!(log_scale(10, 100) > 0.5);
+        assert!(log_scale(1, 100) < log_scale(2, 100));
+    }
+
+    #[test]
+    fn overlays_cycle_through_all_variants() {
+        let mut overlay = MapOverlay::None;
+        for _ in 0..11 {
+            overlay = overlay.next();
+        }
+        assert_eq!(overlay, MapOverlay::None);
+    }
+
+    #[test]
+    fn jump_range_ring_has_constant_radius() {
+        let center = math::v2(0.25, -0.5);
+        let vertexes = ring_vertexes(center, 0.1, math::V3::fill(1.0));
+        assert_eq!(vertexes.len(), JUMP_RANGE_SEGMENTS * 4);
+        for vertex in vertexes {
+            let position = math::v2(vertex.position.x, vertex.position.y);
+            assert!((position.distance(&center) - 0.1).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn convex_hull_drops_interior_points() {
+        let points = vec![
+            math::v2(0.0, 0.0),
+            math::v2(1.0, 0.0),
+            math::v2(0.5, 0.5),
+            math::v2(1.0, 1.0),
+            math::v2(0.0, 1.0),
+            math::v2(0.5, 0.0),
+            math::v2(0.0, 0.0),
+        ];
+        let hull = convex_hull(points);
+        assert_eq!(
+            hull,
+            vec![
+                math::v2(0.0, 0.0),
+                math::v2(1.0, 0.0),
+                math::v2(1.0, 1.0),
+                math::v2(0.0, 1.0),
+            ]
+        );
+        assert_eq!(convex_hull(vec![math::v2(1.0, 1.0)]).len(), 1);
+    }
+
+    #[test]
+    fn constellation_names_fade_between_region_and_system_names() {
+        assert_eq!(constellation_name_alpha(1.0), 0.0);
+        assert_eq!(constellation_name_alpha(3.5), 0.5);
+        assert_eq!(constellation_name_alpha(5.0), 1.0);
+        assert_eq!(constellation_name_alpha(7.0), 1.0);
+        assert_eq!(constellation_name_alpha(8.0), 0.5);
+        assert_eq!(constellation_name_alpha(20.0), 0.0);
+    }
+
+    #[test]
+    fn contested_fw_systems_wash_out() {
+        let uncontested = fw_system_color(500001, 0, 3000);
+        assert_eq!(uncontested, faction_color(500001));
+
+        let contested = fw_system_color(500001, 1500, 3000);
+        assert!(contested.x > uncontested.x && contested.y > uncontested.y);
+        assert_eq!(
+            fw_system_color(500001, 9000, 3000),
+            fw_system_color(500001, 3000, 3000)
+        );
+        assert_eq!(fw_system_color(500001, 100, 0), uncontested);
     }
 }
@@ -0,0 +1,250 @@
+use std::rc::Rc;
+
+use winit::event::MouseButton;
+
+use super::{
+    CircleVertex, DataEvent, GraphicsContext, InputState, MapEvent, SystemData, UserEvent, Widget,
+};
+use crate::math;
+use crate::platform::{Buffer, Frame};
+
+const MINIMAP_SIZE: f32 = 300.0;
+const MINIMAP_DOT_SIZE: f32 = 1.5;
+
+fn map_to_minimap(point: math::V2<f32>, rect: math::Rect<f32>) -> math::V2<f32> {
+    let half_size = (rect.max - rect.min) / 2.0;
+    let center = rect.min + half_size;
+    math::v2(
+        center.x + point.x * half_size.x,
+        center.y - point.y * half_size.y,
+    )
+}
+
+fn minimap_to_map(point: math::V2<f32>, rect: math::Rect<f32>) -> math::V2<f32> {
+    let half_size = (rect.max - rect.min) / 2.0;
+    let center = rect.min + half_size;
+    math::v2(
+        (point.x - center.x) / half_size.x,
+        -(point.y - center.y) / half_size.y,
+    )
+}
+
+fn clip_rect(rect: math::Rect<f32>, bounds: math::Rect<f32>) -> Option<math::Rect<f32>> {
+    let min = math::v2(rect.min.x.max(bounds.min.x), rect.min.y.max(bounds.min.y));
+    let max = math::v2(rect.max.x.min(bounds.max.x), rect.max.y.min(bounds.max.y));
+    if min.x < max.x && min.y < max.y {
+        Some(math::Rect::new(min, max))
+    } else {
+        None
+    }
+}
+
+pub struct Minimap {
+    context: Rc<GraphicsContext>,
+    window_size: math::V2<f32>,
+    view: Option<(f32, math::V2<f32>)>,
+    minimap_rect: math::Rect<f32>,
+    viewport_rect: Option<math::Rect<f32>>,
+    systems_buffer: Option<Buffer<SystemData>>,
+    circle_buffer: Buffer<CircleVertex>,
+    scale_matrix: math::M3<f32>,
+    capturing_pointer: bool,
+    dirty: bool,
+}
+
+impl Minimap {
+    pub fn new(context: Rc<GraphicsContext>) -> Self {
+        let mut circle_verts = Vec::new();
+        circle_verts.push(CircleVertex {
+            position: math::v2(0.0, 0.0),
+        });
+
+        for i in 0..9 {
+            let n = ((2.0 * std::f32::consts::PI) / 8.0) * i as f32;
+            circle_verts.push(CircleVertex {
+                position: math::v2(n.sin(), n.cos()),
+            });
+        }
+
+        let circle_buffer = context.display.fill_buffer(&circle_verts);
+
+        Minimap {
+            context,
+            window_size: math::v2(1024.0, 1024.0),
+            view: None,
+            minimap_rect: math::Rect::new(math::V2::fill(0.0), math::V2::fill(0.0)),
+            viewport_rect: None,
+            systems_buffer: None,
+            circle_buffer,
+            scale_matrix: math::M3::identity(),
+            capturing_pointer: false,
+            dirty: true,
+        }
+    }
+
+    pub fn capturing_pointer(&self) -> bool {
+        self.capturing_pointer
+    }
+
+    fn build_systems(&mut self, world: &crate::world::World) {
+        let max_magnitude = world
+            .systems()
+            .filter(|s| world.is_known_space(s.system_id))
+            .map(|s| math::v3(s.position.x, s.position.z, s.position.y).magnitude())
+            .fold(0.0, f64::max);
+
+        if max_magnitude <= 0.0 {
+            return;
+        }
+
+        let systems: Vec<_> = world
+            .systems()
+            .filter(|s| world.is_known_space(s.system_id))
+            .map(|s| {
+                let position = math::v2(s.position.x, s.position.z);
+                SystemData {
+                    center: (position / max_magnitude).as_f32(),
+                    highlight: math::V4::fill(0.0),
+                    color: super::sec_status_color(s.security_status).expand(0.8),
+                    system_id: s.system_id,
+                    scale: 1.0,
+                    radius: 5.0,
+                }
+            })
+            .collect();
+
+        self.systems_buffer = Some(self.context.display.fill_buffer(&systems));
+        self.context.request_redraw("minimap systems buffer");
+    }
+}
+
+impl Widget for Minimap {
+    fn update(
+        &mut self,
+        _dt: std::time::Duration,
+        input_state: &InputState,
+        world: &crate::world::World,
+    ) {
+        for event in input_state.user_events() {
+            match event {
+                UserEvent::DataEvent(DataEvent::GalaxyImported) => {
+                    self.build_systems(world);
+                }
+                UserEvent::MapEvent(MapEvent::ViewChanged {
+                    zoom,
+                    offset_x,
+                    offset_y,
+                }) => {
+                    self.view = Some((*zoom, math::v2(*offset_x, *offset_y)));
+                    self.dirty = true;
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(new_size) = input_state.window_resized() {
+            self.window_size = new_size.as_f32();
+            self.dirty = true;
+        }
+
+        self.capturing_pointer = self.systems_buffer.is_some()
+            && self.minimap_rect.contains(input_state.mouse_position());
+
+        if self.capturing_pointer && input_state.was_mouse_clicked(MouseButton::Left) {
+            let target = minimap_to_map(input_state.mouse_position(), self.minimap_rect);
+            input_state.send_user_event(UserEvent::MapEvent(MapEvent::CenterOn {
+                x: target.x,
+                y: target.y,
+            }));
+        }
+
+        if !self.dirty {
+            return;
+        }
+
+        let ui_scale = self.context.ui_scale();
+        let padding = 30.0 * ui_scale;
+        let size = MINIMAP_SIZE * ui_scale;
+        self.minimap_rect = math::Rect::new(
+            math::v2(padding, self.window_size.y - padding - size),
+            math::v2(padding + size, self.window_size.y - padding),
+        );
+
+        let half_window = self.window_size / 2.0;
+        let center = self.minimap_rect.min + math::V2::fill(size / 2.0);
+        self.scale_matrix = math::M3::<f32>::identity();
+        self.scale_matrix.c0.x = size / 2.0 / half_window.x;
+        self.scale_matrix.c1.y = size / 2.0 / half_window.y;
+        self.scale_matrix.c2.x = (center.x - half_window.x) / half_window.x;
+        self.scale_matrix.c2.y = -(center.y - half_window.y) / half_window.y;
+
+        self.viewport_rect = self.view.and_then(|(zoom, offset)| {
+            let window_scale = super::map::window_scale(self.window_size) / zoom;
+            let min = math::v2(offset.x - window_scale.x, -offset.y + window_scale.y);
+            let max = math::v2(offset.x + window_scale.x, -offset.y - window_scale.y);
+            let viewport = math::Rect::new(
+                map_to_minimap(min, self.minimap_rect),
+                map_to_minimap(max, self.minimap_rect),
+            );
+            clip_rect(viewport, self.minimap_rect)
+        });
+
+        self.context.request_redraw("minimap dirty");
+        self.dirty = false;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        if let Some(systems) = self.systems_buffer.as_ref() {
+            self.context.display.draw_quad(
+                frame,
+                &self.context.images,
+                math::v4(0.05, 0.05, 0.05, 0.9),
+                self.minimap_rect,
+            );
+
+            let dot_scale = MINIMAP_DOT_SIZE / (0.002 * MINIMAP_SIZE);
+            self.context.display.draw_system(
+                frame,
+                &self.circle_buffer,
+                systems,
+                5.0 * dot_scale,
+                self.scale_matrix,
+                math::M3::identity(),
+            );
+
+            if let Some(viewport) = self.viewport_rect {
+                self.context.display.draw_quad(
+                    frame,
+                    &self.context.images,
+                    math::v4(1.0, 1.0, 1.0, 0.15),
+                    viewport,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimap_positions_round_trip() {
+        let rect = math::Rect::new(math::v2(30.0, 700.0), math::v2(330.0, 1000.0));
+        let point = math::v2(0.25, -0.75);
+        let screen = map_to_minimap(point, rect);
+        assert!(minimap_to_map(screen, rect).distance(&point) < 0.0001);
+        assert_eq!(map_to_minimap(math::v2(-1.0, 1.0), rect), rect.min);
+    }
+
+    #[test]
+    fn viewport_is_clipped_to_minimap() {
+        let bounds = math::Rect::new(math::v2(0.0, 0.0), math::v2(100.0, 100.0));
+        let rect = math::Rect::new(math::v2(-50.0, 20.0), math::v2(50.0, 150.0));
+        let clipped = clip_rect(rect, bounds).unwrap();
+        assert_eq!(clipped.min, math::v2(0.0, 20.0));
+        assert_eq!(clipped.max, math::v2(50.0, 100.0));
+        let outside = math::Rect::new(math::v2(200.0, 200.0), math::v2(300.0, 300.0));
+        assert!(clip_rect(outside, bounds).is_none());
+    }
+}
@@ -0,0 +1,235 @@
+use std::rc::Rc;
+
+use winit::event::VirtualKeyCode;
+
+use super::command::{fuzzy_score, Command};
+use super::{font, GraphicsContext, InputState, UserEvent, Widget};
+use crate::math;
+use crate::platform::Frame;
+
+use font::TextAnchor;
+
+// ':' only opens the palette as the first character of the query box, so
+// prefixed queries like "region:" can still be typed
+pub fn palette_search(query_empty: bool, text: &str) -> Option<&str> {
+    text.strip_prefix(':').filter(|_| query_empty)
+}
+
+pub struct CommandPalette {
+    context: Rc<GraphicsContext>,
+    window_size: math::V2<f32>,
+    open: bool,
+    capturing_input: bool,
+    query_empty: bool,
+    search: String,
+    matches: Vec<Command>,
+    selected: usize,
+    text_spans: Vec<font::PositionedTextSpan>,
+    background_rect: Option<math::Rect<f32>>,
+    selected_rect: Option<math::Rect<f32>>,
+    dirty: bool,
+}
+
+impl CommandPalette {
+    pub fn new(context: Rc<GraphicsContext>) -> Self {
+        CommandPalette {
+            context,
+            window_size: math::v2(1024.0, 1024.0),
+            open: false,
+            capturing_input: false,
+            query_empty: true,
+            search: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            text_spans: Vec::new(),
+            background_rect: None,
+            selected_rect: None,
+            dirty: false,
+        }
+    }
+
+    pub fn capturing_input(&self) -> bool {
+        self.capturing_input
+    }
+
+    pub fn set_query_empty(&mut self, query_empty: bool) {
+        self.query_empty = query_empty;
+    }
+
+    fn close(&mut self) {
+        // Consume the closing key so Escape does not also clear routes this frame
+        self.capturing_input = true;
+        self.open = false;
+        self.search.clear();
+        self.dirty = true;
+    }
+
+    fn update_matches(&mut self) {
+        let settings = &self.context.settings;
+        let mut matches: Vec<_> = Command::ALL
+            .iter()
+            .filter(|c| c.available(settings))
+            .filter_map(|c| fuzzy_score(&self.search, c.name()).map(|s| (s, *c)))
+            .collect();
+        matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+        self.matches = matches.into_iter().map(|(_, c)| c).collect();
+        self.selected = self.selected.min(self.matches.len().saturating_sub(1));
+    }
+}
+
+impl Widget for CommandPalette {
+    fn update(
+        &mut self,
+        _dt: std::time::Duration,
+        input_state: &InputState,
+        _world: &crate::world::World,
+    ) {
+        self.capturing_input = self.open;
+
+        if let Some(new_size) = input_state.window_resized() {
+            self.window_size = new_size.as_f32();
+            self.dirty = true;
+        }
+
+        if !self.open {
+            if let Some(search) = palette_search(self.query_empty, input_state.text()) {
+                self.open = true;
+                self.capturing_input = true;
+                self.selected = 0;
+                self.search = search.to_string();
+                self.dirty = true;
+            }
+        } else {
+            if !input_state.text().is_empty() {
+                self.search.push_str(input_state.text());
+                self.selected = 0;
+                self.dirty = true;
+            }
+
+            if input_state.was_key_down(VirtualKeyCode::Back) {
+                crate::input::pop_grapheme(&mut self.search);
+                self.dirty = true;
+            }
+
+            if input_state.was_key_down(VirtualKeyCode::Down) {
+                self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1));
+                self.dirty = true;
+            }
+
+            if input_state.was_key_down(VirtualKeyCode::Up) {
+                self.selected = self.selected.saturating_sub(1);
+                self.dirty = true;
+            }
+
+            if input_state.was_key_down(VirtualKeyCode::Return) {
+                self.update_matches();
+                if let Some(command) = self.matches.get(self.selected) {
+                    log::info!("running command: {}", command.name());
+                    input_state.send_user_event(UserEvent::Command(*command));
+                }
+                self.close();
+            }
+
+            if input_state.was_key_down(VirtualKeyCode::Escape) {
+                self.close();
+            }
+        }
+
+        if !self.dirty {
+            return;
+        }
+
+        self.text_spans.clear();
+        self.background_rect = None;
+        self.selected_rect = None;
+
+        if self.open {
+            self.update_matches();
+
+            let ui_scale = self.context.ui_scale();
+            let padding = 30.0 * ui_scale;
+            let width = 700.0 * ui_scale;
+            let white = math::V4::fill(1.0);
+            let gray = math::v4(0.5, 0.5, 0.5, 1.0);
+
+            let mut background_rect = math::Rect::new(
+                math::v2((self.window_size.x - width) / 2.0, padding),
+                math::v2((self.window_size.x + width) / 2.0, padding),
+            );
+            let mut cursor = background_rect.min + math::V2::fill(padding);
+
+            let mut search_text = font::TextSpan::new(40.0 * ui_scale, self.context.ui_font, gray);
+            search_text
+                .push(": ")
+                .color(white)
+                .push(self.search.as_str());
+            let search_text =
+                self.context
+                    .font_cache
+                    .layout(search_text, TextAnchor::TopLeft, cursor, false);
+            cursor.y = search_text.bounds.max.y as f32 + padding / 2.0;
+            self.text_spans.push(search_text);
+
+            for (index, command) in self.matches.iter().enumerate() {
+                let mut command_text =
+                    font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, white);
+                command_text
+                    .push(command.name())
+                    .color(gray)
+                    .push(format!("  {:?}", command.key()));
+                let command_text = self.context.font_cache.layout(
+                    command_text,
+                    TextAnchor::TopLeft,
+                    cursor,
+                    false,
+                );
+
+                if index == self.selected {
+                    self.selected_rect = Some(math::Rect::new(
+                        math::v2(background_rect.min.x, cursor.y),
+                        math::v2(background_rect.max.x, command_text.bounds.max.y as f32),
+                    ));
+                }
+
+                cursor.y = command_text.bounds.max.y as f32;
+                self.text_spans.push(command_text);
+            }
+
+            background_rect.max.y = cursor.y + padding;
+            self.background_rect = Some(background_rect);
+        }
+
+        self.context.request_redraw("command palette dirty");
+        self.dirty = false;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        if let Some(background) = self.background_rect {
+            self.context.display.draw_quad(
+                frame,
+                &self.context.images,
+                math::v4(0.1, 0.1, 0.1, 0.95),
+                background,
+            );
+
+            if let Some(selected) = self.selected_rect {
+                self.context.display.draw_quad(
+                    frame,
+                    &self.context.images,
+                    math::v4(0.25, 0.25, 0.25, 1.0),
+                    selected,
+                );
+            }
+
+            if !self.text_spans.is_empty() {
+                self.context.display.draw_text(
+                    frame,
+                    &self.context.font_cache,
+                    &self.text_spans,
+                    self.context.ui_scale(),
+                );
+            }
+        }
+    }
+}
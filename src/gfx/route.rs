@@ -1,7 +1,9 @@
+use ahash::AHashMap as HashMap;
 use std::rc::Rc;
 
 use crate::math;
 use crate::platform::Frame;
+use crate::world::{RouteError, SecurityClass, Stats};
 
 use super::{
     font, DataEvent, GraphicsContext, InputState, QueryEvent, RouteEvent, UserEvent, Widget,
@@ -9,6 +11,9 @@ use super::{
 
 use font::TextAnchor;
 
+const DEFAULT_SECONDS_PER_JUMP: f64 = 45.0;
+const DEFAULT_DANGER_THRESHOLD: i32 = 5;
+
 pub struct RouteBox {
     context: Rc<GraphicsContext>,
     window_size: math::V2<f32>,
@@ -18,6 +23,8 @@ pub struct RouteBox {
     background_rect: Option<math::Rect<f32>>,
     dirty: bool,
     selected_system: Option<i32>,
+    route_failed: Option<RouteError>,
+    unresolved: Vec<String>,
 }
 
 impl RouteBox {
@@ -31,6 +38,8 @@ impl RouteBox {
             background_rect: None,
             dirty: true,
             selected_system: None,
+            route_failed: None,
+            unresolved: Vec::new(),
         }
     }
 
@@ -61,11 +70,26 @@ impl Widget for RouteBox {
         for event in input_state.user_events() {
             match event {
                 UserEvent::QueryEvent(QueryEvent::RouteChanged) => {
+                    self.route_failed = None;
+                    self.unresolved.clear();
+                    self.dirty = true;
+                }
+                UserEvent::QueryEvent(QueryEvent::SystemsUnresolved(names)) => {
+                    self.unresolved = names.clone();
+                    self.dirty = true;
+                }
+                UserEvent::QueryEvent(QueryEvent::RouteFailed(error)) => {
+                    self.route_failed = Some(*error);
                     self.dirty = true;
                 }
                 UserEvent::DataEvent(DataEvent::SovStandingsChanged) => {
                     self.dirty = true;
                 }
+                UserEvent::DataEvent(DataEvent::SystemStatsChanged)
+                    if self.context.settings.show_route_danger =>
+                {
+                    self.dirty = true;
+                }
                 UserEvent::DataEvent(DataEvent::CharacterLocationChanged(location)) => {
                     self.dirty = true;
                     self.player_location = location.clone();
@@ -92,7 +116,7 @@ impl Widget for RouteBox {
         let ui_scale = self.context.ui_scale();
         let padding = 30.0 * ui_scale;
 
-        if world.route_nodes().len() > 0 {
+        if world.routes().len() > 0 || self.route_failed.is_some() || !self.unresolved.is_empty() {
             let mut background_rect = math::Rect::new(
                 math::v2(padding, padding),
                 math::v2(padding + 650.0 * ui_scale, padding + 360.0 * ui_scale),
@@ -100,26 +124,74 @@ impl Widget for RouteBox {
 
             let mut cursor = background_rect.min + math::V2::fill(padding);
 
-            let player_on_route = self
-                .player_location
-                .map(|p| world.is_on_route(p))
-                .unwrap_or(false);
+            let white = math::V4::fill(1.0);
+            let multiple_routes = world.routes().len() > 1;
 
-            let mut visited = player_on_route;
-            let mut last_region = None;
-            let mut last_constellation = None;
+            let mut messages = Vec::new();
+            if let Some(error) = self.route_failed {
+                messages.push(world.route_error_message(error));
+            }
+            if !self.unresolved.is_empty() {
+                messages.push(format!("Unknown systems: {}", self.unresolved.join(", ")));
+            }
 
-            let white = math::V4::fill(1.0);
+            for message in messages {
+                let mut error_text = font::TextSpan::new(
+                    30.0 * ui_scale,
+                    self.context.ui_font,
+                    math::v4(1.0, 0.3, 0.3, 1.0),
+                );
+                error_text.push(message);
+                let error_text =
+                    self.context
+                        .font_cache
+                        .layout(error_text, TextAnchor::TopLeft, cursor, false);
+                cursor.y = error_text.bounds.max.y as f32 + padding;
+                self.text_spans.push(error_text);
+            }
+
+            for (route_index, route) in world.routes().iter().enumerate() {
+                if route_index > 0 {
+                    cursor.y += padding;
+                }
+
+                let player_on_route = self
+                    .player_location
+                    .map(|p| route.contains(p))
+                    .unwrap_or(false);
 
-            if let Some((start, end)) = world.route_target() {
+                let mut visited = player_on_route;
+                let mut last_region = None;
+                let danger: HashMap<i32, Stats> = if self.context.settings.show_route_danger {
+                    world.route_danger(route).into_iter().collect()
+                } else {
+                    HashMap::new()
+                };
+                let danger_threshold = self
+                    .context
+                    .settings
+                    .route_danger_threshold
+                    .unwrap_or(DEFAULT_DANGER_THRESHOLD);
+                let mut last_constellation = None;
+
+                let (start, end) = route.target();
                 if let (Some(start), Some(end)) = (world.system(start), world.system(end)) {
                     let mut title_text =
                         font::TextSpan::new(50.0 * ui_scale, self.context.ui_font, white);
+                    if multiple_routes {
+                        title_text
+                            .font(self.context.symbol_font)
+                            .color(super::route_color(route_index).expand(1.0))
+                            .push("● ")
+                            .font(self.context.ui_font)
+                            .color(white)
+                            .push(format!("{}: ", route.name()));
+                    }
                     title_text.push(format!(
                         "{} » {}: {} Jumps",
                         start.name,
                         end.name,
-                        world.route_nodes().len() - 1
+                        route.nodes().len() - 1
                     ));
 
                     let title_text = self.context.font_cache.layout(
@@ -130,98 +202,203 @@ impl Widget for RouteBox {
                     );
                     cursor.y = title_text.bounds.max.y as f32;
                     self.text_spans.push(title_text);
-                }
-            }
 
-            for node in world.route_nodes() {
-                let system = world.system(node.system_id);
+                    let summary = world.route_summary(route);
+                    let mut jumps_text =
+                        font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, white);
+                    jumps_text.push(format!(
+                        "{} Systems · {} Gates",
+                        summary.systems, summary.gates
+                    ));
+                    if summary.jump_bridges > 0 {
+                        jumps_text.push(format!(" · {} Bridges", summary.jump_bridges));
+                    }
+                    if summary.wormholes > 0 {
+                        jumps_text.push(format!(" · {} Wormholes", summary.wormholes));
+                    }
+
+                    let jumps_text = self.context.font_cache.layout(
+                        jumps_text,
+                        TextAnchor::TopLeft,
+                        cursor,
+                        false,
+                    );
+                    cursor.y = jumps_text.bounds.max.y as f32;
+                    self.text_spans.push(jumps_text);
+
+                    let security = summary.security;
+                    let mut security_text =
+                        font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, white);
+                    security_text
+                        .color(super::security_class_color(SecurityClass::High).expand(1.0))
+                        .push(format!("{} High", security.high))
+                        .color(white)
+                        .push(" · ")
+                        .color(super::security_class_color(SecurityClass::Low).expand(1.0))
+                        .push(format!("{} Low", security.low))
+                        .color(white)
+                        .push(" · ")
+                        .color(super::security_class_color(SecurityClass::Null).expand(1.0))
+                        .push(format!("{} Null", security.null));
+                    if security.wormhole > 0 {
+                        security_text
+                            .color(white)
+                            .push(" · ")
+                            .color(super::security_class_color(SecurityClass::Wormhole).expand(1.0))
+                            .push(format!("{} Wormhole", security.wormhole));
+                    }
 
-                if system.is_none() {
-                    continue;
+                    let security_text = self.context.font_cache.layout(
+                        security_text,
+                        TextAnchor::TopLeft,
+                        cursor,
+                        false,
+                    );
+                    cursor.y = security_text.bounds.max.y as f32;
+                    self.text_spans.push(security_text);
+
+                    let player_index = self
+                        .player_location
+                        .and_then(|p| route.nodes().iter().position(|n| n.system_id == p));
+                    if let Some(player_index) = player_index {
+                        let total = route.nodes().len() - 1;
+                        let remaining = total - player_index;
+                        let seconds_per_jump = self
+                            .context
+                            .settings
+                            .seconds_per_jump
+                            .unwrap_or(DEFAULT_SECONDS_PER_JUMP);
+                        let eta = (remaining as f64 * seconds_per_jump / 60.0).ceil();
+
+                        let mut progress_text =
+                            font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, white);
+                        progress_text.push(format!(
+                            "{} / {} jumps, {} remaining · ETA ~{}m",
+                            player_index, total, remaining, eta
+                        ));
+
+                        let progress_text = self.context.font_cache.layout(
+                            progress_text,
+                            TextAnchor::TopLeft,
+                            cursor,
+                            false,
+                        );
+                        cursor.y = progress_text.bounds.max.y as f32;
+                        self.text_spans.push(progress_text);
+                    }
                 }
-                let system = system.unwrap();
-
-                let constellation = world.constellation(system.constellation_id);
-                let region = constellation
-                    .as_ref()
-                    .and_then(|c| world.region(c.region_id));
-                let sov = world.sov_standing(system.system_id);
-                let alliance = sov
-                    .as_ref()
-                    .and_then(|s| s.alliance_id)
-                    .and_then(|a| world.alliance(a));
-
-                let player_system = Some(system.system_id) == self.player_location;
-                visited = !(player_system || !visited);
-
-                let system_color = if visited && !player_system {
-                    math::V3::fill(0.3).expand(1.0)
-                } else {
-                    white
-                };
 
-                let (jump_color, jump_text) = if player_system {
-                    (math::V4::new(1.0, 0.0, 0.0, 1.0), "▶ ")
-                } else if node.arrive_jump.is_some() {
-                    (
-                        super::jump_type_color(node.arrive_jump.as_ref().unwrap()).expand(1.0),
-                        //"1·2•3∙4●5⚫6⬤78 ",
-                        "● ",
-                    )
-                } else {
-                    (
-                        super::jump_type_color(&crate::world::JumpType::System).expand(1.0),
-                        "● ",
-                    )
-                };
+                for node in route.nodes() {
+                    let system = world.system(node.system_id);
 
-                let system_sec_color = super::sec_status_color(system.security_status).expand(1.0);
-                let standings_color =
-                    super::standing_color(sov.map(|s| s.standing).unwrap_or(0.0)).expand(1.0);
-
-                let mut node_text =
-                    font::TextSpan::new(30.0 * ui_scale, self.context.symbol_font, jump_color);
-                node_text
-                    .push(jump_text)
-                    .font(self.context.ui_font)
-                    .color(system_color)
-                    .push(&system.name)
-                    .color(white)
-                    .push(" (")
-                    .color(system_sec_color)
-                    .push(format!("{:.2}", system.security_status))
-                    .color(white)
-                    .push(") ");
-
-                if let Some(alliance) = alliance {
+                    if system.is_none() {
+                        continue;
+                    }
+                    let system = system.unwrap();
+
+                    let constellation = world.constellation(system.constellation_id);
+                    let region = constellation
+                        .as_ref()
+                        .and_then(|c| world.region(c.region_id));
+                    let sov = world.sov_standing(system.system_id);
+                    let alliance = sov
+                        .as_ref()
+                        .and_then(|s| s.alliance_id)
+                        .and_then(|a| world.alliance(a));
+
+                    let player_system = Some(system.system_id) == self.player_location;
+                    visited = !(player_system || !visited);
+
+                    let system_color = if visited && !player_system {
+                        math::V3::fill(0.3).expand(1.0)
+                    } else {
+                        white
+                    };
+
+                    let (jump_color, jump_text) = if player_system {
+                        (math::V4::new(1.0, 0.0, 0.0, 1.0), "▶ ")
+                    } else if node.arrive_jump.is_some() {
+                        (
+                            super::jump_type_color(node.arrive_jump.as_ref().unwrap()).expand(1.0),
+                            //"1·2•3∙4●5⚫6⬤78 ",
+                            "● ",
+                        )
+                    } else {
+                        (
+                            super::jump_type_color(&crate::world::JumpType::System).expand(1.0),
+                            "● ",
+                        )
+                    };
+
+                    let system_sec_color =
+                        super::sec_status_color(system.security_status).expand(1.0);
+                    let standings_color =
+                        super::standing_color(sov.map(|s| s.standing).unwrap_or(0.0)).expand(1.0);
+
+                    let mut node_text =
+                        font::TextSpan::new(30.0 * ui_scale, self.context.symbol_font, jump_color);
                     node_text
-                        .color(standings_color)
-                        .push(format!("[{}] ", alliance.ticker))
-                        .color(white);
-                }
+                        .push(jump_text)
+                        .font(self.context.ui_font)
+                        .color(system_color)
+                        .push(&system.name)
+                        .color(white)
+                        .push(" (")
+                        .color(system_sec_color)
+                        .push(format!("{:.2}", system.security_status))
+                        .color(white)
+                        .push(") ");
+
+                    if let Some(alliance) = alliance {
+                        node_text
+                            .color(standings_color)
+                            .push(format!("[{}] ", alliance.ticker))
+                            .color(white);
+                    }
 
-                if last_region != region.map(|r| r.region_id) {
-                    if let (Some(constellation), Some(region)) = (constellation, region) {
-                        node_text.push(format!("» {} » {} ", constellation.name, region.name));
+                    if last_region != region.map(|r| r.region_id) {
+                        if let (Some(constellation), Some(region)) = (constellation, region) {
+                            node_text.push(format!("» {} » {} ", constellation.name, region.name));
+                        }
+                    } else if last_constellation != constellation.map(|c| c.constellation_id) {
+                        if let Some(constellation) = constellation {
+                            node_text.push(format!("» {} ", constellation.name));
+                        }
                     }
-                } else if last_constellation != constellation.map(|c| c.constellation_id) {
-                    if let Some(constellation) = constellation {
-                        node_text.push(format!("» {} ", constellation.name));
+
+                    if let Some(stats) = danger.get(&system.system_id) {
+                        let kills = stats.ship_kills + stats.pod_kills;
+                        if kills > 0 {
+                            let danger_color = if kills > danger_threshold {
+                                math::v4(1.0, 0.2, 0.2, 1.0)
+                            } else {
+                                math::v4(0.8, 0.8, 0.8, 1.0)
+                            };
+                            node_text
+                                .font(self.context.symbol_font)
+                                .color(danger_color)
+                                .push(format!("⚔{} 💀{}", stats.ship_kills, stats.pod_kills))
+                                .font(self.context.ui_font)
+                                .color(white);
+                        }
                     }
-                }
 
-                let node_text =
-                    self.context
-                        .font_cache
-                        .layout(node_text, TextAnchor::TopLeft, cursor, false);
-                cursor.y = node_text.bounds.max.y as f32;
+                    let node_text = self.context.font_cache.layout(
+                        node_text,
+                        TextAnchor::TopLeft,
+                        cursor,
+                        false,
+                    );
+                    cursor.y = node_text.bounds.max.y as f32;
 
-                last_region = region.map(|r| r.region_id);
-                last_constellation = constellation.map(|c| c.constellation_id);
+                    last_region = region.map(|r| r.region_id);
+                    last_constellation = constellation.map(|c| c.constellation_id);
 
-                self.node_bounds.push((node.system_id, node_text.bounds));
-                self.text_spans.push(node_text);
+                    self.node_bounds.push((node.system_id, node_text.bounds));
+                    self.text_spans.push(node_text);
+                }
             }
+
             background_rect.max.y = cursor.y + padding;
 
             self.background_rect = Some(background_rect);
@@ -0,0 +1,191 @@
+use std::rc::Rc;
+
+use winit::event::MouseButton;
+
+use super::{font, GraphicsContext, InputState, MapEvent, QueryEvent, UserEvent, Widget};
+use crate::math;
+use crate::platform::Frame;
+
+use font::TextAnchor;
+
+const MAX_SEARCH_RESULTS: usize = 10;
+
+pub struct SearchResults {
+    context: Rc<GraphicsContext>,
+    window_size: math::V2<f32>,
+    systems: Vec<i32>,
+    hovered: Option<usize>,
+    rows: Vec<(i32, math::Rect<f32>)>,
+    text_spans: Vec<font::PositionedTextSpan>,
+    background_rect: Option<math::Rect<f32>>,
+    capturing_pointer: bool,
+    dirty: bool,
+}
+
+impl SearchResults {
+    pub fn new(context: Rc<GraphicsContext>) -> Self {
+        SearchResults {
+            context,
+            window_size: math::v2(1024.0, 1024.0),
+            systems: Vec::new(),
+            hovered: None,
+            rows: Vec::new(),
+            text_spans: Vec::new(),
+            background_rect: None,
+            capturing_pointer: false,
+            dirty: false,
+        }
+    }
+
+    pub fn capturing_pointer(&self) -> bool {
+        self.capturing_pointer
+    }
+}
+
+impl Widget for SearchResults {
+    fn update(
+        &mut self,
+        _dt: std::time::Duration,
+        input_state: &InputState,
+        world: &crate::world::World,
+    ) {
+        for event in input_state.user_events() {
+            if let UserEvent::QueryEvent(QueryEvent::SystemsMatched(systems)) = event {
+                self.systems = systems.clone();
+                self.hovered = None;
+                self.dirty = true;
+            }
+        }
+
+        if let Some(new_size) = input_state.window_resized() {
+            self.window_size = new_size.as_f32();
+            self.dirty = true;
+        }
+
+        self.capturing_pointer = self
+            .background_rect
+            .is_some_and(|r| r.contains(input_state.mouse_position()));
+
+        if input_state.mouse_move_delta() != math::V2::fill(0.0) {
+            let mouse_position = input_state.mouse_position();
+            let hovered = self
+                .rows
+                .iter()
+                .position(|(_, r)| r.contains(mouse_position));
+            if hovered != self.hovered {
+                self.hovered = hovered;
+                self.context.request_redraw("search results hover");
+            }
+        }
+
+        if input_state.was_mouse_clicked(MouseButton::Left) {
+            if let Some((system_id, _)) = self.hovered.and_then(|i| self.rows.get(i)) {
+                input_state.send_user_event(UserEvent::MapEvent(MapEvent::SelectedSystemChanged(
+                    Some(*system_id),
+                )));
+                input_state
+                    .send_user_event(UserEvent::MapEvent(MapEvent::CenterOnSystem(*system_id)));
+            }
+        }
+
+        if !self.dirty {
+            return;
+        }
+
+        self.text_spans.clear();
+        self.rows.clear();
+        self.background_rect = None;
+
+        if !self.systems.is_empty() {
+            let ui_scale = self.context.ui_scale();
+            let padding = 30.0 * ui_scale;
+            let width = 650.0 * ui_scale;
+            let white = math::V4::fill(1.0);
+            let gray = math::v4(0.6, 0.6, 0.6, 1.0);
+
+            let top = padding * 2.0 + 360.0 * ui_scale;
+            let mut background_rect = math::Rect::new(
+                math::v2(self.window_size.x - padding - width, top),
+                math::v2(self.window_size.x - padding, top),
+            );
+            let mut cursor = background_rect.min + math::V2::fill(padding);
+
+            for system_id in self.systems.iter().take(MAX_SEARCH_RESULTS) {
+                let system = match world.system(*system_id) {
+                    Some(system) => system,
+                    None => continue,
+                };
+                let region = world
+                    .constellation(system.constellation_id)
+                    .and_then(|c| world.region(c.region_id));
+
+                let mut text = font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, white);
+                text.push(&system.name).push(" ");
+                text.color(super::sec_status_color(system.security_status).expand(1.0))
+                    .push(format!("{:.1}", system.security_status));
+                if let Some(region) = region {
+                    text.color(gray).push(format!("  {}", region.name));
+                }
+                let text = self
+                    .context
+                    .font_cache
+                    .layout(text, TextAnchor::TopLeft, cursor, false);
+
+                let row = math::Rect::new(
+                    math::v2(background_rect.min.x, cursor.y),
+                    math::v2(background_rect.max.x, text.bounds.max.y as f32),
+                );
+                self.rows.push((*system_id, row));
+                cursor.y = text.bounds.max.y as f32;
+                self.text_spans.push(text);
+            }
+
+            let remaining = self.systems.len().saturating_sub(MAX_SEARCH_RESULTS);
+            if remaining > 0 {
+                let mut text = font::TextSpan::new(25.0 * ui_scale, self.context.ui_font, gray);
+                text.push(format!("and {} more", remaining));
+                let text = self
+                    .context
+                    .font_cache
+                    .layout(text, TextAnchor::TopLeft, cursor, false);
+                cursor.y = text.bounds.max.y as f32;
+                self.text_spans.push(text);
+            }
+
+            background_rect.max.y = cursor.y + padding;
+            self.background_rect = Some(background_rect);
+        }
+
+        self.context.request_redraw("search results dirty");
+        self.dirty = false;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        if let Some(background) = self.background_rect {
+            self.context.display.draw_quad(
+                frame,
+                &self.context.images,
+                math::v4(0.1, 0.1, 0.1, 0.8),
+                background,
+            );
+
+            if let Some((_, row)) = self.hovered.and_then(|i| self.rows.get(i)) {
+                self.context.display.draw_quad(
+                    frame,
+                    &self.context.images,
+                    math::v4(0.25, 0.25, 0.25, 1.0),
+                    *row,
+                );
+            }
+
+            if !self.text_spans.is_empty() {
+                self.context.display.draw_text(
+                    frame,
+                    &self.context.font_cache,
+                    &self.text_spans,
+                    self.context.ui_scale(),
+                );
+            }
+        }
+    }
+}
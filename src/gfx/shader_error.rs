@@ -0,0 +1,105 @@
+use std::rc::Rc;
+
+use super::{font, GraphicsContext, InputState, Widget};
+use crate::math;
+use crate::platform::Frame;
+
+use font::TextAnchor;
+
+pub struct ShaderErrorBox {
+    context: Rc<GraphicsContext>,
+    window_size: math::V2<f32>,
+    errors: Vec<String>,
+    text_spans: Vec<font::PositionedTextSpan>,
+    background_rect: Option<math::Rect<f32>>,
+}
+
+impl ShaderErrorBox {
+    pub fn new(context: Rc<GraphicsContext>) -> Self {
+        ShaderErrorBox {
+            context,
+            window_size: math::v2(1024.0, 1024.0),
+            errors: Vec::new(),
+            text_spans: Vec::new(),
+            background_rect: None,
+        }
+    }
+}
+
+impl Widget for ShaderErrorBox {
+    fn update(
+        &mut self,
+        _dt: std::time::Duration,
+        input_state: &InputState,
+        _world: &crate::world::World,
+    ) {
+        if !self.context.settings.dev_mode {
+            return;
+        }
+
+        let mut dirty = false;
+        if let Some(new_size) = input_state.window_resized() {
+            self.window_size = new_size.as_f32();
+            dirty = true;
+        }
+
+        let errors = self.context.display.shader_errors();
+        if errors != self.errors {
+            self.errors = errors;
+            dirty = true;
+        }
+
+        if !dirty {
+            return;
+        }
+
+        self.text_spans.clear();
+        self.background_rect = None;
+
+        if !self.errors.is_empty() {
+            let ui_scale = self.context.ui_scale();
+            let padding = 30.0 * ui_scale;
+            let error_color = math::v4(1.0, 0.3, 0.3, 1.0);
+
+            let mut cursor = math::v2(padding * 2.0, padding * 2.0);
+            for line in self.errors.iter().flat_map(|e| e.lines()) {
+                let mut text =
+                    font::TextSpan::new(30.0 * ui_scale, self.context.ui_font, error_color);
+                text.push(line);
+                let text = self
+                    .context
+                    .font_cache
+                    .layout(text, TextAnchor::TopLeft, cursor, false);
+                cursor.y = text.bounds.max.y as f32;
+                self.text_spans.push(text);
+            }
+
+            self.background_rect = Some(math::Rect::new(
+                math::v2(padding, padding),
+                math::v2(self.window_size.x - padding, cursor.y + padding),
+            ));
+        }
+
+        self.context.request_redraw("shader errors changed");
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        if let Some(background) = self.background_rect {
+            self.context.display.draw_quad(
+                frame,
+                &self.context.images,
+                math::v4(0.1, 0.0, 0.0, 0.9),
+                background,
+            );
+
+            if !self.text_spans.is_empty() {
+                self.context.display.draw_text(
+                    frame,
+                    &self.context.font_cache,
+                    &self.text_spans,
+                    self.context.ui_scale(),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::rc::Rc;
+
+use super::{font, GraphicsContext, InputState, Severity, UserEvent, Widget};
+use crate::math;
+use crate::platform::time::Instant;
+use crate::platform::Frame;
+
+use font::TextAnchor;
+
+const TOAST_DURATION: f32 = 5.0;
+const MAX_TOASTS: usize = 4;
+
+struct Toast {
+    message: String,
+    severity: Severity,
+    remaining: f32,
+}
+
+fn push_toast(toasts: &mut Vec<Toast>, message: &str, severity: Severity) {
+    toasts.retain(|t| t.message != message || t.severity != severity);
+    toasts.push(Toast {
+        message: message.to_string(),
+        severity,
+        remaining: TOAST_DURATION,
+    });
+    if toasts.len() > MAX_TOASTS {
+        toasts.remove(0);
+    }
+}
+
+fn expire_toasts(toasts: &mut Vec<Toast>, dt: f32) -> bool {
+    let count = toasts.len();
+    for toast in toasts.iter_mut() {
+        toast.remaining -= dt;
+    }
+    toasts.retain(|t| t.remaining > 0.0);
+    toasts.len() != count
+}
+
+fn severity_color(severity: Severity) -> math::V4<f32> {
+    match severity {
+        Severity::Info => math::v4(0.3, 0.6, 1.0, 1.0),
+        Severity::Warning => math::v4(1.0, 0.8, 0.2, 1.0),
+        Severity::Error => math::v4(1.0, 0.3, 0.3, 1.0),
+    }
+}
+
+pub struct Toasts {
+    context: Rc<GraphicsContext>,
+    window_size: math::V2<f32>,
+    toasts: Vec<Toast>,
+    text_spans: Vec<font::PositionedTextSpan>,
+    backgrounds: Vec<(math::Rect<f32>, math::Rect<f32>, Severity)>,
+    dirty: bool,
+}
+
+impl Toasts {
+    pub fn new(context: Rc<GraphicsContext>) -> Self {
+        Toasts {
+            context,
+            window_size: math::v2(1024.0, 1024.0),
+            toasts: Vec::new(),
+            text_spans: Vec::new(),
+            backgrounds: Vec::new(),
+            dirty: false,
+        }
+    }
+}
+
+impl Widget for Toasts {
+    fn update(
+        &mut self,
+        dt: std::time::Duration,
+        input_state: &InputState,
+        _world: &crate::world::World,
+    ) {
+        if !self.toasts.is_empty() {
+            self.dirty |= expire_toasts(&mut self.toasts, dt.as_secs_f32());
+        }
+
+        for event in input_state.user_events() {
+            if let UserEvent::Notice(message, severity) = event {
+                push_toast(&mut self.toasts, message, *severity);
+                self.dirty = true;
+            }
+        }
+
+        if let Some(new_size) = input_state.window_resized() {
+            self.window_size = new_size.as_f32();
+            self.dirty = true;
+        }
+
+        let next_expiry = self.toasts.iter().map(|t| t.remaining).reduce(f32::min);
+        if let Some(remaining) = next_expiry {
+            let remaining = std::time::Duration::from_secs_f32(remaining.max(0.0));
+            self.context.request_wake(Instant::now() + remaining);
+        }
+
+        if !self.dirty {
+            return;
+        }
+
+        self.text_spans.clear();
+        self.backgrounds.clear();
+
+        let ui_scale = self.context.ui_scale();
+        let padding = 15.0 * ui_scale;
+        let stripe = 6.0 * ui_scale;
+        let white = math::V4::fill(1.0);
+        let mut bottom = self.window_size.y - padding * 3.0;
+
+        for toast in self.toasts.iter().rev() {
+            let mut text = font::TextSpan::new(28.0 * ui_scale, self.context.ui_font, white);
+            text.push(&toast.message);
+            let cursor = math::v2(self.window_size.x / 2.0, bottom - padding);
+            let text = self
+                .context
+                .font_cache
+                .layout(text, TextAnchor::Bottom, cursor, false);
+
+            let background = math::Rect::new(
+                text.bounds.min.as_f32() - math::v2(padding + stripe, padding),
+                text.bounds.max.as_f32() + math::V2::fill(padding),
+            );
+            let indicator = math::Rect::new(
+                background.min,
+                math::v2(background.min.x + stripe, background.max.y),
+            );
+            bottom = background.min.y - padding / 2.0;
+
+            self.text_spans.push(text);
+            self.backgrounds
+                .push((background, indicator, toast.severity));
+        }
+
+        self.context.request_redraw("toasts dirty");
+        self.dirty = false;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        if self.backgrounds.is_empty() {
+            return;
+        }
+
+        for (background, indicator, severity) in self.backgrounds.iter() {
+            self.context.display.draw_quad(
+                frame,
+                &self.context.images,
+                math::v4(0.1, 0.1, 0.1, 0.85),
+                *background,
+            );
+            self.context.display.draw_quad(
+                frame,
+                &self.context.images,
+                severity_color(*severity),
+                *indicator,
+            );
+        }
+
+        self.context.display.draw_text(
+            frame,
+            &self.context.font_cache,
+            &self.text_spans,
+            self.context.ui_scale(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toasts_expire_and_collapse_duplicates() {
+        let mut toasts = Vec::new();
+        push_toast(&mut toasts, "first", Severity::Info);
+        push_toast(&mut toasts, "second", Severity::Error);
+        push_toast(&mut toasts, "first", Severity::Info);
+        assert_eq!(toasts.len(), 2);
+        assert_eq!(toasts[1].message, "first");
+
+        for i in 0..MAX_TOASTS + 2 {
+            push_toast(&mut toasts, &i.to_string(), Severity::Warning);
+        }
+        assert_eq!(toasts.len(), MAX_TOASTS);
+
+        assert!(!expire_toasts(&mut toasts, TOAST_DURATION / 2.0));
+        assert!(expire_toasts(&mut toasts, TOAST_DURATION / 2.0));
+        assert!(toasts.is_empty());
+    }
+}
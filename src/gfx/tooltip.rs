@@ -0,0 +1,168 @@
+use std::rc::Rc;
+
+use winit::event::MouseButton;
+
+use super::{font, DataEvent, GraphicsContext, InputState, MapEvent, UserEvent, Widget};
+use crate::math;
+use crate::platform::Frame;
+
+use font::TextAnchor;
+
+const TOOLTIP_OFFSET: f32 = 24.0;
+
+pub struct SystemTooltip {
+    context: Rc<GraphicsContext>,
+    window_size: math::V2<f32>,
+    system: Option<i32>,
+    suppressed: bool,
+    capturing_input: bool,
+    text_spans: Vec<font::PositionedTextSpan>,
+    background_rect: Option<math::Rect<f32>>,
+    dirty: bool,
+}
+
+impl SystemTooltip {
+    pub fn new(context: Rc<GraphicsContext>) -> Self {
+        SystemTooltip {
+            context,
+            window_size: math::v2(1024.0, 1024.0),
+            system: None,
+            suppressed: false,
+            capturing_input: false,
+            text_spans: Vec::new(),
+            background_rect: None,
+            dirty: false,
+        }
+    }
+
+    pub fn set_capturing_input(&mut self, capturing_input: bool) {
+        self.capturing_input = capturing_input;
+    }
+}
+
+impl Widget for SystemTooltip {
+    fn update(
+        &mut self,
+        _dt: std::time::Duration,
+        input_state: &InputState,
+        world: &crate::world::World,
+    ) {
+        for event in input_state.user_events() {
+            match event {
+                UserEvent::MapEvent(MapEvent::SelectedSystemChanged(system)) => {
+                    self.system = *system;
+                    self.dirty = true;
+                }
+                UserEvent::DataEvent(DataEvent::SovStandingsChanged) => {
+                    self.dirty = true;
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(new_size) = input_state.window_resized() {
+            self.window_size = new_size.as_f32();
+            self.dirty = true;
+        }
+
+        let busy = self.capturing_input
+            || input_state.is_mouse_down(MouseButton::Left)
+            || input_state.is_mouse_down(MouseButton::Right)
+            || input_state.scroll() != 0.0
+            || input_state.pinch_delta() != 0.0
+            || input_state.scroll_pan() != math::V2::fill(0.0)
+            || !input_state.text().is_empty();
+        let moved = input_state.mouse_move_delta() != math::V2::fill(0.0);
+
+        if busy && !self.suppressed {
+            self.suppressed = true;
+            self.dirty = true;
+        } else if !busy && moved {
+            self.suppressed = false;
+            self.dirty |= self.system.is_some() || self.background_rect.is_some();
+        }
+
+        if !self.dirty {
+            return;
+        }
+
+        self.text_spans.clear();
+        self.background_rect = None;
+
+        let system = self
+            .system
+            .filter(|_| !self.suppressed)
+            .and_then(|id| world.system(id));
+
+        if let Some(system) = system {
+            let ui_scale = self.context.ui_scale();
+            let padding = 10.0 * ui_scale;
+            let white = math::V4::fill(1.0);
+            let gray = math::v4(0.6, 0.6, 0.6, 1.0);
+
+            let ticker = world
+                .sov_standing(system.system_id)
+                .and_then(|sov| sov.alliance_id)
+                .and_then(|alliance_id| world.alliance(alliance_id))
+                .map(|alliance| alliance.ticker);
+
+            let layout = |cursor: math::V2<f32>| {
+                let mut text = font::TextSpan::new(25.0 * ui_scale, self.context.ui_font, white);
+                text.push(&system.name).push(" ");
+                text.color(super::sec_status_color(system.security_status).expand(1.0))
+                    .push(format!("{:.1}", system.security_status));
+                if let Some(ticker) = ticker.as_ref() {
+                    text.color(gray).push(format!(" [{}]", ticker));
+                }
+
+                let text = self
+                    .context
+                    .font_cache
+                    .layout(text, TextAnchor::TopLeft, cursor, false);
+                let background_rect = math::Rect::new(
+                    text.bounds.min.as_f32() - math::V2::fill(padding),
+                    text.bounds.max.as_f32() + math::V2::fill(padding),
+                );
+                (text, background_rect)
+            };
+
+            let offset = TOOLTIP_OFFSET * ui_scale + padding;
+            let cursor = input_state.mouse_position() + math::V2::fill(offset);
+            let (mut text, mut background_rect) = layout(cursor);
+
+            let overflow = math::v2(
+                (background_rect.max.x - self.window_size.x).max(0.0),
+                (background_rect.max.y - self.window_size.y).max(0.0),
+            );
+            if overflow != math::V2::fill(0.0) {
+                let (shifted_text, shifted_rect) = layout(cursor - overflow);
+                text = shifted_text;
+                background_rect = shifted_rect;
+            }
+
+            self.text_spans.push(text);
+            self.background_rect = Some(background_rect);
+        }
+
+        self.context.request_redraw("system tooltip dirty");
+        self.dirty = false;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        if let Some(background) = self.background_rect {
+            self.context.display.draw_quad(
+                frame,
+                &self.context.images,
+                math::v4(0.1, 0.1, 0.1, 0.85),
+                background,
+            );
+
+            self.context.display.draw_text(
+                frame,
+                &self.context.font_cache,
+                &self.text_spans,
+                self.context.ui_scale(),
+            );
+        }
+    }
+}
@@ -4,23 +4,39 @@ use winit::event_loop::EventLoopProxy;
 
 use crate::gfx::UserEvent;
 use crate::math;
+use crate::platform::time::Instant;
 use crate::platform::{EventReceiver, EventSender};
+use crate::replay::EventRecorder;
+
+use std::time::Duration;
+
+const CLICK_DISTANCE_SQUARED: f32 = 25.0;
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
 
 pub struct InputState {
-    event_sender: EventSender,
+    event_sender: Option<EventSender>,
     event_receiver: EventReceiver,
     closed: bool,
+    focus_gained: bool,
     text: String,
     pressed_keys: HashSet<winit::event::VirtualKeyCode>,
     released_keys: HashSet<winit::event::VirtualKeyCode>,
     mouse_wheel_delta: f32,
+    scroll_pan_delta: math::V2<f32>,
+    pinch_delta: f32,
     window_size: math::V2<u32>,
     window_start_size: math::V2<u32>,
+    layout_invalidated: bool,
     mouse_position: math::V2<f32>,
     mouse_start_position: math::V2<f32>,
+    mouse_press_position: math::V2<f32>,
     pressed_mouse: HashSet<winit::event::MouseButton>,
     released_mouse: HashSet<winit::event::MouseButton>,
+    last_click: Option<(Instant, math::V2<f32>)>,
+    double_clicked: bool,
     user_events: Vec<UserEvent>,
+    keys_captured: bool,
+    recorder: Option<EventRecorder>,
 }
 
 impl InputState {
@@ -28,57 +44,105 @@ impl InputState {
         event_sender: EventSender,
         event_receiver: EventReceiver,
         window_size: math::V2<u32>,
+    ) -> InputState {
+        InputState::with_sender(Some(event_sender), event_receiver, window_size)
+    }
+
+    // event loop proxies need a display, so tests run without one
+    #[cfg(all(test, not(target_arch = "wasm32")))]
+    pub fn detached(window_size: math::V2<u32>) -> InputState {
+        InputState::with_sender(None, (), window_size)
+    }
+
+    fn with_sender(
+        event_sender: Option<EventSender>,
+        event_receiver: EventReceiver,
+        window_size: math::V2<u32>,
     ) -> InputState {
         InputState {
             event_sender,
             event_receiver,
             closed: false,
+            focus_gained: false,
             text: String::new(),
             pressed_keys: HashSet::new(),
             released_keys: HashSet::new(),
             mouse_wheel_delta: 0.0,
+            scroll_pan_delta: math::V2::fill(0.0),
+            pinch_delta: 0.0,
             window_size,
             window_start_size: math::V2::fill(1024),
+            layout_invalidated: false,
             mouse_position: math::V2::fill(0.0),
             mouse_start_position: math::V2::fill(0.0),
+            mouse_press_position: math::V2::fill(0.0),
             pressed_mouse: HashSet::new(),
             released_mouse: HashSet::new(),
+            last_click: None,
+            double_clicked: false,
             user_events: Vec::new(),
+            keys_captured: false,
+            recorder: None,
         }
     }
 
+    pub fn record_events(&mut self, recorder: EventRecorder) {
+        self.recorder = Some(recorder);
+    }
+
+    pub fn take_recorder(&mut self) -> Option<EventRecorder> {
+        self.recorder.take()
+    }
+
     pub fn received_user_events(&mut self) -> impl Iterator<Item = UserEvent> {
         self.event_receiver.user_event_iter()
     }
 
     pub fn push_user_event(&mut self, event: UserEvent) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.record(&event);
+        }
         self.user_events.push(event);
     }
 
     pub fn send_user_event(&self, event: UserEvent) {
-        self.event_sender.send_user_event(event);
+        if let Some(event_sender) = self.event_sender.as_ref() {
+            event_sender.send_user_event(event);
+        }
     }
 
     pub fn reset(&mut self) {
         self.mouse_start_position = self.mouse_position;
         self.mouse_wheel_delta = 0.0;
+        self.scroll_pan_delta = math::V2::fill(0.0);
+        self.pinch_delta = 0.0;
         self.window_start_size = self.window_size;
+        self.layout_invalidated = false;
         self.released_keys.clear();
         self.released_mouse.clear();
+        self.double_clicked = false;
         self.text.clear();
+        self.focus_gained = false;
         self.user_events.clear();
+        self.keys_captured = false;
     }
 
     pub fn process(&mut self, event: Event<UserEvent>) {
         use winit::event::*;
         match event {
-            Event::UserEvent(user_event) => self.user_events.push(user_event),
+            Event::UserEvent(user_event) => self.push_user_event(user_event),
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
             } => {
                 self.closed = true;
             }
+            Event::WindowEvent {
+                event: WindowEvent::Focused(focused),
+                ..
+            } => {
+                self.focus_gained = focused;
+            }
             Event::WindowEvent {
                 event: WindowEvent::ReceivedCharacter(c),
                 ..
@@ -113,12 +177,23 @@ impl InputState {
                 event: WindowEvent::MouseWheel { delta, .. },
                 ..
             } => {
-                let delta = match delta {
-                    MouseScrollDelta::LineDelta(_x, y) => y * 5.0,
-                    MouseScrollDelta::PixelDelta(pos) => pos.y as f32,
-                };
-
-                self.mouse_wheel_delta += delta;
+                let control = self.is_key_down(VirtualKeyCode::LControl)
+                    || self.is_key_down(VirtualKeyCode::RControl);
+                match delta {
+                    MouseScrollDelta::LineDelta(_x, y) => self.mouse_wheel_delta += y * 5.0,
+                    // browsers report every wheel in pixels, so only desktop touchpads pan
+                    MouseScrollDelta::PixelDelta(pos) if cfg!(target_arch = "wasm32") => {
+                        self.mouse_wheel_delta += pos.y as f32
+                    }
+                    // winit 0.26 has no touchpad magnify event, so pinches can't be told apart
+                    // from scrolls. Control + touchpad scroll stands in for pinch to zoom.
+                    MouseScrollDelta::PixelDelta(pos) if control => {
+                        self.pinch_delta += pos.y as f32
+                    }
+                    MouseScrollDelta::PixelDelta(pos) => {
+                        self.scroll_pan_delta += math::v2(pos.x as f32, pos.y as f32)
+                    }
+                }
             }
             Event::WindowEvent {
                 event: WindowEvent::MouseInput { state, button, .. },
@@ -127,10 +202,27 @@ impl InputState {
                 ElementState::Pressed => {
                     self.released_mouse.remove(&button);
                     self.pressed_mouse.insert(button);
+                    self.mouse_press_position = self.mouse_position;
                 }
                 ElementState::Released => {
                     self.pressed_mouse.remove(&button);
                     self.released_mouse.insert(button);
+
+                    if button == MouseButton::Left && self.was_mouse_clicked(button) {
+                        let now = Instant::now();
+                        let double_click = self.last_click.map(|(time, position)| {
+                            now.duration_since(time) < DOUBLE_CLICK_TIME
+                                && position.distance_squared(&self.mouse_position)
+                                    < CLICK_DISTANCE_SQUARED
+                        });
+
+                        if double_click.unwrap_or(false) {
+                            self.double_clicked = true;
+                            self.last_click = None;
+                        } else {
+                            self.last_click = Some((now, self.mouse_position));
+                        }
+                    }
                 }
             },
             Event::WindowEvent {
@@ -150,8 +242,12 @@ impl InputState {
         }
     }
 
+    pub fn invalidate_layout(&mut self) {
+        self.layout_invalidated = true;
+    }
+
     pub fn window_resized(&self) -> Option<math::V2<u32>> {
-        if self.window_start_size != self.window_size {
+        if self.window_start_size != self.window_size || self.layout_invalidated {
             Some(self.window_size)
         } else {
             None
@@ -162,6 +258,14 @@ impl InputState {
         self.mouse_wheel_delta
     }
 
+    pub fn scroll_pan(&self) -> math::V2<f32> {
+        self.scroll_pan_delta
+    }
+
+    pub fn pinch_delta(&self) -> f32 {
+        self.pinch_delta
+    }
+
     pub fn text(&self) -> &str {
         &self.text
     }
@@ -170,6 +274,15 @@ impl InputState {
         self.released_keys.contains(&key)
     }
 
+    // Set while a text field has focus so key presses don't also trigger command hotkeys
+    pub fn set_keys_captured(&mut self, captured: bool) {
+        self.keys_captured = captured;
+    }
+
+    pub fn keys_captured(&self) -> bool {
+        self.keys_captured
+    }
+
     pub fn is_key_down(&self, key: VirtualKeyCode) -> bool {
         self.pressed_keys.contains(&key)
     }
@@ -182,6 +295,10 @@ impl InputState {
         self.closed
     }
 
+    pub fn focus_gained(&self) -> bool {
+        self.focus_gained
+    }
+
     pub fn mouse_move_delta(&self) -> math::V2<f32> {
         self.mouse_start_position - self.mouse_position
     }
@@ -193,6 +310,61 @@ impl InputState {
     pub fn is_mouse_down(&self, button: MouseButton) -> bool {
         self.pressed_mouse.contains(&button)
     }
+
+    pub fn was_mouse_clicked(&self, button: MouseButton) -> bool {
+        self.released_mouse.contains(&button)
+            && self
+                .mouse_press_position
+                .distance_squared(&self.mouse_position)
+                < CLICK_DISTANCE_SQUARED
+    }
+
+    pub fn was_double_click(&self) -> bool {
+        self.double_clicked
+    }
+}
+
+pub fn pop_grapheme(text: &mut String) {
+    fn is_extend(c: char) -> bool {
+        matches!(c as u32,
+            0x0300..=0x036f
+            | 0x1ab0..=0x1aff
+            | 0x1dc0..=0x1dff
+            | 0x200c..=0x200d
+            | 0x20d0..=0x20ff
+            | 0xfe00..=0xfe0f
+            | 0xfe20..=0xfe2f
+            | 0x1f3fb..=0x1f3ff
+            | 0xe0020..=0xe007f
+            | 0xe0100..=0xe01ef)
+    }
+
+    fn is_regional_indicator(c: char) -> bool {
+        matches!(c as u32, 0x1f1e6..=0x1f1ff)
+    }
+
+    while let Some(c) = text.pop() {
+        if is_extend(c) {
+            continue;
+        }
+
+        if text.ends_with('\u{200d}') {
+            text.pop();
+            continue;
+        }
+
+        if is_regional_indicator(c)
+            && text
+                .chars()
+                .next_back()
+                .map(is_regional_indicator)
+                .unwrap_or(false)
+        {
+            text.pop();
+        }
+
+        break;
+    }
 }
 
 pub trait UserEventSender: Clone {
@@ -230,3 +402,78 @@ impl UserEventReceiver for () {
         std::iter::empty()
     }
 }
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use winit::dpi::PhysicalPosition;
+    use winit::event::{
+        DeviceId, ElementState, KeyboardInput, MouseScrollDelta, TouchPhase, WindowEvent,
+    };
+    use winit::window::WindowId;
+
+    fn window_event(input_state: &mut InputState, event: WindowEvent<'static>) {
+        input_state.process(Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        });
+    }
+
+    #[allow(deprecated)]
+    fn key(input_state: &mut InputState, key: VirtualKeyCode, state: ElementState) {
+        window_event(
+            input_state,
+            WindowEvent::KeyboardInput {
+                device_id: unsafe { DeviceId::dummy() },
+                input: KeyboardInput {
+                    scancode: 0,
+                    state,
+                    virtual_keycode: Some(key),
+                    modifiers: Default::default(),
+                },
+                is_synthetic: false,
+            },
+        );
+    }
+
+    #[allow(deprecated)]
+    fn wheel(input_state: &mut InputState, delta: MouseScrollDelta) {
+        window_event(
+            input_state,
+            WindowEvent::MouseWheel {
+                device_id: unsafe { DeviceId::dummy() },
+                delta,
+                phase: TouchPhase::Moved,
+                modifiers: Default::default(),
+            },
+        );
+    }
+
+    #[test]
+    fn wheel_deltas_are_routed_by_device() {
+        let mut input_state = InputState::detached(math::v2(1024, 1024));
+        let pixels = || MouseScrollDelta::PixelDelta(PhysicalPosition::new(3.0, 4.0));
+
+        wheel(&mut input_state, MouseScrollDelta::LineDelta(0.0, 1.0));
+        assert_eq!(input_state.scroll(), 5.0);
+        assert_eq!(input_state.scroll_pan(), math::v2(0.0, 0.0));
+        assert_eq!(input_state.pinch_delta(), 0.0);
+        input_state.reset();
+
+        wheel(&mut input_state, pixels());
+        assert_eq!(input_state.scroll(), 0.0);
+        assert_eq!(input_state.scroll_pan(), math::v2(3.0, 4.0));
+        assert_eq!(input_state.pinch_delta(), 0.0);
+        input_state.reset();
+
+        key(
+            &mut input_state,
+            VirtualKeyCode::LControl,
+            ElementState::Pressed,
+        );
+        wheel(&mut input_state, pixels());
+        assert_eq!(input_state.scroll(), 0.0);
+        assert_eq!(input_state.scroll_pan(), math::v2(0.0, 0.0));
+        assert_eq!(input_state.pinch_delta(), 4.0);
+    }
+}
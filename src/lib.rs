@@ -4,12 +4,22 @@ mod esi;
 mod gfx;
 mod input;
 mod math;
+#[cfg(test)]
+mod mock_http;
 mod oauth;
+mod paths;
 mod platform;
+mod replay;
+mod settings;
 mod world;
 
+pub use esi::{
+    GetUniverseConstellation, GetUniverseRegion, GetUniverseStargate,
+    GetUniverseStargateDestination, GetUniverseSystem, Position,
+};
 #[cfg(not(target_arch = "wasm32"))]
 pub use gfx::Window;
+pub use world::{Galaxy, GalaxyGraph, JumpType, RouteError, RouteNode, RoutePreference};
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
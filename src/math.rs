@@ -166,6 +166,24 @@ macro_rules! implement_vector{
             }
         }
 
+        impl<T: Num> $name<T> {
+            pub fn component_min(self, other: Self) -> Self {
+                $name {
+                    $($field: Num::min(&self.$field, other.$field),)*
+                }
+            }
+
+            pub fn component_max(self, other: Self) -> Self {
+                $name {
+                    $($field: Num::max(&self.$field, other.$field),)*
+                }
+            }
+
+            pub fn clamp(self, min: Self, max: Self) -> Self {
+                self.component_max(min).component_min(max)
+            }
+        }
+
         impl<T> $name<T>
         where
             T: Mul<Output = T> + Add<Output = T> + Clone + Num,
@@ -266,6 +284,26 @@ where
     }
 }
 
+impl<T> V3<T>
+where
+    T: Mul<Output = T> + Sub<Output = T> + Copy,
+{
+    pub fn cross(self, other: Self) -> V3<T> {
+        V3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+}
+
+pub fn lerp<V, T>(a: V, b: V, t: T) -> V
+where
+    V: Add<Output = V> + Sub<Output = V> + Mul<T, Output = V> + Clone,
+{
+    a.clone() + (b - a) * t
+}
+
 impl<T> V4<T> {
     pub fn contract(self) -> V3<T> {
         V3::new(self.x, self.y, self.z)
@@ -327,6 +365,27 @@ impl<T: Num> M3<T> {
     }
 }
 
+impl M3<f32> {
+    pub fn determinant(&self) -> f32 {
+        self.c0.dot(self.c1.cross(self.c2))
+    }
+
+    pub fn inverse(self) -> Option<M3<f32>> {
+        let det = self.determinant();
+        if det == 0.0 || !(1.0 / det).is_finite() {
+            return None;
+        }
+
+        let rows = M3::new(
+            self.c1.cross(self.c2),
+            self.c2.cross(self.c0),
+            self.c0.cross(self.c1),
+        );
+        let inv = rows.transpose();
+        Some(M3::new(inv.c0 / det, inv.c1 / det, inv.c2 / det))
+    }
+}
+
 impl<T> Mul<M3<T>> for M3<T>
 where
     T: Mul<Output = T> + Add<Output = T> + Num + Clone,
@@ -421,6 +480,105 @@ impl<T: Num> M4<T> {
     }
 }
 
+impl M4<f32> {
+    fn to_array(self) -> [f32; 16] {
+        [
+            self.c0.x, self.c0.y, self.c0.z, self.c0.w, self.c1.x, self.c1.y, self.c1.z, self.c1.w,
+            self.c2.x, self.c2.y, self.c2.z, self.c2.w, self.c3.x, self.c3.y, self.c3.z, self.c3.w,
+        ]
+    }
+
+    fn from_array(m: [f32; 16]) -> M4<f32> {
+        m4(
+            v4(m[0], m[1], m[2], m[3]),
+            v4(m[4], m[5], m[6], m[7]),
+            v4(m[8], m[9], m[10], m[11]),
+            v4(m[12], m[13], m[14], m[15]),
+        )
+    }
+
+    pub fn inverse(self) -> Option<M4<f32>> {
+        let m = self.to_array();
+        let mut inv = [0.0; 16];
+
+        inv[0] = m[5] * m[10] * m[15] - m[5] * m[11] * m[14] - m[9] * m[6] * m[15]
+            + m[9] * m[7] * m[14]
+            + m[13] * m[6] * m[11]
+            - m[13] * m[7] * m[10];
+        inv[4] = -m[4] * m[10] * m[15] + m[4] * m[11] * m[14] + m[8] * m[6] * m[15]
+            - m[8] * m[7] * m[14]
+            - m[12] * m[6] * m[11]
+            + m[12] * m[7] * m[10];
+        inv[8] = m[4] * m[9] * m[15] - m[4] * m[11] * m[13] - m[8] * m[5] * m[15]
+            + m[8] * m[7] * m[13]
+            + m[12] * m[5] * m[11]
+            - m[12] * m[7] * m[9];
+        inv[12] = -m[4] * m[9] * m[14] + m[4] * m[10] * m[13] + m[8] * m[5] * m[14]
+            - m[8] * m[6] * m[13]
+            - m[12] * m[5] * m[10]
+            + m[12] * m[6] * m[9];
+        inv[1] = -m[1] * m[10] * m[15] + m[1] * m[11] * m[14] + m[9] * m[2] * m[15]
+            - m[9] * m[3] * m[14]
+            - m[13] * m[2] * m[11]
+            + m[13] * m[3] * m[10];
+        inv[5] = m[0] * m[10] * m[15] - m[0] * m[11] * m[14] - m[8] * m[2] * m[15]
+            + m[8] * m[3] * m[14]
+            + m[12] * m[2] * m[11]
+            - m[12] * m[3] * m[10];
+        inv[9] = -m[0] * m[9] * m[15] + m[0] * m[11] * m[13] + m[8] * m[1] * m[15]
+            - m[8] * m[3] * m[13]
+            - m[12] * m[1] * m[11]
+            + m[12] * m[3] * m[9];
+        inv[13] = m[0] * m[9] * m[14] - m[0] * m[10] * m[13] - m[8] * m[1] * m[14]
+            + m[8] * m[2] * m[13]
+            + m[12] * m[1] * m[10]
+            - m[12] * m[2] * m[9];
+        inv[2] = m[1] * m[6] * m[15] - m[1] * m[7] * m[14] - m[5] * m[2] * m[15]
+            + m[5] * m[3] * m[14]
+            + m[13] * m[2] * m[7]
+            - m[13] * m[3] * m[6];
+        inv[6] = -m[0] * m[6] * m[15] + m[0] * m[7] * m[14] + m[4] * m[2] * m[15]
+            - m[4] * m[3] * m[14]
+            - m[12] * m[2] * m[7]
+            + m[12] * m[3] * m[6];
+        inv[10] = m[0] * m[5] * m[15] - m[0] * m[7] * m[13] - m[4] * m[1] * m[15]
+            + m[4] * m[3] * m[13]
+            + m[12] * m[1] * m[7]
+            - m[12] * m[3] * m[5];
+        inv[14] = -m[0] * m[5] * m[14] + m[0] * m[6] * m[13] + m[4] * m[1] * m[14]
+            - m[4] * m[2] * m[13]
+            - m[12] * m[1] * m[6]
+            + m[12] * m[2] * m[5];
+        inv[3] = -m[1] * m[6] * m[11] + m[1] * m[7] * m[10] + m[5] * m[2] * m[11]
+            - m[5] * m[3] * m[10]
+            - m[9] * m[2] * m[7]
+            + m[9] * m[3] * m[6];
+        inv[7] = m[0] * m[6] * m[11] - m[0] * m[7] * m[10] - m[4] * m[2] * m[11]
+            + m[4] * m[3] * m[10]
+            + m[8] * m[2] * m[7]
+            - m[8] * m[3] * m[6];
+        inv[11] = -m[0] * m[5] * m[11] + m[0] * m[7] * m[9] + m[4] * m[1] * m[11]
+            - m[4] * m[3] * m[9]
+            - m[8] * m[1] * m[7]
+            + m[8] * m[3] * m[5];
+        inv[15] = m[0] * m[5] * m[10] - m[0] * m[6] * m[9] - m[4] * m[1] * m[10]
+            + m[4] * m[2] * m[9]
+            + m[8] * m[1] * m[6]
+            - m[8] * m[2] * m[5];
+
+        let det = m[0] * inv[0] + m[1] * inv[4] + m[2] * inv[8] + m[3] * inv[12];
+        if det == 0.0 || !(1.0 / det).is_finite() {
+            return None;
+        }
+
+        for value in inv.iter_mut() {
+            *value /= det;
+        }
+
+        Some(M4::from_array(inv))
+    }
+}
+
 impl<T> Mul<M4<T>> for M4<T>
 where
     T: Mul<Output = T> + Add<Output = T> + Num + Clone,
@@ -503,7 +661,10 @@ impl<T: Sub<Output = T> + Num + Clone + Copy + PartialOrd> Rect<T> {
     }
 
     pub fn contains(&self, point: V2<T>) -> bool {
-        self.min.x < point.x && self.max.x > point.x && self.min.y < point.y && self.max.y > point.y
+        self.min.x <= point.x
+            && self.max.x > point.x
+            && self.min.y <= point.y
+            && self.max.y > point.y
     }
 
     pub fn triangle_list_iter(&self) -> TriangleListIter<T> {
@@ -608,4 +769,130 @@ mod tests {
         assert_eq!(left.clone() * num.clone(), result);
         assert_ne!(num * left, result);
     }
+
+    #[test]
+    fn cross_product_of_unit_axes() {
+        let x = v3(1.0, 0.0, 0.0);
+        let y = v3(0.0, 1.0, 0.0);
+        let z = v3(0.0, 0.0, 1.0);
+
+        assert_eq!(x.cross(y), z);
+        assert_eq!(y.cross(z), x);
+        assert_eq!(z.cross(x), y);
+        assert_eq!(y.cross(x), v3(0.0, 0.0, -1.0));
+        assert_eq!(x.cross(x), V3::fill(0.0));
+    }
+
+    #[test]
+    fn lerp_endpoints_and_midpoint() {
+        let a = v2(0.0, 10.0);
+        let b = v2(4.0, -10.0);
+        assert_eq!(lerp(a, b, 0.0), a);
+        assert_eq!(lerp(a, b, 1.0), b);
+        assert_eq!(lerp(a, b, 0.5), v2(2.0, 0.0));
+
+        let a = v4(0.0f32, 0.0, 0.0, 1.0);
+        let b = v4(1.0f32, 1.0, 1.0, 1.0);
+        assert_eq!(lerp(a, b, 0.25), v4(0.25, 0.25, 0.25, 1.0));
+        assert_eq!(
+            lerp(v3(1.0, 2.0, 3.0), v3(3.0, 2.0, 1.0), 0.5),
+            v3(2.0, 2.0, 2.0)
+        );
+    }
+
+    fn assert_m3_near(a: M3<f32>, b: M3<f32>) {
+        for (x, y) in [(a.c0, b.c0), (a.c1, b.c1), (a.c2, b.c2)].iter() {
+            assert!(x.distance(y) < 0.0001, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn m3_inverse() {
+        let mut view = M3::<f32>::identity();
+        view.c0.x = 4.0;
+        view.c1.y = 4.0;
+        view.c2.x = -1.5;
+        view.c2.y = 0.25;
+
+        let inverse = view.inverse().unwrap();
+        assert_m3_near(view * inverse, M3::identity());
+        assert_m3_near(inverse * view, M3::identity());
+
+        let point = v3(0.3, -0.2, 1.0);
+        let round_trip = inverse * (view * point);
+        assert!(round_trip.distance(&point) < 0.0001);
+
+        let num = m3(v3(2.0, 0.0, 1.0), v3(1.0, 3.0, 0.0), v3(0.0, 1.0, 4.0));
+        assert_m3_near(num * num.inverse().unwrap(), M3::identity());
+
+        let singular = m3(v3(1.0, 2.0, 3.0), v3(2.0, 4.0, 6.0), v3(0.0, 1.0, 1.0));
+        assert!(singular.inverse().is_none());
+    }
+
+    #[test]
+    fn m4_inverse() {
+        let num = m4(
+            v4(2.0, 0.0, 0.0, 1.0),
+            v4(0.0, 3.0, 1.0, 0.0),
+            v4(1.0, 0.0, 4.0, 0.0),
+            v4(0.0, 2.0, 0.0, 1.0),
+        );
+        let result = num * num.inverse().unwrap();
+        let identity = M4::<f32>::identity();
+        for (x, y) in [
+            (result.c0, identity.c0),
+            (result.c1, identity.c1),
+            (result.c2, identity.c2),
+            (result.c3, identity.c3),
+        ]
+        .iter()
+        {
+            assert!(x.distance(y) < 0.0001);
+        }
+
+        let singular = m4(
+            v4(1.0, 2.0, 3.0, 4.0),
+            v4(2.0, 4.0, 6.0, 8.0),
+            v4(0.0, 1.0, 0.0, 1.0),
+            v4(1.0, 0.0, 1.0, 0.0),
+        );
+        assert!(singular.inverse().is_none());
+    }
+
+    #[test]
+    fn clamp_highlighted_color() {
+        let color = v3(1.0f32, 0.95, 0.0) + V3::fill(0.1);
+        let clamped = color.clamp(V3::fill(0.0), V3::fill(1.0));
+        assert_eq!(clamped.x, 1.0);
+        assert_eq!(clamped.y, 1.0);
+        assert!((clamped.z - 0.1).abs() < 0.0001);
+
+        let a = v4(1, 5, -2, 0);
+        let b = v4(3, 2, -1, 0);
+        assert_eq!(a.component_min(b), v4(1, 2, -2, 0));
+        assert_eq!(a.component_max(b), v4(3, 5, -1, 0));
+        assert_eq!(
+            v3(-0.5, 0.5, 1.5).clamp(V3::fill(0.0), V3::fill(1.0)),
+            v3(0.0, 0.5, 1.0)
+        );
+    }
+
+    #[test]
+    fn rect_contains_is_half_open() {
+        let rect = Rect::new(v2(10, 20), v2(30, 40));
+        assert!(rect.contains(v2(10, 20)));
+        assert!(rect.contains(v2(10, 39)));
+        assert!(rect.contains(v2(29, 20)));
+        assert!(rect.contains(v2(20, 30)));
+        assert!(!rect.contains(v2(30, 40)));
+        assert!(!rect.contains(v2(30, 30)));
+        assert!(!rect.contains(v2(20, 40)));
+        assert!(!rect.contains(v2(9, 30)));
+
+        let top = Rect::new(v2(0.0, 0.0), v2(10.0, 5.0));
+        let bottom = Rect::new(v2(0.0, 5.0), v2(10.0, 10.0));
+        let edge = v2(5.0, 5.0);
+        assert!(!top.contains(edge));
+        assert!(bottom.contains(edge));
+    }
 }
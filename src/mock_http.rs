@@ -0,0 +1,132 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub enum Reply {
+    Respond(u16, &'static str, &'static str),
+    Delayed(Duration, u16, &'static str, &'static str),
+    Stall,
+}
+
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<AtomicUsize>,
+    max_open: Arc<AtomicUsize>,
+}
+
+impl MockServer {
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+
+    // The most requests that were waiting on a reply at the same time
+    pub fn max_open(&self) -> usize {
+        self.max_open.load(Ordering::SeqCst)
+    }
+}
+
+fn respond(mut stream: TcpStream, status: u16, headers: &str, body: &str) {
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {} Mock\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        headers,
+        body.len(),
+        body
+    );
+}
+
+// Reads the request head and body, returning the request path
+fn read_request(stream: &mut TcpStream) -> String {
+    let mut request = Vec::new();
+    let mut buf = [0; 4096];
+    while let Ok(read) = stream.read(&mut buf) {
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+        let text = String::from_utf8_lossy(&request);
+        if let Some(end) = text.find("\r\n\r\n") {
+            let content_length = text[..end]
+                .lines()
+                .filter_map(|l| l.split_once(':'))
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            if request.len() >= end + 4 + content_length {
+                break;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&request)
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .to_string()
+}
+
+// Answers each connection with the reply picked for its path, stopping once the handler
+// runs out of replies
+pub fn mock_server_with<F>(mut handler: F) -> MockServer
+where
+    F: FnMut(&str) -> Option<Reply> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let requests = Arc::new(AtomicUsize::new(0));
+    let max_open = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+    let open_max = max_open.clone();
+    let open = Arc::new(AtomicUsize::new(0));
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => break,
+            };
+            let reply = match handler(&read_request(&mut stream)) {
+                Some(reply) => reply,
+                None => break,
+            };
+            counter.fetch_add(1, Ordering::SeqCst);
+            let opened = open.fetch_add(1, Ordering::SeqCst) + 1;
+            open_max.fetch_max(opened, Ordering::SeqCst);
+            match reply {
+                Reply::Respond(status, headers, body) => {
+                    open.fetch_sub(1, Ordering::SeqCst);
+                    respond(stream, status, headers, body);
+                }
+                Reply::Delayed(delay, status, headers, body) => {
+                    let open = open.clone();
+                    std::thread::spawn(move || {
+                        std::thread::sleep(delay);
+                        open.fetch_sub(1, Ordering::SeqCst);
+                        respond(stream, status, headers, body);
+                    });
+                }
+                Reply::Stall => {
+                    let open = open.clone();
+                    std::thread::spawn(move || {
+                        std::thread::sleep(Duration::from_secs(1));
+                        open.fetch_sub(1, Ordering::SeqCst);
+                        drop(stream);
+                    });
+                }
+            }
+        }
+    });
+
+    MockServer {
+        url,
+        requests,
+        max_open,
+    }
+}
+
+pub fn mock_server(replies: Vec<Reply>) -> MockServer {
+    let mut replies = replies.into_iter();
+    mock_server_with(move |_| replies.next())
+}
@@ -2,11 +2,15 @@ use ahash::AHashMap as HashMap;
 use serde::{Deserialize, Serialize};
 
 use crate::error::*;
-use crate::platform::{read_file, write_file};
+use crate::paths::Paths;
+use crate::platform::time::{SystemTime, UNIX_EPOCH};
+use crate::platform::{create_dir_all, list_dir, read_file, remove_file, write_file};
+
+use std::path::Path;
 
 const PORT: u16 = 13536;
 const CLIENT_ID: &str = "8abed7fc8c3343098e8c619ed7338fad";
-const SCOPES: [&str; 14] = [
+const SCOPES: [&str; 15] = [
     "publicData",
     "esi-location.read_location.v1",
     "esi-location.read_ship_type.v1",
@@ -21,48 +25,88 @@ const SCOPES: [&str; 14] = [
     "esi-corporations.read_contacts.v1",
     "esi-corporations.read_standings.v1",
     "esi-alliances.read_contacts.v1",
+    "esi-universe.read_structures.v1",
 ];
 const OAUTH_AUTHORIZE: &str = "https://login.eveonline.com/v2/oauth/authorize/";
-const OAUTH_TOKEN: &str = "https://login.eveonline.com/v2/oauth/token/";
+pub const OAUTH_TOKEN: &str = "https://login.eveonline.com/v2/oauth/token/";
 const OAUTH_VERIFY: &str = "https://login.eveonline.com/oauth/verify/";
 
-pub async fn load_or_authorize() -> Result<Profile, Error> {
-    let profile: Option<Profile> = read_file("eve-profile.json")
+pub async fn load_or_authorize(paths: &Paths, offline: bool) -> Result<Profile, Error> {
+    let profile: Option<Profile> = read_file(paths.profile_path())
         .await
         .ok()
         .and_then(|p| serde_json::from_slice(&p).ok());
 
     if let Some(profile) = profile {
-        if crate::esi::ALWAYS_CACHE {
+        if offline {
+            log::info!("offline, using existing oauth profile");
             return Ok(profile);
         }
         if profile.token.expired() {
             log::info!("oauth token expired, refreshing");
-            if let Ok(profile) = refresh(profile).await {
+            if let Ok(profile) = refresh(paths, profile).await {
                 Ok(profile)
             } else {
                 log::info!("oauth token invalid, authorizing");
-                auth::authorize().await
+                auth::authorize(paths).await
             }
-        } else if let Ok(_) = verify(&profile.token).await {
-            log::info!("using existing oauth profile");
-            Ok(profile)
         } else {
+            match verify(&profile.token).await {
+                Ok(_) => {
+                    log::info!("using existing oauth profile");
+                    return Ok(profile);
+                }
+                Err(Error::Reqwest(error)) if error.is_connect() || error.is_timeout() => {
+                    log::warn!("unable to verify oauth token, using existing: {}", error);
+                    return Ok(profile);
+                }
+                Err(_) => (),
+            }
+
             log::info!("oauth token expired, refreshing");
-            if let Ok(profile) = refresh(profile).await {
+            if let Ok(profile) = refresh(paths, profile).await {
                 Ok(profile)
             } else {
                 log::info!("oauth token invalid, authorizing");
-                auth::authorize().await
+                auth::authorize(paths).await
             }
         }
+    } else if offline {
+        Err(Error::OfflineProfileMissing)
     } else {
         log::info!("no oauth profile found, authorizing");
-        auth::authorize().await
+        auth::authorize(paths).await
     }
 }
 
-pub async fn refresh(mut profile: Profile) -> Result<Profile, Error> {
+pub async fn logout(paths: &Paths, character_id: i32) -> Result<(), Error> {
+    log::info!("removing oauth profile for {}", character_id);
+    for path in &[
+        paths.profile_path(),
+        paths.character_profile_path(character_id),
+    ] {
+        match remove_file(path).await {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => return Err(error.into()),
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn authorize(paths: &Paths) -> Result<Profile, Error> {
+    auth::authorize(paths).await
+}
+
+pub async fn refresh(paths: &Paths, profile: Profile) -> Result<Profile, Error> {
+    refresh_with(paths, OAUTH_TOKEN, profile).await
+}
+
+pub async fn refresh_with(
+    paths: &Paths,
+    token_url: &str,
+    mut profile: Profile,
+) -> Result<Profile, Error> {
     log::info!("refreshing oauth credentials");
     let mut request_body = HashMap::new();
     request_body.insert("grant_type", "refresh_token".to_string());
@@ -70,19 +114,86 @@ pub async fn refresh(mut profile: Profile) -> Result<Profile, Error> {
     request_body.insert("client_id", CLIENT_ID.to_string());
 
     let client = reqwest::Client::new();
-    let token_request = client.post(OAUTH_TOKEN).form(&request_body);
-    let token_response = token_request.send().await;
+    let token_request = client.post(token_url).form(&request_body);
+    let token_response = token_request.send().await?.error_for_status()?;
 
-    let token: AccessToken = token_response?.json().await?;
+    let token: AccessToken = token_response.json().await?;
 
     profile.token = token;
-
-    let json = serde_json::to_vec(&profile)?;
-    write_file("eve-profile.json", json).await?;
+    save_profile(paths, &profile).await?;
 
     Ok(profile)
 }
 
+async fn save_profile(paths: &Paths, profile: &Profile) -> Result<(), Error> {
+    let json = serde_json::to_vec(profile)?;
+    create_dir_all(paths.profile_dir_path()).await?;
+    write_file(paths.profile_path(), &json).await?;
+    write_file(
+        paths.character_profile_path(profile.character.character_id),
+        json,
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn read_profile(path: &Path) -> Option<Profile> {
+    let bytes = read_file(path).await.ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+pub async fn list_profiles(paths: &Paths) -> Result<Vec<Character>, Error> {
+    let active = read_profile(&paths.profile_path()).await;
+    let paths = match list_dir(paths.profile_dir_path()).await {
+        Ok(paths) => paths,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(error) => return Err(error.into()),
+    };
+
+    let mut characters: Vec<Character> = Vec::new();
+    for path in paths {
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+
+        match read_profile(&path).await {
+            Some(profile) => characters.push(profile.character),
+            None => log::warn!("unable to read profile: {}", path.display()),
+        }
+    }
+
+    if let Some(active) = active {
+        if !characters
+            .iter()
+            .any(|c| c.character_id == active.character.character_id)
+        {
+            characters.push(active.character);
+        }
+    }
+
+    characters.sort_by(|a, b| a.character_name.cmp(&b.character_name));
+    Ok(characters)
+}
+
+pub async fn load_profile(paths: &Paths, character_id: i32) -> Result<Profile, Error> {
+    let path = paths.character_profile_path(character_id);
+    let profile = match read_profile(&path).await {
+        Some(profile) => profile,
+        None => read_profile(&paths.profile_path())
+            .await
+            .filter(|p| p.character.character_id == character_id)
+            .ok_or(Error::UnknownProfile(character_id))?,
+    };
+
+    if profile.token.expired() {
+        refresh(paths, profile).await
+    } else {
+        save_profile(paths, &profile).await?;
+        Ok(profile)
+    }
+}
+
 async fn verify(token: &AccessToken) -> Result<Character, Error> {
     let client = reqwest::Client::new();
     let token_request = client
@@ -126,17 +237,11 @@ impl AccessToken {
         format!("Bearer {}", self.access_token)
     }
 
-    #[cfg(not(target_arch = "wasm32"))]
     pub fn now() -> u64 {
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_else(|err| err.duration())
-            .as_secs()
-    }
-
-    #[cfg(target_arch = "wasm32")]
-    pub fn now() -> u64 {
-        0
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
     }
 
     pub fn expired(&self) -> bool {
@@ -144,6 +249,65 @@ impl AccessToken {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock_http::{mock_server, Reply};
+    use std::net::TcpListener;
+
+    fn profile() -> Profile {
+        Profile {
+            character: Character {
+                character_id: 1,
+                character_name: "Test".to_string(),
+            },
+            token: AccessToken {
+                access_token: "access".to_string(),
+                expires_in: 1200,
+                token_type: "Bearer".to_string(),
+                refresh_token: "refresh".to_string(),
+                created_at: 0,
+            },
+        }
+    }
+
+    fn test_paths() -> Paths {
+        Paths::new(std::env::temp_dir().join(format!("eve-mapper-oauth-{}", std::process::id())))
+    }
+
+    #[test]
+    fn old_tokens_expire() {
+        let mut token = profile().token;
+        token.created_at = AccessToken::now() - 1300;
+        assert!(token.expired());
+
+        token.created_at = AccessToken::now();
+        assert!(!token.expired());
+    }
+
+    #[test]
+    fn refresh_reports_failing_token_endpoint() {
+        let server = mock_server(vec![Reply::Respond(503, "", "service unavailable")]);
+        let url = format!("{}v2/oauth/token/", server.url);
+        let result = async_std::task::block_on(refresh_with(&test_paths(), &url, profile()));
+        assert!(
+            matches!(result, Err(Error::Reqwest(e)) if e.status().map(|s| s.as_u16()) == Some(503))
+        );
+    }
+
+    #[test]
+    fn refresh_reports_unreachable_token_endpoint() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let url = format!("http://{}/v2/oauth/token/", addr);
+        let result = async_std::task::block_on(refresh_with(&test_paths(), &url, profile()));
+        assert!(matches!(result, Err(Error::Reqwest(_))));
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 mod auth {
     use async_std::sync::Mutex;
@@ -162,7 +326,7 @@ mod auth {
 
     use super::*;
 
-    pub async fn authorize() -> Result<Profile, Error> {
+    pub async fn authorize(paths: &Paths) -> Result<Profile, Error> {
         let (start_tx, start_rx) = oneshot();
         let (end_tx, end_rx) = oneshot();
 
@@ -212,16 +376,27 @@ mod auth {
             }
         });
 
-        let profile = profile_rx.next().await.unwrap();
-        end_tx.send(()).unwrap();
+        let profile = profile_rx.next().await;
+        let _ = end_tx.send(());
         server.await;
 
-        let json = serde_json::to_vec(&profile).unwrap();
-        write_file("eve-profile.json", json).await.unwrap();
+        let profile = profile.ok_or(Error::OauthAuthorize)?;
+        save_profile(paths, &profile).await?;
 
         Ok(profile)
     }
 
+    async fn request_token(request_body: HashMap<&str, String>) -> Result<Profile, Error> {
+        let client = reqwest::Client::new();
+        let token_request = client.post(OAUTH_TOKEN).form(&request_body);
+        let token_response = token_request.send().await?.error_for_status()?;
+        let token: AccessToken = token_response.json().await?;
+
+        let character = verify(&token).await?;
+
+        Ok(Profile { character, token })
+    }
+
     struct OauthService {
         oauth_state: Arc<Mutex<HashMap<String, String>>>,
         profile_tx: Sender<Profile>,
@@ -339,20 +514,25 @@ mod auth {
                         request_body.insert("client_id", CLIENT_ID.to_string());
                         request_body.insert("code_verifier", secret);
 
-                        let client = reqwest::Client::new();
-                        let token_request = client.post(OAUTH_TOKEN).form(&request_body);
-                        let token_response = token_request.send().await;
-                        let token: AccessToken = token_response.unwrap().json().await.unwrap();
-
-                        let character = verify(&token).await.unwrap();
-
-                        profile_tx
-                            .send(Profile {
-                                character: character.clone(),
-                                token,
-                            })
-                            .await
-                            .unwrap();
+                        let character = match request_token(request_body).await {
+                            Ok(profile) => {
+                                let character = profile.character.clone();
+                                if profile_tx.send(profile).await.is_err() {
+                                    log::error!("oauth profile receiver closed");
+                                }
+                                character
+                            }
+                            Err(error) => {
+                                log::error!("unable to complete oauth authorization: {:?}", error);
+                                let response = Response::builder()
+                                    .status(502)
+                                    .body(Body::from(
+                                        "Unable to reach EVE SSO, please try again later.",
+                                    ))
+                                    .unwrap();
+                                return Ok(response);
+                            }
+                        };
 
                         let response = Response::builder()
                             .status(200)
@@ -380,7 +560,8 @@ mod auth {
 mod auth {
     use super::*;
 
-    pub async fn authorize() -> Result<Profile, Error> {
+    pub async fn authorize(_paths: &Paths) -> Result<Profile, Error> {
+        log::warn!("oauth authorization is not supported on web");
         Err(Error::OauthVerify)
     }
 }
@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+
+use crate::platform::{data_dir, file_exists};
+
+pub const STATIC_CACHE_FILE: &str = "eve-static.dat";
+pub const DYNAMIC_CACHE_FILE: &str = "eve-dynamic.dat";
+pub const IMAGE_CACHE_FILE: &str = "eve-images.dat";
+pub const PROFILE_FILE: &str = "eve-profile.json";
+pub const PROFILE_DIR: &str = "profiles";
+pub const UI_SCALE_FILE: &str = "ui-scale.json";
+pub const MAP_VIEW_FILE: &str = "map-view.json";
+
+#[derive(Debug, Clone)]
+pub struct Paths {
+    pub dir: PathBuf,
+    pub static_cache: String,
+    pub dynamic_cache: String,
+    pub image_cache: String,
+    pub profile: String,
+    pub profile_dir: String,
+    pub ui_scale: String,
+    pub map_view: String,
+}
+
+impl Paths {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Paths {
+        Paths {
+            dir: dir.into(),
+            static_cache: STATIC_CACHE_FILE.to_string(),
+            dynamic_cache: DYNAMIC_CACHE_FILE.to_string(),
+            image_cache: IMAGE_CACHE_FILE.to_string(),
+            profile: PROFILE_FILE.to_string(),
+            profile_dir: PROFILE_DIR.to_string(),
+            ui_scale: UI_SCALE_FILE.to_string(),
+            map_view: MAP_VIEW_FILE.to_string(),
+        }
+    }
+
+    pub fn static_cache_path(&self) -> PathBuf {
+        self.dir.join(&self.static_cache)
+    }
+
+    pub fn dynamic_cache_path(&self) -> PathBuf {
+        self.dir.join(&self.dynamic_cache)
+    }
+
+    pub fn image_cache_path(&self) -> PathBuf {
+        self.dir.join(&self.image_cache)
+    }
+
+    pub fn profile_path(&self) -> PathBuf {
+        self.dir.join(&self.profile)
+    }
+
+    pub fn profile_dir_path(&self) -> PathBuf {
+        self.dir.join(&self.profile_dir)
+    }
+
+    pub fn ui_scale_path(&self) -> PathBuf {
+        self.dir.join(&self.ui_scale)
+    }
+
+    pub fn map_view_path(&self) -> PathBuf {
+        self.dir.join(&self.map_view)
+    }
+
+    pub fn character_profile_path(&self, character_id: i32) -> PathBuf {
+        self.profile_dir_path()
+            .join(format!("{}.json", character_id))
+    }
+}
+
+impl Default for Paths {
+    fn default() -> Self {
+        if file_exists(PROFILE_FILE) || file_exists(STATIC_CACHE_FILE) {
+            log::info!("found existing data in working directory, using it");
+            return Paths::new(PathBuf::new());
+        }
+
+        Paths::new(data_dir().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_relative_to_dir() {
+        let paths = Paths::new("data");
+        assert_eq!(
+            paths.static_cache_path(),
+            PathBuf::from("data/eve-static.dat")
+        );
+        assert_eq!(paths.profile_path(), PathBuf::from("data/eve-profile.json"));
+        assert_eq!(
+            paths.character_profile_path(42),
+            PathBuf::from("data/profiles/42.json")
+        );
+        assert_eq!(paths.ui_scale_path(), PathBuf::from("data/ui-scale.json"));
+        assert_eq!(paths.map_view_path(), PathBuf::from("data/map-view.json"));
+
+        let paths = Paths::new(PathBuf::new());
+        assert_eq!(paths.dynamic_cache_path(), PathBuf::from("eve-dynamic.dat"));
+    }
+}
@@ -8,18 +8,23 @@ use std::cell::{Cell, RefCell};
 use std::convert::TryInto;
 
 use crate::gfx::font::{FontCache, PositionedTextSpan};
-use crate::gfx::images::{Image, Images};
+use crate::gfx::images::{Image, Images, MIPMAP_LEVELS};
 use crate::gfx::{CircleVertex, LineVertex, QuadVertex, SystemData, TextVertex, UserEvent};
 use crate::math;
+use crate::settings::Settings;
 
+mod clipboard;
+mod game_log;
 mod shaders;
+pub use clipboard::set_clipboard;
+pub use game_log::watch_local_chat;
 use shaders::*;
 
-pub use async_std::task::spawn;
+pub use async_std::task::{block_on, spawn};
 
 pub use std::time;
 
-pub use async_std::fs::{read as read_file, write as write_file};
+pub use async_std::fs::{create_dir_all, read as read_file, remove_file, write as write_file};
 
 pub const ESI_IMAGE_SERVER: &'static str = "https://images.evetech.net/";
 pub const USER_AGENT: Option<&'static str> =
@@ -29,6 +34,25 @@ pub fn file_exists<P: AsRef<std::path::Path>>(path: P) -> bool {
     std::path::Path::exists(path.as_ref())
 }
 
+pub fn data_dir() -> Option<std::path::PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .or_else(|| std::env::var_os("APPDATA"))
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .map(|home| std::path::PathBuf::from(home).join(".local").join("share"))
+        })?;
+    Some(base.join("eve-mapper"))
+}
+
+pub async fn list_dir<P: AsRef<std::path::Path>>(
+    path: P,
+) -> std::io::Result<Vec<std::path::PathBuf>> {
+    std::fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect()
+}
+
 pub type EventSender = EventLoopProxy<UserEvent>;
 pub type EventReceiver = ();
 
@@ -38,6 +62,10 @@ pub fn create_event_proxy(event_loop: &EventLoop<UserEvent>) -> (EventSender, Ev
 
 pub const DEFAULT_CONTROL_FLOW: ControlFlow = ControlFlow::Wait;
 
+pub fn wait_until(instant: time::Instant) -> ControlFlow {
+    ControlFlow::WaitUntil(instant)
+}
+
 pub fn parse_http_date(s: &str) -> Option<time::SystemTime> {
     httpdate::parse_http_date(s).ok()
 }
@@ -57,24 +85,89 @@ pub struct GraphicsBackend {
     shader_collection: RefCell<shaders::ShaderCollection>,
 }
 
+const GL_REQUESTS: &[(glutin::GlRequest, Option<glutin::GlProfile>)] = &[
+    (
+        glutin::GlRequest::Specific(glutin::Api::OpenGl, (4, 2)),
+        Some(glutin::GlProfile::Core),
+    ),
+    (
+        glutin::GlRequest::Specific(glutin::Api::OpenGl, (3, 3)),
+        Some(glutin::GlProfile::Core),
+    ),
+    (
+        glutin::GlRequest::Specific(glutin::Api::OpenGl, (3, 0)),
+        Some(glutin::GlProfile::Compatibility),
+    ),
+    (glutin::GlRequest::Latest, None),
+];
+
+fn create_display(
+    window_builder: WindowBuilder,
+    event_loop: &EventLoop<UserEvent>,
+    vsync: bool,
+) -> Display {
+    let mut errors = Vec::new();
+
+    for (request, profile) in GL_REQUESTS {
+        let mut context_builder = glutin::ContextBuilder::new()
+            .with_vsync(vsync)
+            .with_srgb(true)
+            .with_gl(*request);
+
+        if let Some(profile) = profile {
+            context_builder = context_builder.with_gl_profile(*profile);
+        }
+
+        match glium::Display::new(window_builder.clone(), context_builder, event_loop) {
+            Ok(display) => {
+                log::info!(
+                    "created {:?} context: {}",
+                    request,
+                    display.get_opengl_version_string()
+                );
+                return display;
+            }
+            Err(error) => {
+                log::warn!("unable to create {:?} context: {}", request, error);
+                errors.push(format!("{:?}: {}", request, error));
+            }
+        }
+    }
+
+    log::error!(
+        "unable to create an OpenGL context, EVE Mapper requires OpenGL 3.0 or newer. \
+         Please make sure your graphics drivers are installed and up to date."
+    );
+    for error in errors {
+        log::error!("{}", error);
+    }
+    std::process::exit(1);
+}
+
 impl GraphicsBackend {
     pub fn new(
         window_builder: WindowBuilder,
         event_loop: &EventLoop<UserEvent>,
         width: u32,
         height: u32,
+        settings: &Settings,
     ) -> GraphicsBackend {
-        let context_builder = glutin::ContextBuilder::new()
-            .with_vsync(true)
-            .with_srgb(true)
-            .with_gl_profile(glutin::GlProfile::Core)
-            .with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, (4, 2)));
-
-        let display = glium::Display::new(window_builder, context_builder, &event_loop).unwrap();
+        let display = create_display(window_builder, event_loop, !settings.disable_vsync);
 
         let window_size = Cell::new(math::V2::new(width, height).as_f32());
 
-        let shader_collection = shaders::ShaderCollection::new("shaders/");
+        let legacy_version = match *display.get_opengl_version() {
+            glium::Version(glium::Api::GlEs, _, _) => Some("100"),
+            glium::Version(glium::Api::Gl, major, minor) if (major, minor) < (4, 2) => Some("130"),
+            _ => None,
+        };
+
+        if let Some(version) = legacy_version {
+            log::warn!("OpenGL 4.2 unavailable, using GLSL {} shaders", version);
+        }
+
+        let shader_collection =
+            shaders::ShaderCollection::new("shaders/", legacy_version, settings.dev_mode);
 
         let system_program = RefCell::new(None);
         let jump_program = RefCell::new(None);
@@ -131,6 +224,11 @@ impl GraphicsBackend {
         self.display.gl_window().window().request_redraw();
     }
 
+    pub fn set_ime_position(&self, position: math::V2<f32>) {
+        let position = winit::dpi::PhysicalPosition::new(position.x, position.y);
+        self.display.gl_window().window().set_ime_position(position);
+    }
+
     pub fn create_texture<T: Texture>(&self, width: u32, height: u32) -> T {
         T::create(&self.display, width, height)
     }
@@ -154,6 +252,20 @@ impl GraphicsBackend {
         self.window_size.set(window_size);
     }
 
+    pub fn max_texture_size(&self) -> u32 {
+        use glium::CapabilitiesSource;
+        self.display.get_capabilities().max_texture_size as u32
+    }
+
+    pub fn reload_shaders(&self) {
+        self.shader_collection.borrow().reload();
+        self.request_redraw();
+    }
+
+    pub fn shader_errors(&self) -> Vec<String> {
+        self.shader_collection.borrow().errors()
+    }
+
     pub fn begin(&self) -> Frame {
         let mut shader_collection = self.shader_collection.borrow_mut();
         shader_collection.load_if_newer(&self.display, &mut self.system_program.borrow_mut());
@@ -340,12 +452,14 @@ impl GraphicsBackend {
         image: Image,
         position: math::Rect<f32>,
     ) {
+        images.generate_mipmaps();
+
         let uniforms = glium::uniform! {
             window_size: self.window_size.get(),
             texture_atlas: images.texture().texture
             .sampled()
             .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear)
-            .minify_filter(glium::uniforms::MinifySamplerFilter::Linear),
+            .minify_filter(glium::uniforms::MinifySamplerFilter::LinearMipmapLinear),
             textured: true,
             color: math::V4::fill(1.0)
         };
@@ -469,8 +583,13 @@ pub struct SrgbTexture<T: TextureFormat> {
 impl<T: TextureFormat> Texture for SrgbTexture<T> {
     fn create(display: &Display, width: u32, height: u32) -> Self {
         SrgbTexture {
-            texture: SrgbTexture2d::empty(display, width, height)
-                .expect("unable to create texture"),
+            texture: SrgbTexture2d::empty_with_mipmaps(
+                display,
+                glium::texture::MipmapsOption::EmptyMipmapsMax(MIPMAP_LEVELS),
+                width,
+                height,
+            )
+            .expect("unable to create texture"),
             marker: Default::default(),
         }
     }
@@ -493,6 +612,14 @@ impl<T: TextureFormat> Texture for SrgbTexture<T> {
     }
 }
 
+impl<T: TextureFormat> SrgbTexture<T> {
+    pub fn generate_mipmaps(&self) {
+        unsafe {
+            self.texture.generate_mipmaps();
+        }
+    }
+}
+
 pub struct U8;
 
 impl TextureFormat for U8 {
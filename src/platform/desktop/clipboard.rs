@@ -0,0 +1,44 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[cfg(target_os = "windows")]
+const CLIPBOARD_COMMANDS: &[(&str, &[&str])] = &[("clip", &[])];
+#[cfg(target_os = "macos")]
+const CLIPBOARD_COMMANDS: &[(&str, &[&str])] = &[("pbcopy", &[])];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const CLIPBOARD_COMMANDS: &[(&str, &[&str])] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+];
+
+pub fn set_clipboard(text: String) {
+    std::thread::spawn(move || {
+        for (command, args) in CLIPBOARD_COMMANDS {
+            let child = Command::new(command)
+                .args(*args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+
+            let mut child = match child {
+                Ok(child) => child,
+                Err(_) => continue,
+            };
+
+            if let Some(mut stdin) = child.stdin.take() {
+                if let Err(error) = stdin.write_all(text.as_bytes()) {
+                    log::error!("unable to write to {}: {:?}", command, error);
+                }
+            }
+
+            if let Err(error) = child.wait() {
+                log::error!("clipboard command {} failed: {:?}", command, error);
+            }
+            return;
+        }
+
+        log::warn!("no clipboard command available");
+    });
+}
@@ -0,0 +1,128 @@
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const LOCAL_CHANNEL_PREFIX: &str = "Local_";
+const CHANNEL_CHANGED: &str = "Channel changed to Local : ";
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub fn watch_local_chat<P: Into<PathBuf>>(dir: P) -> Option<UnboundedReceiver<String>> {
+    let dir = dir.into();
+    if !dir.is_dir() {
+        log::error!("game log directory does not exist: {}", dir.display());
+        return None;
+    }
+
+    let (tx, rx) = unbounded();
+    std::thread::spawn(move || {
+        log::info!("watching game logs in {}", dir.display());
+        watch_dir(&dir, tx);
+    });
+
+    Some(rx)
+}
+
+fn watch_dir(dir: &Path, sender: UnboundedSender<String>) {
+    let mut current_log: Option<(PathBuf, u64)> = None;
+    let mut current_system = None;
+
+    loop {
+        if let Some((path, len)) = newest_local_log(dir) {
+            let changed = current_log
+                .as_ref()
+                .map(|(p, l)| *p != path || *l != len)
+                .unwrap_or(true);
+
+            if changed {
+                match std::fs::read(&path) {
+                    Ok(bytes) => {
+                        let system = last_local_system(&decode_log(&bytes));
+                        if let Some(system) = system.filter(|s| Some(s) != current_system.as_ref())
+                        {
+                            current_system = Some(system.clone());
+                            if sender.unbounded_send(system).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Err(error) => {
+                        log::error!("unable to read game log {}: {:?}", path.display(), error);
+                    }
+                }
+                current_log = Some((path, len));
+            }
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn newest_local_log(dir: &Path) -> Option<(PathBuf, u64)> {
+    let entries = std::fs::read_dir(dir).ok()?;
+
+    entries
+        .filter_map(Result::ok)
+        .filter(|e| {
+            e.file_name()
+                .to_str()
+                .map(|n| n.starts_with(LOCAL_CHANNEL_PREFIX) && n.ends_with(".txt"))
+                .unwrap_or(false)
+        })
+        .filter_map(|e| {
+            let metadata = e.metadata().ok()?;
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            Some((modified, e.path(), metadata.len()))
+        })
+        .max_by_key(|(modified, _, _)| *modified)
+        .map(|(_, path, len)| (path, len))
+}
+
+fn decode_log(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xff, 0xfe]) {
+        let units: Vec<u16> = bytes[2..]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+fn last_local_system(log: &str) -> Option<String> {
+    log.lines().rev().find_map(|line| {
+        let index = line.find(CHANNEL_CHANGED)?;
+        let system = line[index + CHANNEL_CHANGED.len()..].trim();
+        let system = system.trim_end_matches('*').trim();
+        if system.is_empty() {
+            None
+        } else {
+            Some(system.to_string())
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_latest_local_system() {
+        let log = "\
+[ 2021.03.01 18:00:00 ] EVE System > Channel changed to Local : Jita*\r\n\
+[ 2021.03.01 18:01:10 ] Someone > o7\r\n\
+[ 2021.03.01 18:02:31 ] EVE System > Channel changed to Local : Perimeter\r\n";
+        assert_eq!(last_local_system(log), Some("Perimeter".to_string()));
+        assert_eq!(last_local_system("no changes"), None);
+    }
+
+    #[test]
+    fn decodes_utf16_logs() {
+        let mut bytes = vec![0xff, 0xfe];
+        for unit in "Local : Amarr".encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        assert_eq!(decode_log(&bytes), "Local : Amarr");
+    }
+}
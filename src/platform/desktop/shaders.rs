@@ -5,10 +5,11 @@ use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::channel;
 use std::sync::Arc;
 
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
 macro_rules! shader_program(
-    ($name:ident, $vert:literal, $frag:literal) => {
+    ($name:ident, $vert:literal, $frag:literal, $legacy_vert:literal, $legacy_frag:literal) => {
         #[derive(Debug)]
         pub struct $name;
 
@@ -17,6 +18,10 @@ macro_rules! shader_program(
             const FRAGMENT_RELATIVE_PATH: &'static str = $frag;
             const VERTEX_SOURCE: &'static str = include_str!($vert);
             const FRAGMENT_SOURCE: &'static str = include_str!($frag);
+            const LEGACY_VERTEX_RELATIVE_PATH: &'static str = $legacy_vert;
+            const LEGACY_FRAGMENT_RELATIVE_PATH: &'static str = $legacy_frag;
+            const LEGACY_VERTEX_SOURCE: &'static str = include_str!($legacy_vert);
+            const LEGACY_FRAGMENT_SOURCE: &'static str = include_str!($legacy_frag);
         }
 
     }
@@ -25,25 +30,34 @@ macro_rules! shader_program(
 shader_program!(
     SystemsShader,
     "../../../shaders/systems_vert.glsl",
-    "../../../shaders/systems_frag.glsl"
+    "../../../shaders/systems_frag.glsl",
+    "../../../shaders/systems_vert_web.glsl",
+    "../../../shaders/systems_frag_web.glsl"
 );
 
 shader_program!(
     JumpsShader,
     "../../../shaders/jumps_vert.glsl",
-    "../../../shaders/jumps_frag.glsl"
+    "../../../shaders/jumps_frag.glsl",
+    "../../../shaders/jumps_vert_web.glsl",
+    "../../../shaders/jumps_frag_web.glsl"
 );
 
 shader_program!(
     TextShader,
     "../../../shaders/text_vert.glsl",
-    "../../../shaders/text_frag.glsl"
+    "../../../shaders/text_frag.glsl",
+    "../../../shaders/text_vert_web.glsl",
+    // The desktop glyph atlas stores coverage in the red channel, the web one in alpha
+    "../../../shaders/text_frag_legacy.glsl"
 );
 
 shader_program!(
     QuadShader,
     "../../../shaders/quad_vert.glsl",
-    "../../../shaders/quad_frag.glsl"
+    "../../../shaders/quad_frag.glsl",
+    "../../../shaders/quad_vert_web.glsl",
+    "../../../shaders/quad_frag_web.glsl"
 );
 
 pub trait ShaderProgram {
@@ -51,22 +65,42 @@ pub trait ShaderProgram {
     const FRAGMENT_RELATIVE_PATH: &'static str;
     const VERTEX_SOURCE: &'static str;
     const FRAGMENT_SOURCE: &'static str;
+    const LEGACY_VERTEX_RELATIVE_PATH: &'static str;
+    const LEGACY_FRAGMENT_RELATIVE_PATH: &'static str;
+    const LEGACY_VERTEX_SOURCE: &'static str;
+    const LEGACY_FRAGMENT_SOURCE: &'static str;
 
-    fn vertex_source() -> &'static str {
-        Self::VERTEX_SOURCE
+    fn vertex_source(legacy: bool) -> &'static str {
+        if legacy {
+            Self::LEGACY_VERTEX_SOURCE
+        } else {
+            Self::VERTEX_SOURCE
+        }
     }
 
-    fn fragment_source() -> &'static str {
-        Self::FRAGMENT_SOURCE
+    fn fragment_source(legacy: bool) -> &'static str {
+        if legacy {
+            Self::LEGACY_FRAGMENT_SOURCE
+        } else {
+            Self::FRAGMENT_SOURCE
+        }
     }
 
-    fn vertex_path<P: AsRef<Path>>(shader_dir: P) -> PathBuf {
-        let path = PathBuf::from(Self::VERTEX_RELATIVE_PATH);
+    fn vertex_path<P: AsRef<Path>>(shader_dir: P, legacy: bool) -> PathBuf {
+        let path = if legacy {
+            PathBuf::from(Self::LEGACY_VERTEX_RELATIVE_PATH)
+        } else {
+            PathBuf::from(Self::VERTEX_RELATIVE_PATH)
+        };
         shader_dir.as_ref().join(path.file_name().unwrap())
     }
 
-    fn fragment_path<P: AsRef<Path>>(shader_dir: P) -> PathBuf {
-        let path = PathBuf::from(Self::FRAGMENT_RELATIVE_PATH);
+    fn fragment_path<P: AsRef<Path>>(shader_dir: P, legacy: bool) -> PathBuf {
+        let path = if legacy {
+            PathBuf::from(Self::LEGACY_FRAGMENT_RELATIVE_PATH)
+        } else {
+            PathBuf::from(Self::FRAGMENT_RELATIVE_PATH)
+        };
         shader_dir.as_ref().join(path.file_name().unwrap())
     }
 }
@@ -74,19 +108,37 @@ pub trait ShaderProgram {
 pub struct ShaderCollection {
     pub version: Arc<AtomicUsize>,
     pub closed: Arc<AtomicBool>,
-    watcher: notify::RecommendedWatcher,
+    watcher: Option<notify::RecommendedWatcher>,
     update_thread: Option<std::thread::JoinHandle<()>>,
     shader_dir: PathBuf,
+    legacy_version: Option<&'static str>,
+    errors: BTreeMap<PathBuf, String>,
 }
 
 impl ShaderCollection {
-    pub fn new<P: AsRef<Path>>(shader_dir: P) -> ShaderCollection {
-        let (tx, rx) = channel();
-        let watcher = notify::watcher(tx, std::time::Duration::from_millis(100)).unwrap();
-
+    pub fn new<P: AsRef<Path>>(
+        shader_dir: P,
+        legacy_version: Option<&'static str>,
+        hot_reload: bool,
+    ) -> ShaderCollection {
         let closed = Arc::new(AtomicBool::new(false));
         let version = Arc::new(AtomicUsize::new(0));
 
+        if !hot_reload {
+            return ShaderCollection {
+                closed,
+                version,
+                watcher: None,
+                update_thread: None,
+                shader_dir: shader_dir.as_ref().into(),
+                legacy_version,
+                errors: BTreeMap::new(),
+            };
+        }
+
+        let (tx, rx) = channel();
+        let watcher = notify::watcher(tx, std::time::Duration::from_millis(100)).unwrap();
+
         let update_thread = Some(std::thread::spawn({
             let closed = closed.clone();
             let version = version.clone();
@@ -116,9 +168,11 @@ impl ShaderCollection {
         ShaderCollection {
             closed,
             version,
-            watcher,
+            watcher: Some(watcher),
             update_thread,
             shader_dir: shader_dir.as_ref().into(),
+            legacy_version,
+            errors: BTreeMap::new(),
         }
     }
 
@@ -127,8 +181,9 @@ impl ShaderCollection {
         display: &glium::Display,
         shader: &mut Option<Shader<S>>,
     ) {
-        let vertex_path = S::vertex_path(&self.shader_dir);
-        let fragment_path = S::fragment_path(&self.shader_dir);
+        let legacy = self.legacy_version.is_some();
+        let vertex_path = S::vertex_path(&self.shader_dir, legacy);
+        let fragment_path = S::fragment_path(&self.shader_dir, legacy);
 
         let current_version = self.version();
 
@@ -142,14 +197,16 @@ impl ShaderCollection {
                 let vertex_source = std::fs::read_to_string(&vertex_path).unwrap();
                 let fragment_source = std::fs::read_to_string(&fragment_path).unwrap();
 
-                let shader_result = program_from_source(display, &vertex_source, &fragment_source);
+                let shader_result =
+                    self.program_from_source(display, &vertex_source, &fragment_source);
                 match shader_result {
                     Ok(program) => {
                         *shader = Shader {
                             version: current_version,
                             program,
                             shader_type: Default::default(),
-                        }
+                        };
+                        self.errors.remove(&vertex_path);
                     }
                     Err(error) => {
                         log::error!(
@@ -159,18 +216,20 @@ impl ShaderCollection {
                             fragment_path.display()
                         );
                         shader.version = current_version;
+                        self.add_error(vertex_path, &fragment_path, error);
                     }
                 }
             }
         } else {
-            let _ = self
-                .watcher
-                .watch(vertex_path, notify::RecursiveMode::NonRecursive);
-            let _ = self
-                .watcher
-                .watch(fragment_path, notify::RecursiveMode::NonRecursive);
-            let shader_result =
-                program_from_source(display, S::vertex_source(), S::fragment_source());
+            if let Some(watcher) = self.watcher.as_mut() {
+                let _ = watcher.watch(&vertex_path, notify::RecursiveMode::NonRecursive);
+                let _ = watcher.watch(&fragment_path, notify::RecursiveMode::NonRecursive);
+            }
+            let shader_result = self.program_from_source(
+                display,
+                S::vertex_source(legacy),
+                S::fragment_source(legacy),
+            );
             match shader_result {
                 Ok(program) => {
                     *shader = Some(Shader {
@@ -179,12 +238,15 @@ impl ShaderCollection {
                         shader_type: Default::default(),
                     })
                 }
-                Err(error) => log::error!(
-                    "unable to load shader: {} {} {}",
-                    error,
-                    S::VERTEX_RELATIVE_PATH,
-                    S::FRAGMENT_RELATIVE_PATH
-                ),
+                Err(error) => {
+                    log::error!(
+                        "unable to load shader: {} {} {}",
+                        error,
+                        vertex_path.display(),
+                        fragment_path.display()
+                    );
+                    self.add_error(vertex_path, &fragment_path, error);
+                }
             }
         }
     }
@@ -192,6 +254,72 @@ impl ShaderCollection {
     pub fn version(&self) -> usize {
         self.version.load(Ordering::Relaxed)
     }
+
+    pub fn reload(&self) {
+        if self.watcher.is_some() {
+            self.version.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn errors(&self) -> Vec<String> {
+        self.errors.values().cloned().collect()
+    }
+
+    fn add_error(
+        &mut self,
+        vertex_path: PathBuf,
+        fragment_path: &Path,
+        error: glium::ProgramCreationError,
+    ) {
+        let error = format!(
+            "{} {}\n{}",
+            vertex_path.display(),
+            fragment_path.display(),
+            error
+        );
+        self.errors.insert(vertex_path, error);
+    }
+
+    fn program_from_source(
+        &self,
+        display: &glium::Display,
+        vertex_shader: &str,
+        fragment_shader: &str,
+    ) -> Result<glium::Program, glium::ProgramCreationError> {
+        if let Some(version) = self.legacy_version {
+            program_from_source(
+                display,
+                &legacy_source(version, vertex_shader),
+                &legacy_source(version, fragment_shader),
+            )
+        } else {
+            program_from_source(display, vertex_shader, fragment_shader)
+        }
+    }
+}
+
+// The web shaders prefix attributes and uniforms with `a_` and `u_`, strip them to match the
+// names bound by the desktop backend
+fn legacy_source(version: &str, source: &str) -> String {
+    let mut output = format!("#version {}\n", version);
+    let mut previous = None;
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let word_start = previous
+            .map(|p: char| !(p.is_alphanumeric() || p == '_'))
+            .unwrap_or(true);
+        if word_start && (c == 'a' || c == 'u') && chars.peek() == Some(&'_') {
+            chars.next();
+            previous = Some('_');
+            continue;
+        }
+
+        output.push(c);
+        previous = Some(c);
+    }
+
+    output
 }
 
 fn program_from_source(
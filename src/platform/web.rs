@@ -9,16 +9,20 @@ use crate::gfx::font::{FontCache, PositionedTextSpan};
 use crate::gfx::images::{Image, Images};
 use crate::gfx::{CircleVertex, LineVertex, QuadVertex, SystemData, TextVertex, UserEvent};
 use crate::math;
+use crate::settings::Settings;
 
 use std::cell::{Cell, RefCell};
 use std::convert::TryInto;
 use std::rc::Rc;
 
+pub use futures::executor::block_on;
 pub use wasm_bindgen_futures::spawn_local as spawn;
 pub use wasm_timer as time;
 
 mod gl;
 
+use crate::paths::{DYNAMIC_CACHE_FILE, PROFILE_FILE, STATIC_CACHE_FILE};
+
 const PROFILE: &[u8] = include_bytes!("../../eve-profile.json");
 const STATIC: &[u8] = include_bytes!("../../eve-static.dat");
 const DYNAMIC: &[u8] = include_bytes!("../../eve-dynamic.dat");
@@ -30,19 +34,23 @@ pub const USER_AGENT: Option<&'static str> = None;
 
 pub fn file_exists<P: AsRef<std::path::Path>>(path: P) -> bool {
     match path.as_ref().file_name().and_then(|s| s.to_str()) {
-        Some("eve-profile.json") => true,
-        Some("eve-static.dat") => true,
-        Some("eve-dynamic.dat") => true,
+        Some(PROFILE_FILE) => true,
+        Some(STATIC_CACHE_FILE) => true,
+        Some(DYNAMIC_CACHE_FILE) => true,
         Some("bridges.tsv") => true,
         _ => false,
     }
 }
 
+pub fn data_dir() -> Option<std::path::PathBuf> {
+    None
+}
+
 pub async fn read_file<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Vec<u8>> {
     match path.as_ref().file_name().and_then(|s| s.to_str()) {
-        Some("eve-profile.json") => Ok(Vec::from(PROFILE)),
-        Some("eve-static.dat") => Ok(Vec::from(STATIC)),
-        Some("eve-dynamic.dat") => Ok(Vec::from(DYNAMIC)),
+        Some(PROFILE_FILE) => Ok(Vec::from(PROFILE)),
+        Some(STATIC_CACHE_FILE) => Ok(Vec::from(STATIC)),
+        Some(DYNAMIC_CACHE_FILE) => Ok(Vec::from(DYNAMIC)),
         Some("bridges.tsv") => Ok(Vec::from(BRIDGES)),
         Some(p) => {
             log::info!("loading file: {}", p);
@@ -59,6 +67,20 @@ pub async fn write_file<P: AsRef<std::path::Path>, C: AsRef<[u8]>>(
     Ok(())
 }
 
+pub async fn remove_file<P: AsRef<std::path::Path>>(_path: P) -> std::io::Result<()> {
+    Ok(())
+}
+
+pub async fn create_dir_all<P: AsRef<std::path::Path>>(_path: P) -> std::io::Result<()> {
+    Ok(())
+}
+
+pub async fn list_dir<P: AsRef<std::path::Path>>(
+    _path: P,
+) -> std::io::Result<Vec<std::path::PathBuf>> {
+    Ok(Vec::new())
+}
+
 pub fn parse_http_date(s: &str) -> Option<time::SystemTime> {
     None
 }
@@ -71,8 +93,26 @@ pub fn create_event_proxy(_event_loop: &EventLoop<UserEvent>) -> (EventSender, E
     (tx, rx)
 }
 
+// EventLoopProxy can't wake the web event loop, so user events go through a channel that is
+// only drained once per loop iteration. Waiting would leave them queued until the next
+// input event, so web always polls and wait_until has nothing to schedule.
 pub const DEFAULT_CONTROL_FLOW: ControlFlow = ControlFlow::Poll;
 
+pub fn wait_until(_instant: time::Instant) -> ControlFlow {
+    DEFAULT_CONTROL_FLOW
+}
+
+pub fn watch_local_chat<P: Into<std::path::PathBuf>>(
+    _dir: P,
+) -> Option<futures::channel::mpsc::UnboundedReceiver<String>> {
+    log::warn!("game log location is not supported on web");
+    None
+}
+
+pub fn set_clipboard(text: String) {
+    log::warn!("clipboard is not supported on web, copied text:\n{}", text);
+}
+
 const SYSTEMS_VERT: &'static str = include_str!("../../shaders/systems_vert_web.glsl");
 const SYSTEMS_FRAG: &'static str = include_str!("../../shaders/systems_frag_web.glsl");
 
@@ -85,9 +125,20 @@ const QUAD_FRAG: &'static str = include_str!("../../shaders/quad_frag_web.glsl")
 const TEXT_VERT: &'static str = include_str!("../../shaders/text_vert_web.glsl");
 const TEXT_FRAG: &'static str = include_str!("../../shaders/text_frag_web.glsl");
 
+const SHADER_FILES: [&str; 8] = [
+    "systems_vert_web.glsl",
+    "systems_frag_web.glsl",
+    "jumps_vert_web.glsl",
+    "jumps_frag_web.glsl",
+    "quad_vert_web.glsl",
+    "quad_frag_web.glsl",
+    "text_vert_web.glsl",
+    "text_frag_web.glsl",
+];
+
 pub struct GraphicsBackend {
     canvas: web_sys::HtmlCanvasElement,
-    window: winit::window::Window,
+    window: Rc<winit::window::Window>,
     context: Rc<gl::GlContext>,
     window_size: Cell<math::V2<f32>>,
     system_program: RefCell<gl::GlProgram>,
@@ -96,6 +147,8 @@ pub struct GraphicsBackend {
     text_program: RefCell<gl::GlProgram>,
     quad_indices: RefCell<Vec<u32>>,
     quad_index_buffer: RefCell<gl::GlIndexBuffer<u32>>,
+    hot_reload: bool,
+    reloaded_shaders: Rc<RefCell<Option<Vec<String>>>>,
 }
 
 impl GraphicsBackend {
@@ -104,6 +157,7 @@ impl GraphicsBackend {
         event_loop: &EventLoop<UserEvent>,
         width: u32,
         height: u32,
+        settings: &Settings,
     ) -> GraphicsBackend {
         let document = web_sys::window().unwrap().document().unwrap();
         let canvas: web_sys::HtmlCanvasElement = document
@@ -124,6 +178,7 @@ impl GraphicsBackend {
             .with_inner_size(winit::dpi::LogicalSize::new(width, height))
             .build(event_loop)
             .unwrap();
+        let window = Rc::new(window);
 
         let window_size = { math::v2(canvas.width(), canvas.height()).as_f32() };
         let context = Rc::new(gl::GlContext::new(canvas.clone()));
@@ -162,9 +217,60 @@ impl GraphicsBackend {
             text_program,
             quad_indices,
             quad_index_buffer,
+            hot_reload: settings.dev_mode,
+            reloaded_shaders: Rc::new(RefCell::new(None)),
         }
     }
 
+    pub fn reload_shaders(&self) {
+        if !self.hot_reload {
+            return;
+        }
+
+        let base_url = web_sys::window()
+            .and_then(|w| w.document())
+            .and_then(|d| d.url().ok())
+            .and_then(|url| reqwest::Url::parse(&url).ok());
+        let base_url = match base_url {
+            Some(base_url) => base_url,
+            None => return,
+        };
+
+        let reloaded_shaders = self.reloaded_shaders.clone();
+        let window = self.window.clone();
+        spawn(async move {
+            let mut sources = Vec::new();
+            for file in SHADER_FILES.iter() {
+                let url = match base_url.join(&format!("shaders/{}", file)) {
+                    Ok(url) => url,
+                    Err(error) => {
+                        log::error!("invalid shader url {}: {:?}", file, error);
+                        return;
+                    }
+                };
+                let source = match reqwest::get(url).await {
+                    Ok(response) if response.status().is_success() => response.text().await,
+                    Ok(response) => {
+                        log::error!("unable to fetch shader {}: {}", file, response.status());
+                        return;
+                    }
+                    Err(error) => Err(error),
+                };
+                match source {
+                    Ok(source) => sources.push(source),
+                    Err(error) => {
+                        log::error!("unable to fetch shader {}: {:?}", file, error);
+                        return;
+                    }
+                }
+            }
+
+            log::info!("fetched updated shaders");
+            *reloaded_shaders.borrow_mut() = Some(sources);
+            window.request_redraw();
+        });
+    }
+
     fn depth_test(&self, enable: bool) {
         if enable {
             self.context.enable(GL::DEPTH_TEST);
@@ -177,6 +283,11 @@ impl GraphicsBackend {
         self.window.request_redraw();
     }
 
+    pub fn set_ime_position(&self, position: math::V2<f32>) {
+        let position = winit::dpi::PhysicalPosition::new(position.x, position.y);
+        self.window.set_ime_position(position);
+    }
+
     pub fn create_texture<T: Texture>(&self, width: u32, height: u32) -> T {
         T::create(self.context.clone(), width, height)
     }
@@ -200,11 +311,36 @@ impl GraphicsBackend {
         log::info!("resized {} {}", window_size.x, window_size.y);
     }
 
+    pub fn max_texture_size(&self) -> u32 {
+        self.context
+            .get_parameter(GL::MAX_TEXTURE_SIZE)
+            .ok()
+            .and_then(|size| size.as_f64())
+            .map(|size| size as u32)
+            .unwrap_or(2048)
+    }
+
+    pub fn shader_errors(&self) -> Vec<String> {
+        Vec::new()
+    }
+
     pub fn window_size(&self) -> math::V2<f32> {
         self.window_size.get()
     }
 
     pub fn begin(&self) -> Frame {
+        if let Some(sources) = self.reloaded_shaders.borrow_mut().take() {
+            log::info!("updating shaders");
+            let context = self.context.clone();
+            *self.system_program.borrow_mut() =
+                gl::GlProgram::new(context.clone(), &sources[0], &sources[1]);
+            *self.jumps_program.borrow_mut() =
+                gl::GlProgram::new(context.clone(), &sources[2], &sources[3]);
+            *self.quad_program.borrow_mut() =
+                gl::GlProgram::new(context.clone(), &sources[4], &sources[5]);
+            *self.text_program.borrow_mut() = gl::GlProgram::new(context, &sources[6], &sources[7]);
+        }
+
         Frame {
             context: self.context.clone(),
         }
@@ -334,6 +470,7 @@ impl GraphicsBackend {
         image: Image,
         position: math::Rect<f32>,
     ) {
+        images.generate_mipmaps();
         self.depth_test(false);
         let mut uniforms = gl::GlUniformCollection::new();
         let window_size = self.window_size.get();
@@ -446,6 +583,7 @@ impl<T: TextureFormat> Texture for RgbTexture<T> {
 pub struct SrgbTexture<T: TextureFormat> {
     marker: std::marker::PhantomData<T>,
     texture: gl::GlTexture,
+    mipmaps: bool,
 }
 
 impl<T: TextureFormat> Texture for SrgbTexture<T> {
@@ -456,9 +594,14 @@ impl<T: TextureFormat> Texture for SrgbTexture<T> {
             PixelFormat::Rgba => gl::PixelFormat::RGBA,
         };
         let texture = gl::GlTexture::new(context, width, height, format);
+        let mipmaps = width.is_power_of_two() && height.is_power_of_two();
+        if mipmaps {
+            texture.enable_mipmaps();
+        }
         SrgbTexture {
             texture,
             marker: Default::default(),
+            mipmaps,
         }
     }
 
@@ -475,7 +618,15 @@ impl<T: TextureFormat> Texture for SrgbTexture<T> {
             region.height(),
             format,
             data,
-        )
+        );
+    }
+}
+
+impl<T: TextureFormat> SrgbTexture<T> {
+    pub fn generate_mipmaps(&self) {
+        if self.mipmaps {
+            self.texture.generate_mipmaps();
+        }
     }
 }
 
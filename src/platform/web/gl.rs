@@ -788,6 +788,21 @@ impl GlTexture {
         GlTexture { gl, texture }
     }
 
+    pub fn enable_mipmaps(&self) {
+        self.generate_mipmaps();
+        self.gl.tex_parameteri(
+            GL::TEXTURE_2D,
+            GL::TEXTURE_MIN_FILTER,
+            GL::LINEAR_MIPMAP_LINEAR as i32,
+        );
+    }
+
+    pub fn generate_mipmaps(&self) {
+        self.gl.active_texture(GL::TEXTURE0);
+        self.gl.bind_texture(GL::TEXTURE_2D, Some(&self.texture));
+        self.gl.generate_mipmap(GL::TEXTURE_2D);
+    }
+
     pub fn sub_image(
         &self,
         x: u32,
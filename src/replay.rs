@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::gfx::{DataEvent, UserEvent};
+use crate::input::InputState;
+use crate::platform::time::Instant;
+use crate::platform::{read_file, write_file};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedEvent {
+    time: u64,
+    event: UserEvent,
+}
+
+pub struct EventRecorder {
+    path: String,
+    start: Option<Instant>,
+    events: Vec<RecordedEvent>,
+}
+
+impl EventRecorder {
+    pub fn new<S: Into<String>>(path: S) -> Self {
+        EventRecorder {
+            path: path.into(),
+            start: None,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, event: &UserEvent) {
+        match event {
+            UserEvent::DataEvent(DataEvent::GalaxyImported) => {
+                self.start = Some(Instant::now());
+                self.events.clear();
+            }
+            UserEvent::DataEvent(DataEvent::GalaxyLoaded(_)) | UserEvent::FrameDrawn => (),
+            event => {
+                if let Some(start) = self.start {
+                    self.events.push(RecordedEvent {
+                        time: start.elapsed().as_millis() as u64,
+                        event: event.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    pub async fn save(&self) -> Result<(), Error> {
+        let mut log = String::new();
+        for event in &self.events {
+            log.push_str(&serde_json::to_string(event)?);
+            log.push('\n');
+        }
+
+        write_file(&self.path, log).await?;
+        log::info!("saved {} events to {}", self.events.len(), self.path);
+        Ok(())
+    }
+}
+
+pub struct EventReplay {
+    start: Option<Instant>,
+    events: VecDeque<RecordedEvent>,
+}
+
+impl EventReplay {
+    pub async fn load(path: &str) -> Result<EventReplay, Error> {
+        let bytes = read_file(path).await?;
+        let log = String::from_utf8_lossy(&bytes);
+
+        let mut events = VecDeque::new();
+        for line in log.lines().filter(|l| !l.is_empty()) {
+            events.push_back(serde_json::from_str(line)?);
+        }
+
+        log::info!("loaded {} events from {}", events.len(), path);
+
+        Ok(EventReplay {
+            start: None,
+            events,
+        })
+    }
+
+    pub fn finished(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn update(&mut self, input_state: &mut InputState) {
+        if self.start.is_none() {
+            let imported = input_state
+                .user_events()
+                .any(|e| matches!(e, UserEvent::DataEvent(DataEvent::GalaxyImported)));
+            if imported {
+                log::info!("starting event replay");
+                self.start = Some(Instant::now());
+            }
+        }
+
+        if let Some(start) = self.start {
+            let elapsed = start.elapsed();
+            while let Some(event) = self.events.front() {
+                if Duration::from_millis(event.time) > elapsed {
+                    break;
+                }
+                let event = self.events.pop_front().unwrap();
+                input_state.push_user_event(event.event);
+            }
+        }
+    }
+}
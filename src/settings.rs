@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::platform::{file_exists, read_file};
+
+const SETTINGS_FILE: &str = "eve-settings.json";
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub system_name_max_length: Option<usize>,
+    pub show_scale_bar: bool,
+    pub route_kill_penalty: f64,
+    pub record_events: Option<String>,
+    pub replay_events: Option<String>,
+    pub ui_font: Option<String>,
+    pub dev_mode: bool,
+    pub image_atlas_size: Option<u32>,
+    pub disable_vsync: bool,
+    pub max_frame_rate: Option<u32>,
+    pub game_log_dir: Option<String>,
+    pub system_metrics: Option<String>,
+    pub avoided_systems: Vec<String>,
+    pub trade_hubs: Option<Vec<i32>>,
+    pub clear_route_on_arrival: bool,
+    pub seconds_per_jump: Option<f64>,
+    pub show_route_danger: bool,
+    pub route_danger_threshold: Option<i32>,
+    pub esi_concurrency: Option<usize>,
+    pub esi_timeout_seconds: Option<u64>,
+    pub offline: bool,
+    pub data_dir: Option<String>,
+}
+
+impl Settings {
+    pub async fn load() -> Settings {
+        if !file_exists(SETTINGS_FILE) {
+            log::info!("no settings found, using defaults");
+            return Settings::default();
+        }
+
+        match Self::read().await {
+            Ok(settings) => {
+                log::info!("loaded settings from {}", SETTINGS_FILE);
+                settings
+            }
+            Err(error) => {
+                log::error!("unable to load settings, using defaults: {:?}", error);
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn apply_args<I: IntoIterator<Item = String>>(&mut self, args: I) {
+        for arg in args {
+            match arg.as_str() {
+                "--offline" => self.offline = true,
+                _ => log::warn!("unknown argument: {}", arg),
+            }
+        }
+    }
+
+    async fn read() -> Result<Settings, Error> {
+        let bytes = read_file(SETTINGS_FILE).await?;
+        let settings = serde_json::from_slice(&bytes)?;
+        Ok(settings)
+    }
+}
@@ -1,18 +1,24 @@
 use ahash::{AHashMap as HashMap, AHashSet as HashSet};
-use async_std::sync::RwLock as RwLockAsync;
 use async_std::task::sleep;
 use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
 use futures::future::FutureExt;
 use futures::stream::futures_unordered::FuturesUnordered;
 use futures::stream::StreamExt;
+use petgraph::visit::EdgeRef;
 use petgraph::Graph;
+use serde::{Deserialize, Serialize};
 
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 
 use crate::esi;
-use crate::gfx::{DataEvent, UserEvent, UserEventSender};
+use crate::gfx::{DataEvent, LoadStage, Severity, UserEvent, UserEventSender};
 use crate::math;
-use crate::platform::{file_exists, read_file, spawn, EventSender};
+use crate::oauth;
+use crate::platform::time::Instant;
+use crate::platform::{block_on, file_exists, read_file, spawn, watch_local_chat, EventSender};
 
 #[derive(Debug, Clone, Copy)]
 pub enum Edge {
@@ -22,13 +28,18 @@ pub enum Edge {
     Jump { left: i32, right: i32 },
 }
 
+// Every hop costs one JUMP_COST so routes agree with the jump counts from
+// distances_from. Bridges and wormholes pay a fraction of a hop extra so
+// gates win ties, without ever outweighing a second gate jump.
+const JUMP_COST: f64 = 1073741824.0;
+
 impl Edge {
     fn distance(&self) -> f64 {
         match self {
             Edge::Warp { distance, .. } => 1e3 - distance,
-            Edge::Jump { .. } => (2.0f64).powi(30),
-            Edge::JumpBridge { .. } => (2.0f64).powi(31),
-            Edge::Wormhole { .. } => (2.0f64).powi(32),
+            Edge::Jump { .. } => JUMP_COST,
+            Edge::JumpBridge { .. } => JUMP_COST * 1.25,
+            Edge::Wormhole { .. } => JUMP_COST * 1.5,
         }
     }
 }
@@ -48,9 +59,14 @@ enum Node {
         source: i32,
         destination: i32,
     },
+    Wormhole {
+        stargate: i32,
+        source: i32,
+        destination: i32,
+    },
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum JumpType {
     System,
     Constellation,
@@ -59,10 +75,21 @@ pub enum JumpType {
     Wormhole,
 }
 
+impl JumpType {
+    fn label(&self) -> &'static str {
+        match self {
+            JumpType::System | JumpType::Constellation | JumpType::Region => "GATE",
+            JumpType::JumpGate => "BRIDGE",
+            JumpType::Wormhole => "WORMHOLE",
+        }
+    }
+}
+
 pub struct Jump {
     pub left_system_id: i32,
     pub right_system_id: i32,
     pub jump_type: JumpType,
+    pub restricted: bool,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -73,13 +100,34 @@ pub struct Stats {
     pub jumps: i32,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Sov {
     pub alliance_id: Option<i32>,
     pub corporation_id: Option<i32>,
     pub standing: f64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SovSnapshot {
+    sov: Vec<(i32, Sov)>,
+    affiliation: Option<Affiliation>,
+    alliances: Vec<esi::GetAlliance>,
+    corporations: Vec<esi::GetCorporation>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Affiliation {
+    pub alliance_id: Option<i32>,
+    pub corporation_id: Option<i32>,
+}
+
+impl Affiliation {
+    fn holds(&self, sov: &Sov) -> bool {
+        let same = |a: Option<i32>, b: Option<i32>| a.is_some() && a == b;
+        same(self.alliance_id, sov.alliance_id) || same(self.corporation_id, sov.corporation_id)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct RouteNode {
     pub arrive_jump: Option<JumpType>,
@@ -87,52 +135,442 @@ pub struct RouteNode {
     pub system_id: i32,
 }
 
+pub const METERS_PER_LIGHT_YEAR: f64 = 9.4607e15;
+pub const DEFAULT_ROUTE: &str = "main";
+pub const SHORTEST_ROUTE: &str = "shortest";
+pub const SAFER_ROUTE: &str = "safer";
+const MAX_ROUTES: usize = 4;
+pub const MAX_TOUR_SYSTEMS: usize = 16;
+// Jita, Amarr, Dodixie, Rens and Hek
+const DEFAULT_TRADE_HUBS: [i32; 5] = [30000142, 30002187, 30002659, 30002510, 30002053];
+const DISTANCE_CACHE_SIZE: usize = 8;
+const MAX_SYSTEM_MATCHES: usize = 50;
+const GALAXY_LOAD_WINDOW: usize = 64;
+const LOAD_PROGRESS_INTERVAL: usize = 50;
+const SOV_SNAPSHOT: &str = "sov-standings";
+// contacts are cached by ESI for five minutes
+const SOV_SNAPSHOT_LIFETIME: std::time::Duration = std::time::Duration::from_secs(300);
+const FOCUS_REFRESH_THRESHOLD: std::time::Duration = std::time::Duration::from_secs(60);
+const HIGH_SEC_THRESHOLD: f64 = 0.45;
+const WORMHOLE_SYSTEM_IDS: std::ops::Range<i32> = 31000000..32000000;
+// wormhole, abyssal and test regions all have ids above this range
+const KNOWN_SPACE_REGION_IDS: std::ops::Range<i32> = 10000000..11000000;
+// UUA-F4, J7HZ-F and A821-A, unreachable jove regions
+const JOVE_REGION_IDS: [i32; 3] = [10000004, 10000017, 10000019];
+
+fn is_known_space_region(region_id: i32) -> bool {
+    KNOWN_SPACE_REGION_IDS.contains(&region_id) && !JOVE_REGION_IDS.contains(&region_id)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityClass {
+    High,
+    Low,
+    Null,
+    Wormhole,
+}
+
+impl SecurityClass {
+    pub fn classify(system_id: i32, security: f64) -> Self {
+        if WORMHOLE_SYSTEM_IDS.contains(&system_id) {
+            SecurityClass::Wormhole
+        } else if security >= HIGH_SEC_THRESHOLD {
+            SecurityClass::High
+        } else if security > 0.0 {
+            SecurityClass::Low
+        } else {
+            SecurityClass::Null
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SecurityClass::High => "High Sec",
+            SecurityClass::Low => "Low Sec",
+            SecurityClass::Null => "Null Sec",
+            SecurityClass::Wormhole => "Wormhole",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutePreference {
+    Shortest,
+    Safer,
+    LessSecure,
+}
+
+impl RoutePreference {
+    fn security_penalty(&self, class: SecurityClass) -> f64 {
+        let avoid = match self {
+            RoutePreference::Shortest => false,
+            RoutePreference::Safer => class != SecurityClass::High,
+            RoutePreference::LessSecure => class == SecurityClass::High,
+        };
+
+        if avoid {
+            (2.0f64).powi(40)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SecurityBreakdown {
+    pub high: usize,
+    pub low: usize,
+    pub null: usize,
+    pub wormhole: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FocusSummary {
+    pub position: math::V3<f64>,
+    pub system_count: usize,
+    pub average_security: f64,
+    pub total_jumps: i32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RouteSummary {
+    pub systems: usize,
+    pub gates: usize,
+    pub jump_bridges: usize,
+    pub wormholes: usize,
+    pub security: SecurityBreakdown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RouteError {
+    UnknownSystem(i32),
+    NoRoute(i32, i32),
+    TooManySystems(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Route {
+    name: String,
+    waypoints: Vec<i32>,
+    preference: RoutePreference,
+    systems: Vec<i32>,
+    nodes: Vec<RouteNode>,
+}
+
+impl Route {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn target(&self) -> (i32, i32) {
+        (self.waypoints[0], self.waypoints[self.waypoints.len() - 1])
+    }
+
+    pub fn nodes(&self) -> &[RouteNode] {
+        self.nodes.as_slice()
+    }
+
+    pub fn contains(&self, system_id: i32) -> bool {
+        self.systems.iter().any(|&r| r == system_id)
+    }
+}
+
 enum UpdateRequest {
     AllianceLogo(i32),
+    WarmCache(Vec<i32>, Vec<i32>),
     SendRouteToClient(Option<i32>, Vec<i32>),
+    SetWaypoint(i32),
+    ListProfiles,
+    SwitchProfile(i32),
+    Logout,
+    ClearCache,
 }
 
-pub struct World {
+#[derive(Clone, Debug)]
+struct DistanceCache {
+    entries: VecDeque<(i32, Arc<HashMap<i32, u32>>)>,
+    capacity: usize,
+}
+
+impl DistanceCache {
+    fn new(capacity: usize) -> Self {
+        DistanceCache {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    fn get(&mut self, system_id: i32) -> Option<Arc<HashMap<i32, u32>>> {
+        let index = self.entries.iter().position(|(s, _)| *s == system_id)?;
+        let entry = self.entries.remove(index)?;
+        let distances = entry.1.clone();
+        self.entries.push_front(entry);
+        Some(distances)
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn insert(&mut self, system_id: i32, distances: Arc<HashMap<i32, u32>>) {
+        self.entries.retain(|(s, _)| *s != system_id);
+        self.entries.push_front((system_id, distances));
+        self.entries.truncate(self.capacity);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GalaxyGraph {
     systems: HashMap<i32, esi::GetUniverseSystem>,
     systems_by_name: HashMap<String, i32>,
+    system_name_index: Vec<(String, i32)>,
     stargates: HashMap<i32, esi::GetUniverseStargate>,
     constellations: HashMap<i32, esi::GetUniverseConstellation>,
     regions: HashMap<i32, esi::GetUniverseRegion>,
     graph: Graph<Node, Edge, petgraph::Undirected, u32>,
-    route: Vec<i32>,
-    route_target: Option<(i32, i32)>,
-    route_nodes: Vec<RouteNode>,
-    system_stats: Arc<RwLock<HashMap<i32, Stats>>>,
-    player_system: Arc<RwLock<Option<i32>>>,
-    sov: Arc<RwLock<HashMap<i32, Sov>>>,
-    alliances: Arc<RwLock<HashMap<i32, esi::GetAlliance>>>,
-    corporations: Arc<RwLock<HashMap<i32, esi::GetCorporation>>>,
-    alliance_logos: Arc<RwLock<HashMap<i32, Arc<Vec<u8>>>>>,
-    event_sender: EventSender,
-    update_sender: Option<UnboundedSender<UpdateRequest>>,
+    system_nodes: HashMap<i32, petgraph::graph::NodeIndex>,
+    distance_cache: RefCell<DistanceCache>,
+    excluded_jumps: HashSet<JumpType>,
+    bridge_access: HashMap<(i32, i32), Vec<i32>>,
+    access_ids: Vec<i32>,
 }
 
-impl World {
-    pub fn new(event_sender: EventSender) -> Self {
-        World {
+impl GalaxyGraph {
+    pub fn new() -> Self {
+        GalaxyGraph {
             systems: HashMap::new(),
             systems_by_name: HashMap::new(),
+            system_name_index: Vec::new(),
             stargates: HashMap::new(),
             constellations: HashMap::new(),
             regions: HashMap::new(),
             graph: Graph::new_undirected(),
-            route: Vec::new(),
-            route_target: None,
-            route_nodes: Vec::new(),
-            system_stats: Arc::new(RwLock::new(HashMap::new())),
-            player_system: Arc::new(RwLock::new(None)),
-            sov: Arc::new(RwLock::new(HashMap::new())),
-            alliances: Arc::new(RwLock::new(HashMap::new())),
-            corporations: Arc::new(RwLock::new(HashMap::new())),
-            alliance_logos: Arc::new(RwLock::new(HashMap::new())),
-            event_sender,
-            update_sender: None,
+            system_nodes: HashMap::new(),
+            distance_cache: RefCell::new(DistanceCache::new(DISTANCE_CACHE_SIZE)),
+            excluded_jumps: HashSet::new(),
+            bridge_access: HashMap::new(),
+            access_ids: Vec::new(),
+        }
+    }
+
+    pub fn build(
+        regions: Vec<esi::GetUniverseRegion>,
+        constellations: Vec<esi::GetUniverseConstellation>,
+        systems: Vec<esi::GetUniverseSystem>,
+        stargates: Vec<esi::GetUniverseStargate>,
+    ) -> Self {
+        let mut galaxy = GalaxyGraph::new();
+        let mut all_stargates = HashMap::new();
+
+        for region in regions {
+            galaxy.regions.insert(region.region_id, region);
+        }
+
+        for constellation in constellations {
+            galaxy
+                .constellations
+                .insert(constellation.constellation_id, constellation);
+        }
+
+        for system in systems {
+            let node_id = galaxy.graph.add_node(Node::System {
+                system: system.system_id,
+            });
+            galaxy.system_nodes.insert(system.system_id, node_id);
+
+            galaxy
+                .systems_by_name
+                .insert(system.name.clone(), system.system_id);
+            galaxy.systems.insert(system.system_id, system);
+        }
+
+        for stargate in stargates {
+            let node_id = galaxy.graph.add_node(Node::Stargate {
+                stargate: stargate.stargate_id,
+                source: stargate.system_id,
+                destination: stargate.destination.system_id,
+            });
+            all_stargates.insert(stargate.stargate_id, node_id);
+            galaxy.stargates.insert(stargate.stargate_id, stargate);
+        }
+
+        for system in galaxy.systems.values() {
+            let system_node = galaxy.system_nodes.get(&system.system_id).unwrap();
+            let system_position: math::V3<f64> =
+                math::V3::new(system.position.x, system.position.y, system.position.z);
+
+            if let Some(system_stargates) = &system.stargates {
+                for stargate_id in system_stargates {
+                    let (stargate, stargate_node) = match (
+                        galaxy.stargates.get(stargate_id),
+                        all_stargates.get(stargate_id),
+                    ) {
+                        (Some(stargate), Some(node)) => (stargate, node),
+                        _ => continue,
+                    };
+                    let stargate_position: math::V3<f64> = math::V3::new(
+                        stargate.position.x,
+                        stargate.position.y,
+                        stargate.position.z,
+                    );
+
+                    let edge = Edge::Warp {
+                        system: system.system_id,
+                        distance: system_position.distance(&stargate_position) / 1e12,
+                    };
+
+                    galaxy
+                        .graph
+                        .add_edge(system_node.clone(), stargate_node.clone(), edge);
+
+                    for stargate_id_inner in system_stargates {
+                        if stargate_id >= stargate_id_inner {
+                            continue;
+                        }
+
+                        let (stargate_inner, stargate_inner_node) = match (
+                            galaxy.stargates.get(stargate_id_inner),
+                            all_stargates.get(stargate_id_inner),
+                        ) {
+                            (Some(stargate), Some(node)) => (stargate, node),
+                            _ => continue,
+                        };
+                        let stargate_inner_position: math::V3<f64> = math::V3::new(
+                            stargate_inner.position.x,
+                            stargate_inner.position.y,
+                            stargate_inner.position.z,
+                        );
+
+                        let edge = Edge::Warp {
+                            system: system.system_id,
+                            distance: stargate_position.distance(&stargate_inner_position) / 1e12,
+                        };
+
+                        galaxy.graph.add_edge(
+                            stargate_node.clone(),
+                            stargate_inner_node.clone(),
+                            edge,
+                        );
+                    }
+
+                    if stargate.system_id >= stargate.destination.system_id {
+                        continue;
+                    }
+
+                    let destination_node = all_stargates.get(&stargate.destination.stargate_id);
+
+                    if let Some(destination_node) = destination_node {
+                        let edge = Edge::Jump {
+                            left: stargate.system_id,
+                            right: stargate.destination.system_id,
+                        };
+
+                        galaxy.graph.add_edge(
+                            stargate_node.clone(),
+                            destination_node.clone(),
+                            edge,
+                        );
+                    }
+                }
+            }
         }
+
+        galaxy.system_name_index = GalaxyGraph::build_name_index(&galaxy.systems_by_name);
+        galaxy
+    }
+
+    fn add_virtual_gates(
+        &mut self,
+        gate_id: i32,
+        left: &esi::GetUniverseSystem,
+        right: &esi::GetUniverseSystem,
+        wormhole: bool,
+    ) {
+        let left_gate_id = gate_id;
+        let right_gate_id = gate_id + 1;
+        let left_gate = esi::GetUniverseStargate {
+            stargate_id: left_gate_id,
+            name: format!("{} » {}", left.name, right.name),
+            destination: esi::GetUniverseStargateDestination {
+                stargate_id: right_gate_id,
+                system_id: right.system_id,
+            },
+            position: esi::Position {
+                x: left.position.x,
+                y: left.position.y,
+                z: left.position.z,
+            },
+            system_id: left.system_id,
+        };
+
+        let right_gate = esi::GetUniverseStargate {
+            stargate_id: right_gate_id,
+            name: format!("{} » {}", right.name, left.name),
+            destination: esi::GetUniverseStargateDestination {
+                stargate_id: left_gate_id,
+                system_id: left.system_id,
+            },
+            position: esi::Position {
+                x: right.position.x,
+                y: right.position.y,
+                z: right.position.z,
+            },
+            system_id: right.system_id,
+        };
+
+        let gate_node = |stargate, source, destination| {
+            if wormhole {
+                Node::Wormhole {
+                    stargate,
+                    source,
+                    destination,
+                }
+            } else {
+                Node::JumpGate {
+                    stargate,
+                    source,
+                    destination,
+                }
+            }
+        };
+
+        self.stargates.insert(left_gate_id, left_gate);
+        let left_node = gate_node(left_gate_id, left.system_id, right.system_id);
+        let left_node_id = self.graph.add_node(left_node);
+        let left_system_node = self.system_nodes.get(&left.system_id).unwrap();
+
+        self.stargates.insert(right_gate_id, right_gate);
+        let right_node = gate_node(right_gate_id, right.system_id, left.system_id);
+        let right_node_id = self.graph.add_node(right_node);
+        let right_system_node = self.system_nodes.get(&right.system_id).unwrap();
+
+        let left_warp = Edge::Warp {
+            system: left.system_id,
+            distance: 1.0,
+        };
+
+        let right_warp = Edge::Warp {
+            system: right.system_id,
+            distance: 1.0,
+        };
+
+        let edge = if wormhole {
+            Edge::Wormhole {
+                system: left.system_id,
+                wormhole: right.system_id,
+            }
+        } else {
+            Edge::JumpBridge {
+                left: left.system_id,
+                right: right.system_id,
+            }
+        };
+
+        self.graph
+            .add_edge(left_node_id, *left_system_node, left_warp);
+        self.graph
+            .add_edge(right_node_id, *right_system_node, right_warp);
+        self.graph.add_edge(left_node_id, right_node_id, edge);
     }
 
     pub fn systems(&self) -> impl Iterator<Item = &esi::GetUniverseSystem> {
@@ -143,7 +581,7 @@ impl World {
         self.systems.get(&system_id)
     }
 
-    fn system_by_name(&self, name: &str) -> Option<&esi::GetUniverseSystem> {
+    pub fn system_by_name(&self, name: &str) -> Option<&esi::GetUniverseSystem> {
         self.systems_by_name
             .get(name)
             .and_then(|id| self.system(*id))
@@ -157,195 +595,256 @@ impl World {
         self.regions.get(&region_id)
     }
 
+    pub fn constellations(&self) -> impl Iterator<Item = &esi::GetUniverseConstellation> {
+        self.constellations.values()
+    }
+
     pub fn constellation(&self, constellation_id: i32) -> Option<&esi::GetUniverseConstellation> {
         self.constellations.get(&constellation_id)
     }
 
-    pub fn alliance(&self, alliance_id: i32) -> Option<esi::GetAlliance> {
-        self.alliances.read().unwrap().get(&alliance_id).cloned()
+    pub fn is_known_space(&self, system_id: i32) -> bool {
+        self.system(system_id)
+            .and_then(|s| self.constellation(s.constellation_id))
+            .map(|c| is_known_space_region(c.region_id))
+            .unwrap_or(false)
     }
 
-    pub fn corporation(&self, corporation_id: i32) -> Option<esi::GetCorporation> {
-        self.corporations
-            .read()
-            .unwrap()
-            .get(&corporation_id)
-            .cloned()
+    pub fn distances_from(&self, system_id: i32) -> Arc<HashMap<i32, u32>> {
+        if let Some(distances) = self.distance_cache.borrow_mut().get(system_id) {
+            return distances;
+        }
+
+        let idx = match self.system_nodes.get(&system_id) {
+            Some(idx) => *idx,
+            None => return Arc::new(HashMap::new()),
+        };
+
+        let graph =
+            petgraph::visit::EdgeFiltered::from_fn(&self.graph, |e| !self.is_excluded(e.weight()));
+        let distances = petgraph::algo::dijkstra(&graph, idx, None, |e| match e.weight() {
+            Edge::JumpBridge { .. } | Edge::Jump { .. } | Edge::Wormhole { .. } => 1,
+            _ => 0,
+        });
+
+        let distances: Arc<HashMap<_, _>> = Arc::new(
+            distances
+                .into_iter()
+                .filter_map(|(k, distance)| match self.graph[k] {
+                    Node::System { system } => Some((system, distance)),
+                    _ => None,
+                })
+                .collect(),
+        );
+
+        self.distance_cache
+            .borrow_mut()
+            .insert(system_id, distances.clone());
+        distances
     }
 
-    pub fn alliance_logo(&self, alliance_id: i32) -> Option<Arc<Vec<u8>>> {
-        let logo = self
-            .alliance_logos
-            .read()
-            .unwrap()
-            .get(&alliance_id)
-            .cloned();
-        if logo.is_some() {
-            logo
-        } else {
-            if let Some(sender) = self.update_sender.as_ref() {
-                let _ = sender.unbounded_send(UpdateRequest::AllianceLogo(alliance_id));
-            }
-            None
-        }
+    pub fn nearest_system(&self, from: i32, candidates: &[i32]) -> Option<(i32, u32)> {
+        let distances = self.distances_from(from);
+        candidates
+            .iter()
+            .filter_map(|id| Some((*id, *distances.get(id)?)))
+            .min_by_key(|(_, distance)| *distance)
     }
 
-    pub fn stats(&self, system_id: i32) -> Option<Stats> {
-        let stats = self.system_stats.read().unwrap();
-        stats.get(&system_id).cloned()
+    pub fn security_class(&self, system_id: i32) -> SecurityClass {
+        let security = self
+            .system(system_id)
+            .map(|s| s.security_status)
+            .unwrap_or(0.0);
+        SecurityClass::classify(system_id, security)
     }
 
-    pub fn distances_from(&self, system_id: i32) -> HashMap<i32, u32> {
-        let idx = self
-            .graph
-            .node_indices()
-            .find(|n| {
-                if let Node::System { system } = self.graph[*n] {
-                    system == system_id
-                } else {
-                    false
-                }
+    pub fn tour_order(&self, systems: &[i32]) -> Result<Vec<i32>, RouteError> {
+        let distances = systems
+            .iter()
+            .map(|&from| {
+                let distances = self.distances_from(from);
+                systems
+                    .iter()
+                    .map(|&to| match distances.get(&to) {
+                        Some(distance) => Ok(*distance),
+                        None if from == to => Ok(0),
+                        None => Err(RouteError::NoRoute(from, to)),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
             })
-            .unwrap();
-
-        let distances = petgraph::algo::dijkstra(&self.graph, idx, None, |e| match e.weight() {
-            Edge::JumpBridge { .. } | Edge::Jump { .. } | Edge::Wormhole { .. } => 1,
-            _ => 0,
-        });
+            .collect::<Result<Vec<_>, _>>()?;
 
-        distances
+        Ok(shortest_tour(&distances)
             .into_iter()
-            .filter_map(|(k, distance)| match self.graph[k] {
-                Node::System { system } => Some((system, distance)),
-                _ => None,
-            })
-            .collect()
+            .map(|idx| systems[idx])
+            .collect())
     }
 
-    pub fn clear_route(&mut self) {
-        self.route_target = None;
-        self.route_nodes.clear();
-        self.route.clear();
-    }
-
-    pub fn create_route(&mut self, from: i32, to: i32) {
-        let route_target = Some((from, to));
-        if self.route_target == route_target {
-            return;
+    fn node_system(
+        graph: &Graph<Node, Edge, petgraph::Undirected, u32>,
+        node: petgraph::graph::NodeIndex,
+    ) -> i32 {
+        match graph[node] {
+            Node::System { system } => system,
+            Node::Stargate { source, .. }
+            | Node::JumpGate { source, .. }
+            | Node::Wormhole { source, .. } => source,
         }
+    }
 
-        self.route_target = route_target;
-
-        let from = self
-            .graph
-            .node_indices()
-            .find(|s| match self.graph[*s] {
-                Node::System { system } if system == from => true,
-                _ => false,
-            })
-            .unwrap();
-
-        let route = petgraph::algo::astar(
-            &self.graph,
+    fn search_route<F: Fn(&Edge, i32) -> f64>(
+        graph: &Graph<Node, Edge, petgraph::Undirected, u32>,
+        from: petgraph::graph::NodeIndex,
+        to: i32,
+        avoided: &HashSet<i32>,
+        jump_cost: F,
+    ) -> Option<(f64, Vec<petgraph::graph::NodeIndex>)> {
+        petgraph::algo::astar(
+            graph,
             from,
-            |id| {
-                let node_id = self.graph[id];
-                match node_id {
-                    Node::System { system } if system == to => true,
-                    _ => false,
+            |id| matches!(graph[id], Node::System { system } if system == to),
+            |e| {
+                let edge = e.weight();
+                match edge {
+                    Edge::Jump { .. } | Edge::JumpBridge { .. } | Edge::Wormhole { .. } => {
+                        let destination = Self::node_system(graph, e.target());
+                        if avoided.contains(&destination) {
+                            f64::INFINITY
+                        } else {
+                            jump_cost(edge, destination)
+                        }
+                    }
+                    _ => edge.distance(),
                 }
             },
-            |e| e.weight().distance(),
             |_e| 0.0,
-        );
+        )
+        .filter(|(cost, _)| cost.is_finite())
+    }
 
-        if let Some(route) = route {
-            let mut route_systems = Vec::new();
-            let mut route_nodes = Vec::new();
-
-            let mut visited = HashSet::new();
-            let mut arrive_gate = None;
-            for gate in route.1 {
-                let node = self.graph[gate];
-                match node {
-                    Node::JumpGate {
-                        stargate,
-                        source,
-                        destination,
-                    }
-                    | Node::Stargate {
-                        stargate,
-                        source,
-                        destination,
-                    } => {
-                        let gate = self.stargates.get(&stargate).unwrap();
-                        visited.insert(source);
-                        if !visited.contains(&destination) {
-                            let source = self.system(source).unwrap();
-                            let dest = self.system(destination).unwrap();
-                            let source_const = self.constellation(source.constellation_id);
-                            let dest_const = self.constellation(dest.constellation_id);
-
-                            route_systems.push(source.system_id);
-                            let leave_gate = match node {
-                                Node::JumpGate { .. } => Some(JumpType::JumpGate),
-                                Node::Stargate { .. } => {
-                                    if source.constellation_id == dest.constellation_id {
-                                        Some(JumpType::System)
-                                    } else if source_const.map(|c| c.region_id)
-                                        == dest_const.map(|c| c.region_id)
-                                    {
-                                        Some(JumpType::Constellation)
-                                    } else {
-                                        Some(JumpType::Region)
-                                    }
+    pub fn route(
+        &self,
+        from: i32,
+        to: i32,
+        preference: RoutePreference,
+    ) -> Result<Vec<RouteNode>, RouteError> {
+        self.find_route(from, to, preference, &HashSet::new(), |_| 0.0)
+    }
+
+    pub fn find_route<F: Fn(i32) -> f64>(
+        &self,
+        from: i32,
+        to: i32,
+        preference: RoutePreference,
+        avoided: &HashSet<i32>,
+        jump_penalty: F,
+    ) -> Result<Vec<RouteNode>, RouteError> {
+        let source = from;
+        let from = *self
+            .system_nodes
+            .get(&from)
+            .ok_or(RouteError::UnknownSystem(from))?;
+        if !self.system_nodes.contains_key(&to) {
+            return Err(RouteError::UnknownSystem(to));
+        }
+
+        let route = Self::search_route(&self.graph, from, to, avoided, |edge, destination| {
+            if self.is_excluded(edge) {
+                return f64::INFINITY;
+            }
+
+            let cost = edge.distance() + jump_penalty(destination);
+            cost + preference.security_penalty(self.security_class(destination))
+        });
+
+        let route = route.ok_or(RouteError::NoRoute(source, to))?;
+        let mut route_nodes = Vec::new();
+
+        let mut visited = HashSet::new();
+        let mut arrive_gate = None;
+        for gate in route.1 {
+            let node = self.graph[gate];
+            match node {
+                Node::JumpGate {
+                    stargate,
+                    source,
+                    destination,
+                }
+                | Node::Wormhole {
+                    stargate,
+                    source,
+                    destination,
+                }
+                | Node::Stargate {
+                    stargate,
+                    source,
+                    destination,
+                } => {
+                    let gate = self.stargates.get(&stargate).unwrap();
+                    visited.insert(source);
+                    if !visited.contains(&destination) {
+                        let source = self.system(source).unwrap();
+                        let dest = self.system(destination).unwrap();
+                        let source_const = self.constellation(source.constellation_id);
+                        let dest_const = self.constellation(dest.constellation_id);
+
+                        let leave_gate = match node {
+                            Node::JumpGate { .. } => Some(JumpType::JumpGate),
+                            Node::Wormhole { .. } => Some(JumpType::Wormhole),
+                            Node::Stargate { .. } => {
+                                if source.constellation_id == dest.constellation_id {
+                                    Some(JumpType::System)
+                                } else if source_const.map(|c| c.region_id)
+                                    == dest_const.map(|c| c.region_id)
+                                {
+                                    Some(JumpType::Constellation)
+                                } else {
+                                    Some(JumpType::Region)
                                 }
-                                _ => None,
-                            };
+                            }
+                            _ => None,
+                        };
 
-                            route_nodes.push(RouteNode {
-                                system_id: gate.system_id,
-                                arrive_jump: arrive_gate,
-                                leave_jump: leave_gate,
-                            });
+                        route_nodes.push(RouteNode {
+                            system_id: gate.system_id,
+                            arrive_jump: arrive_gate,
+                            leave_jump: leave_gate,
+                        });
 
-                            arrive_gate = leave_gate;
-                        }
+                        arrive_gate = leave_gate;
                     }
-                    Node::System { .. } => (),
                 }
+                Node::System { .. } => (),
             }
-            route_nodes.push(RouteNode {
-                system_id: to,
-                arrive_jump: arrive_gate,
-                leave_jump: None,
-            });
-            route_systems.push(to);
-
-            self.route = route_systems;
-            self.route_nodes = route_nodes;
         }
-    }
-
-    pub fn is_on_route(&self, system_id: i32) -> bool {
-        self.route.iter().any(|&r| r == system_id)
-    }
-
-    pub fn route_nodes(&self) -> &[RouteNode] {
-        self.route_nodes.as_slice()
-    }
+        route_nodes.push(RouteNode {
+            system_id: to,
+            arrive_jump: arrive_gate,
+            leave_jump: None,
+        });
 
-    pub fn route_target(&self) -> Option<(i32, i32)> {
-        self.route_target
+        Ok(route_nodes)
     }
 
-    pub fn send_route_to_client(&self) {
-        let route = self.route.clone();
-        let player_location = self.location();
-
-        if let Some(sender) = self.update_sender.as_ref() {
-            let _ = sender.unbounded_send(UpdateRequest::SendRouteToClient(player_location, route));
+    pub fn find_waypoint_route<F: Fn(i32) -> f64>(
+        &self,
+        waypoints: &[i32],
+        preference: RoutePreference,
+        avoided: &HashSet<i32>,
+        jump_penalty: F,
+    ) -> Result<Vec<RouteNode>, RouteError> {
+        let mut nodes: Vec<RouteNode> = Vec::new();
+        for leg in waypoints.windows(2) {
+            let mut leg_nodes =
+                self.find_route(leg[0], leg[1], preference, avoided, &jump_penalty)?;
+            if let Some(waypoint) = nodes.pop() {
+                leg_nodes[0].arrive_jump = waypoint.arrive_jump;
+            }
+            nodes.extend(leg_nodes);
         }
+
+        Ok(nodes)
     }
 
     pub fn jumps(&self) -> Vec<Jump> {
@@ -358,31 +857,11 @@ impl World {
                         let left_sys = self.system(*left).unwrap();
                         let right_sys = self.system(*right).unwrap();
 
-                        let jump_type = if left_sys.constellation_id != right_sys.constellation_id {
-                            let left_constellation =
-                                self.constellations.get(&left_sys.constellation_id);
-                            let right_constellation =
-                                self.constellations.get(&right_sys.constellation_id);
-
-                            if let (Some(left_constellation), Some(right_constellation)) =
-                                (left_constellation, right_constellation)
-                            {
-                                if left_constellation.region_id != right_constellation.region_id {
-                                    JumpType::Region
-                                } else {
-                                    JumpType::Constellation
-                                }
-                            } else {
-                                JumpType::Constellation
-                            }
-                        } else {
-                            JumpType::System
-                        };
-
                         Some(Jump {
                             left_system_id: left_sys.system_id,
                             right_system_id: right_sys.system_id,
-                            jump_type,
+                            jump_type: self.gate_jump_type(left_sys, right_sys),
+                            restricted: false,
                         })
                     }
                     Edge::JumpBridge { left, right } => {
@@ -392,6 +871,7 @@ impl World {
                             left_system_id: left_sys.system_id,
                             right_system_id: right_sys.system_id,
                             jump_type: JumpType::JumpGate,
+                            restricted: !self.bridge_allowed(*left, *right),
                         })
                     }
                     Edge::Wormhole { system, wormhole } => {
@@ -400,7 +880,8 @@ impl World {
                         Some(Jump {
                             left_system_id: left_sys.system_id,
                             right_system_id: right_sys.system_id,
-                            jump_type: JumpType::JumpGate,
+                            jump_type: JumpType::Wormhole,
+                            restricted: false,
                         })
                     }
                     _ => None,
@@ -409,251 +890,1271 @@ impl World {
             .collect()
     }
 
-    pub async fn load_sov_standings(
-        sov_standings: &Arc<RwLock<HashMap<i32, Sov>>>,
-        alliances: &Arc<RwLock<HashMap<i32, esi::GetAlliance>>>,
-        corporations: &Arc<RwLock<HashMap<i32, esi::GetCorporation>>>,
-        client: &esi::Client,
-    ) {
-        let character = client.get_character_self().await.unwrap();
-
-        let alliance_standings = Arc::new(RwLockAsync::new(HashMap::new()));
-        let corporation_standings = Arc::new(RwLockAsync::new(HashMap::new()));
+    fn gate_jump_type(
+        &self,
+        left: &esi::GetUniverseSystem,
+        right: &esi::GetUniverseSystem,
+    ) -> JumpType {
+        if left.constellation_id == right.constellation_id {
+            return JumpType::System;
+        }
 
-        let update_alliance_standings = async {
-            if let Some(alliance_id) = character.alliance_id {
-                let mut page = 1;
-                loop {
-                    let standings = client
-                        .get_alliance_contacts(alliance_id, page)
-                        .await
-                        .unwrap();
+        let left_constellation = self.constellations.get(&left.constellation_id);
+        let right_constellation = self.constellations.get(&right.constellation_id);
 
-                    if standings.contacts.len() == 0 {
-                        break;
-                    }
+        match (left_constellation, right_constellation) {
+            (Some(left), Some(right)) if left.region_id != right.region_id => JumpType::Region,
+            _ => JumpType::Constellation,
+        }
+    }
 
-                    for standing in standings.contacts {
-                        match standing.contact_type.as_str() {
-                            "corporation" => {
-                                corporation_standings
-                                    .write()
-                                    .await
-                                    .insert(standing.contact_id, standing.standing);
-                            }
-                            "alliance" => {
-                                alliance_standings
-                                    .write()
-                                    .await
-                                    .insert(standing.contact_id, standing.standing);
-                            }
-                            _ => (),
-                        }
-                    }
+    pub fn set_excluded_jumps(&mut self, excluded: HashSet<JumpType>) {
+        if excluded != self.excluded_jumps {
+            self.excluded_jumps = excluded;
+            self.distance_cache.borrow_mut().clear();
+        }
+    }
 
-                    if let Some(total_pages) = standings.pages {
-                        if total_pages <= page {
-                            break;
-                        }
-                    }
+    pub fn set_access_ids(&mut self, ids: Vec<i32>) -> bool {
+        if ids == self.access_ids {
+            return false;
+        }
 
-                    page += 1;
-                }
-            }
-        };
+        self.access_ids = ids;
+        self.distance_cache.borrow_mut().clear();
+        true
+    }
 
-        let update_corporation_standings = async {
-            let mut page = 1;
-            loop {
-                let standings = client
-                    .get_corporation_contacts(character.corporation_id, page)
-                    .await
-                    .unwrap();
+    fn restrict_bridge(&mut self, left: i32, right: i32, allowed: Vec<i32>) {
+        self.bridge_access
+            .insert((left.min(right), left.max(right)), allowed);
+    }
 
-                if standings.contacts.len() == 0 {
-                    break;
-                }
+    fn bridge_allowed(&self, left: i32, right: i32) -> bool {
+        if self.access_ids.is_empty() {
+            return true;
+        }
 
-                for standing in standings.contacts {
-                    match standing.contact_type.as_str() {
-                        "corporation" => {
-                            corporation_standings
-                                .write()
-                                .await
-                                .insert(standing.contact_id, standing.standing);
-                        }
-                        "alliance" => {
-                            alliance_standings
-                                .write()
-                                .await
-                                .insert(standing.contact_id, standing.standing);
-                        }
-                        _ => (),
-                    }
-                }
+        match self.bridge_access.get(&(left.min(right), left.max(right))) {
+            Some(allowed) => self.access_ids.iter().any(|id| allowed.contains(id)),
+            None => true,
+        }
+    }
 
-                if let Some(total_pages) = standings.pages {
-                    if total_pages <= page {
-                        break;
-                    }
-                }
-                page += 1;
+    fn is_excluded(&self, edge: &Edge) -> bool {
+        if let Edge::JumpBridge { left, right } = *edge {
+            if !self.bridge_allowed(left, right) {
+                return true;
             }
-        };
+        }
 
-        let update_character_standings = async {
-            let mut page = 1;
-            loop {
-                let standings = client.get_character_contacts(page).await.unwrap();
+        if self.excluded_jumps.is_empty() {
+            return false;
+        }
 
-                if standings.contacts.len() == 0 {
-                    break;
-                }
+        let jump_type = match *edge {
+            Edge::Jump { left, right } => match (self.system(left), self.system(right)) {
+                (Some(left), Some(right)) => self.gate_jump_type(left, right),
+                _ => return false,
+            },
+            Edge::JumpBridge { .. } => JumpType::JumpGate,
+            Edge::Wormhole { .. } => JumpType::Wormhole,
+            Edge::Warp { .. } => return false,
+        };
 
-                for standing in standings.contacts {
-                    match standing.contact_type.as_str() {
-                        "corporation" => {
-                            corporation_standings
-                                .write()
-                                .await
-                                .insert(standing.contact_id, standing.standing);
-                        }
-                        "alliance" => {
-                            alliance_standings
-                                .write()
-                                .await
-                                .insert(standing.contact_id, standing.standing);
-                        }
-                        _ => (),
-                    }
-                }
+        self.excluded_jumps.contains(&jump_type)
+    }
 
-                if let Some(total_pages) = standings.pages {
-                    if total_pages <= page {
-                        break;
-                    }
-                }
+    fn system_adjacency(&self, stargates_only: bool) -> HashMap<i32, Vec<i32>> {
+        let mut adjacency: HashMap<i32, Vec<i32>> = HashMap::new();
+        for edge in self.graph.edge_references() {
+            let (left, right) = match *edge.weight() {
+                Edge::Jump { left, right } => (left, right),
+                Edge::JumpBridge { left, right } if !stargates_only => (left, right),
+                Edge::Wormhole { system, wormhole } if !stargates_only => (system, wormhole),
+                _ => continue,
+            };
 
-                page += 1;
-            }
-        };
+            adjacency.entry(left).or_default().push(right);
+            adjacency.entry(right).or_default().push(left);
+        }
 
-        let (sov_map, _, _, _) = futures::join!(
-            client.get_sovereignty_map().map(Result::unwrap),
-            update_alliance_standings,
-            update_corporation_standings,
-            update_character_standings
-        );
+        for neighbors in adjacency.values_mut() {
+            neighbors.sort_unstable();
+            neighbors.dedup();
+        }
 
-        {
-            let mut sov = sov_standings.write().unwrap();
-            sov.clear();
+        adjacency
+    }
+
+    pub fn route_text(&self, nodes: &[RouteNode]) -> String {
+        if nodes.is_empty() {
+            return String::new();
         }
 
-        let mut alliance_ids = Vec::new();
-        let mut corporation_ids = Vec::new();
+        let name = |system_id| {
+            self.system(system_id)
+                .map(|s| format!("{} ({:.2})", s.name, s.security_status))
+                .unwrap_or_else(|| format!("Unknown ({})", system_id))
+        };
 
-        for system in sov_map {
-            let alliance = if let Some(alliance_id) = system.alliance_id {
-                alliance_ids.push(alliance_id);
-                alliance_standings.read().await.get(&alliance_id).cloned()
-            } else {
-                None
-            };
-            let corporation = if let Some(corporation_id) = system.corporation_id {
-                corporation_ids.push(corporation_id);
-                corporation_standings
-                    .read()
-                    .await
-                    .get(&corporation_id)
-                    .cloned()
-            } else {
-                None
-            };
+        let jumps = nodes.len() - 1;
+        let mut text = if jumps == 1 {
+            String::from("1 jump\n")
+        } else {
+            format!("{} jumps\n", jumps)
+        };
 
-            if let Some(standing) = alliance.or(corporation) {
-                let mut sov = sov_standings.write().unwrap();
-                sov.insert(
-                    system.system_id,
-                    Sov {
-                        alliance_id: system.alliance_id,
-                        corporation_id: system.corporation_id,
-                        standing,
-                    },
-                );
-            } else if system.alliance_id.is_some() || system.corporation_id.is_some() {
-                let mut sov = sov_standings.write().unwrap();
-                sov.insert(
-                    system.system_id,
-                    Sov {
-                        alliance_id: system.alliance_id,
-                        corporation_id: system.corporation_id,
-                        standing: 0.0,
-                    },
-                );
+        for (index, node) in nodes.iter().enumerate() {
+            text.push_str(&name(node.system_id));
+            if let (Some(next), Some(jump)) = (nodes.get(index + 1), node.leave_jump) {
+                let next = self.system(next.system_id);
+                let next = next.map(|s| s.name.as_str()).unwrap_or("Unknown");
+                text.push_str(&format!(" » {} [{}]", next, jump.label()));
             }
+            text.push('\n');
         }
 
-        let alliances_fut: FuturesUnordered<_> = alliance_ids
+        text
+    }
+
+    pub fn match_system(&self, search: &str) -> Vec<i32> {
+        GalaxyGraph::search_name_index(&self.system_name_index, search)
+    }
+
+    fn build_name_index(systems_by_name: &HashMap<String, i32>) -> Vec<(String, i32)> {
+        let mut index: Vec<_> = systems_by_name
             .iter()
-            .map(|alliance_id| client.get_alliance(*alliance_id))
+            .map(|(name, id)| (name.trim().to_uppercase(), *id))
             .collect();
+        index.sort();
+        index
+    }
 
-        let corporations_fut: FuturesUnordered<_> = corporation_ids
+    fn search_name_index(index: &[(String, i32)], search: &str) -> Vec<i32> {
+        let search = search.trim().to_uppercase();
+        let start = index.partition_point(|(name, _)| name.as_str() < search.as_str());
+
+        let mut matches: Vec<_> = index[start..]
             .iter()
-            .map(|corporation_id| client.get_corporation(*corporation_id))
+            .take_while(|(name, _)| name.starts_with(&search))
             .collect();
+        matches.sort_by_key(|(name, id)| (*name != search, name.len(), name, *id));
 
-        let (alliance_res, corporation_res): (Vec<_>, Vec<_>) = futures::join!(
-            alliances_fut.map(Result::unwrap).collect(),
-            corporations_fut.map(Result::unwrap).collect()
-        );
+        matches
+            .into_iter()
+            .take(MAX_SYSTEM_MATCHES)
+            .map(|(_, id)| *id)
+            .collect()
+    }
+}
 
-        {
-            let mut alls = alliances.write().unwrap();
-            for alliance in alliance_res {
-                alls.insert(alliance.alliance_id, alliance);
-            }
-        }
+impl Default for GalaxyGraph {
+    fn default() -> Self {
+        GalaxyGraph::new()
+    }
+}
 
-        {
-            let mut corps = corporations.write().unwrap();
-            for corporation in corporation_res {
-                corps.insert(corporation.corporation_id, corporation);
-            }
+pub struct World {
+    galaxy: GalaxyGraph,
+    chokepoints: RefCell<Option<Arc<HashSet<i32>>>>,
+    routes: Vec<Route>,
+    pockets: Vec<(i32, HashSet<i32>)>,
+    kill_penalty: f64,
+    route_preference: RoutePreference,
+    avoided_systems: HashSet<i32>,
+    trade_hubs: Vec<i32>,
+    game_log_dir: Option<String>,
+    system_metrics_path: Option<String>,
+    system_metrics: Arc<RwLock<HashMap<i32, f64>>>,
+    system_stats: Arc<RwLock<HashMap<i32, Stats>>>,
+    incursions: Arc<RwLock<Vec<esi::GetIncursion>>>,
+    fw_systems: Arc<RwLock<HashMap<i32, esi::GetFactionWarfareSystem>>>,
+    player_system: Arc<RwLock<Option<i32>>>,
+    player_affiliation: Arc<RwLock<Option<Affiliation>>>,
+    docked_location: Arc<RwLock<Option<String>>>,
+    sov: Arc<RwLock<HashMap<i32, Sov>>>,
+    alliances: Arc<RwLock<HashMap<i32, esi::GetAlliance>>>,
+    corporations: Arc<RwLock<HashMap<i32, esi::GetCorporation>>>,
+    alliance_logos: Arc<RwLock<HashMap<i32, Arc<Vec<u8>>>>>,
+    event_sender: EventSender,
+    update_sender: Option<UnboundedSender<UpdateRequest>>,
+    refresh_requested: Arc<AtomicBool>,
+    profiles: Arc<RwLock<Vec<oauth::Character>>>,
+    profile_switched: Arc<AtomicBool>,
+    client: Option<esi::Client>,
+}
+
+impl World {
+    pub fn new(event_sender: EventSender) -> Self {
+        World {
+            galaxy: GalaxyGraph::new(),
+            chokepoints: RefCell::new(None),
+            routes: Vec::new(),
+            pockets: Vec::new(),
+            kill_penalty: 0.0,
+            route_preference: RoutePreference::Shortest,
+            avoided_systems: HashSet::new(),
+            trade_hubs: DEFAULT_TRADE_HUBS.to_vec(),
+            game_log_dir: None,
+            system_metrics_path: None,
+            system_metrics: Arc::new(RwLock::new(HashMap::new())),
+            system_stats: Arc::new(RwLock::new(HashMap::new())),
+            incursions: Arc::new(RwLock::new(Vec::new())),
+            fw_systems: Arc::new(RwLock::new(HashMap::new())),
+            player_system: Arc::new(RwLock::new(None)),
+            player_affiliation: Arc::new(RwLock::new(None)),
+            docked_location: Arc::new(RwLock::new(None)),
+            sov: Arc::new(RwLock::new(HashMap::new())),
+            alliances: Arc::new(RwLock::new(HashMap::new())),
+            corporations: Arc::new(RwLock::new(HashMap::new())),
+            alliance_logos: Arc::new(RwLock::new(HashMap::new())),
+            event_sender,
+            update_sender: None,
+            refresh_requested: Arc::new(AtomicBool::new(false)),
+            profiles: Arc::new(RwLock::new(Vec::new())),
+            profile_switched: Arc::new(AtomicBool::new(false)),
+            client: None,
         }
     }
 
-    pub async fn load_system_stats(
-        system_stats: &Arc<RwLock<HashMap<i32, Stats>>>,
-        client: &esi::Client,
-    ) {
-        let (system_kills, system_jumps) = futures::join!(
-            client.get_universe_system_kills().map(Result::unwrap),
-            client.get_universe_system_jumps().map(Result::unwrap)
-        );
+    pub fn systems(&self) -> impl Iterator<Item = &esi::GetUniverseSystem> {
+        self.galaxy.systems()
+    }
 
-        let mut stats = system_stats.write().unwrap();
-        for sys in system_jumps {
-            if let Some(stat) = stats.get_mut(&sys.system_id) {
-                stat.jumps = sys.ship_jumps;
-            }
-        }
+    pub fn system(&self, system_id: i32) -> Option<&esi::GetUniverseSystem> {
+        self.galaxy.system(system_id)
+    }
 
-        for sys in system_kills {
-            if let Some(stat) = stats.get_mut(&sys.system_id) {
-                stat.npc_kills = sys.npc_kills;
-                stat.ship_kills = sys.ship_kills;
-                stat.pod_kills = sys.pod_kills;
-            }
-        }
+    pub fn system_by_name(&self, name: &str) -> Option<&esi::GetUniverseSystem> {
+        self.galaxy.system_by_name(name)
     }
 
-    pub fn import(&mut self, galaxy: Galaxy) {
-        for system_id in galaxy.systems.keys() {
-            {
-                let mut stats = self.system_stats.write().unwrap();
-                stats.insert(
+    pub fn regions(&self) -> impl Iterator<Item = &esi::GetUniverseRegion> {
+        self.galaxy.regions()
+    }
+
+    pub fn region(&self, region_id: i32) -> Option<&esi::GetUniverseRegion> {
+        self.galaxy.region(region_id)
+    }
+
+    pub fn region_by_name(&self, name: &str) -> Option<&esi::GetUniverseRegion> {
+        let name = name.trim();
+        self.regions().find(|r| r.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn constellation_by_name(&self, name: &str) -> Option<&esi::GetUniverseConstellation> {
+        let name = name.trim();
+        self.constellations()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn systems_in_constellation(&self, constellation_id: i32) -> Vec<i32> {
+        self.constellation(constellation_id)
+            .and_then(|c| c.systems.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn systems_in_region(&self, region_id: i32) -> Vec<i32> {
+        self.region(region_id)
+            .and_then(|r| r.constellations.as_ref())
+            .into_iter()
+            .flatten()
+            .flat_map(|c| self.systems_in_constellation(*c))
+            .collect()
+    }
+
+    pub fn constellations(&self) -> impl Iterator<Item = &esi::GetUniverseConstellation> {
+        self.galaxy.constellations()
+    }
+
+    pub fn constellation(&self, constellation_id: i32) -> Option<&esi::GetUniverseConstellation> {
+        self.galaxy.constellation(constellation_id)
+    }
+
+    pub fn is_known_space(&self, system_id: i32) -> bool {
+        self.galaxy.is_known_space(system_id)
+    }
+
+    pub fn alliance(&self, alliance_id: i32) -> Option<esi::GetAlliance> {
+        self.alliances.read().unwrap().get(&alliance_id).cloned()
+    }
+
+    pub fn corporation(&self, corporation_id: i32) -> Option<esi::GetCorporation> {
+        self.corporations
+            .read()
+            .unwrap()
+            .get(&corporation_id)
+            .cloned()
+    }
+
+    pub fn alliance_logo(&self, alliance_id: i32) -> Option<Arc<Vec<u8>>> {
+        let logo = self
+            .alliance_logos
+            .read()
+            .unwrap()
+            .get(&alliance_id)
+            .cloned();
+        if logo.is_some() {
+            logo
+        } else {
+            if let Some(sender) = self.update_sender.as_ref() {
+                let _ = sender.unbounded_send(UpdateRequest::AllianceLogo(alliance_id));
+            }
+            None
+        }
+    }
+
+    pub fn warm_region(&self, region_id: i32) {
+        let region = match self.region(region_id) {
+            Some(region) => region,
+            None => return,
+        };
+
+        let sov = self.sov.read().unwrap();
+        let mut alliance_ids = HashSet::new();
+        let mut corporation_ids = HashSet::new();
+        for system in self.systems() {
+            let in_region = self
+                .constellation(system.constellation_id)
+                .map(|c| c.region_id == region_id)
+                .unwrap_or(false);
+            if !in_region {
+                continue;
+            }
+
+            if let Some(sov) = sov.get(&system.system_id) {
+                alliance_ids.extend(sov.alliance_id);
+                corporation_ids.extend(sov.corporation_id);
+            }
+        }
+
+        let alliance_logos = self.alliance_logos.read().unwrap();
+        let alliances = self.alliances.read().unwrap();
+        let alliance_ids: Vec<_> = alliance_ids
+            .into_iter()
+            .filter(|a| !alliance_logos.contains_key(a) || !alliances.contains_key(a))
+            .collect();
+        let corporations = self.corporations.read().unwrap();
+        let corporation_ids: Vec<_> = corporation_ids
+            .into_iter()
+            .filter(|c| !corporations.contains_key(c))
+            .collect();
+
+        log::info!(
+            "warming {}: {} alliances, {} corporations",
+            region.name,
+            alliance_ids.len(),
+            corporation_ids.len()
+        );
+
+        if let Some(sender) = self.update_sender.as_ref() {
+            let _ = sender.unbounded_send(UpdateRequest::WarmCache(alliance_ids, corporation_ids));
+        }
+    }
+
+    pub fn request_refresh(&self) {
+        self.refresh_requested.store(true, Ordering::Relaxed);
+    }
+
+    pub fn request_profiles(&self) {
+        if let Some(sender) = self.update_sender.as_ref() {
+            let _ = sender.unbounded_send(UpdateRequest::ListProfiles);
+        }
+    }
+
+    pub fn profiles(&self) -> Vec<oauth::Character> {
+        self.profiles.read().unwrap().clone()
+    }
+
+    pub fn save_cache(&self) {
+        if let Some(client) = self.client.as_ref() {
+            if let Err(error) = block_on(client.save_cache()) {
+                log::error!("unable to save cache: {:?}", error);
+            }
+        }
+    }
+
+    pub fn clear_cache(&self) {
+        if let Some(sender) = self.update_sender.as_ref() {
+            let _ = sender.unbounded_send(UpdateRequest::ClearCache);
+        }
+    }
+
+    pub fn logout(&self) {
+        if let Some(sender) = self.update_sender.as_ref() {
+            let _ = sender.unbounded_send(UpdateRequest::Logout);
+        }
+    }
+
+    pub fn switch_profile(&self, character_id: i32) {
+        if let Some(sender) = self.update_sender.as_ref() {
+            let _ = sender.unbounded_send(UpdateRequest::SwitchProfile(character_id));
+        }
+    }
+
+    pub fn stats(&self, system_id: i32) -> Option<Stats> {
+        let stats = self.system_stats.read().unwrap();
+        stats.get(&system_id).cloned()
+    }
+
+    pub fn incursions(&self) -> Vec<esi::GetIncursion> {
+        self.incursions.read().unwrap().clone()
+    }
+
+    pub fn fw_system(&self, system_id: i32) -> Option<esi::GetFactionWarfareSystem> {
+        self.fw_systems.read().unwrap().get(&system_id).cloned()
+    }
+
+    pub fn focus_summary(&self, systems: &HashSet<i32>) -> Option<FocusSummary> {
+        let (position, security, count) = systems
+            .iter()
+            .filter_map(|s| self.system(*s))
+            .map(|s| {
+                let position = math::v3(s.position.x, s.position.y, s.position.z);
+                (position, s.security_status)
+            })
+            .fold(
+                (math::V3::fill(0.0), 0.0, 0),
+                |acc, (position, security)| (acc.0 + position, acc.1 + security, acc.2 + 1),
+            );
+
+        if count == 0 {
+            return None;
+        }
+
+        let total_jumps = systems
+            .iter()
+            .filter_map(|s| self.stats(*s))
+            .map(|s| s.jumps)
+            .sum();
+
+        Some(FocusSummary {
+            position: position / (count as f64),
+            system_count: count,
+            average_security: security / (count as f64),
+            total_jumps,
+        })
+    }
+
+    pub fn distances_from(&self, system_id: i32) -> Arc<HashMap<i32, u32>> {
+        self.galaxy.distances_from(system_id)
+    }
+
+    pub fn security_class(&self, system_id: i32) -> SecurityClass {
+        self.galaxy.security_class(system_id)
+    }
+
+    pub fn jump_distance(&self, from: i32, to: i32) -> Option<u32> {
+        self.distances_from(from).get(&to).cloned()
+    }
+
+    pub fn light_year_distance(&self, from: i32, to: i32) -> Option<f64> {
+        let (from, to) = (self.system(from)?, self.system(to)?);
+        let from = math::v3(from.position.x, from.position.y, from.position.z);
+        let to = math::v3(to.position.x, to.position.y, to.position.z);
+
+        Some(from.distance_squared(&to).sqrt() / METERS_PER_LIGHT_YEAR)
+    }
+
+    pub fn light_years_from(&self, system_id: i32) -> HashMap<i32, f64> {
+        let source = match self.system(system_id) {
+            Some(system) => math::v3(system.position.x, system.position.y, system.position.z),
+            None => return HashMap::new(),
+        };
+
+        self.systems()
+            .map(|s| {
+                let position = math::v3(s.position.x, s.position.y, s.position.z);
+                let distance = position.distance_squared(&source).sqrt();
+                (s.system_id, distance / METERS_PER_LIGHT_YEAR)
+            })
+            .collect()
+    }
+
+    pub fn systems_within_range(&self, system_id: i32, light_years: f64) -> Vec<i32> {
+        let source = match self.system(system_id) {
+            Some(system) => math::v3(system.position.x, system.position.y, system.position.z),
+            None => return Vec::new(),
+        };
+
+        let max_distance = light_years * METERS_PER_LIGHT_YEAR;
+        self.systems()
+            .filter(|s| s.system_id != system_id)
+            .filter(|s| {
+                let position = math::v3(s.position.x, s.position.y, s.position.z);
+                position.distance(&source) <= max_distance
+            })
+            .map(|s| s.system_id)
+            .collect()
+    }
+
+    pub fn clear_routes(&mut self) {
+        self.routes.clear();
+    }
+
+    pub fn clear_arrived_routes(&mut self, location: i32) -> bool {
+        let route_count = self.routes.len();
+        self.routes.retain(|r| {
+            if r.target().1 == location {
+                log::info!("arrived at destination of route: {}", r.name);
+                false
+            } else {
+                true
+            }
+        });
+        self.routes.len() != route_count
+    }
+
+    pub fn set_kill_penalty(&mut self, kill_penalty: f64) {
+        self.kill_penalty = kill_penalty;
+    }
+
+    pub fn set_trade_hubs(&mut self, trade_hubs: Vec<i32>) {
+        self.trade_hubs = trade_hubs;
+    }
+
+    pub fn nearest_hub(&self, from: i32) -> Option<(i32, u32)> {
+        self.galaxy.nearest_system(from, &self.trade_hubs)
+    }
+
+    pub fn kill_penalty(&self) -> f64 {
+        self.kill_penalty
+    }
+
+    pub fn set_game_log_dir(&mut self, game_log_dir: Option<String>) {
+        self.game_log_dir = game_log_dir;
+    }
+
+    pub fn set_system_metrics_path(&mut self, path: Option<String>) {
+        self.system_metrics_path = path;
+    }
+
+    pub fn system_metric(&self, system_id: i32) -> Option<f64> {
+        let metrics = self.system_metrics.read().unwrap();
+        metrics.get(&system_id).cloned()
+    }
+
+    pub fn system_metric_range(&self) -> Option<(f64, f64)> {
+        let metrics = self.system_metrics.read().unwrap();
+        metrics.values().fold(None, |range, value| match range {
+            Some((min, max)) => Some((value.min(min), value.max(max))),
+            None => Some((*value, *value)),
+        })
+    }
+
+    pub fn set_route_preference(&mut self, preference: RoutePreference) {
+        self.route_preference = preference;
+    }
+
+    pub fn route_preference(&self) -> RoutePreference {
+        self.route_preference
+    }
+
+    pub fn set_excluded_jumps(&mut self, excluded: HashSet<JumpType>) {
+        self.galaxy.set_excluded_jumps(excluded);
+        self.refresh_routes();
+    }
+
+    pub fn refresh_bridge_access(&mut self) -> bool {
+        let affiliation = *self.player_affiliation.read().unwrap();
+        let access_ids = affiliation
+            .map(|a| a.alliance_id.into_iter().chain(a.corporation_id).collect())
+            .unwrap_or_default();
+
+        if self.galaxy.set_access_ids(access_ids) {
+            self.refresh_routes();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_avoided_systems(&mut self, ids: HashSet<i32>) {
+        self.avoided_systems = ids;
+    }
+
+    pub fn create_route(&mut self, name: &str, from: i32, to: i32) -> Result<(), RouteError> {
+        self.create_route_with_preference(name, &[from, to], self.route_preference)
+    }
+
+    pub fn create_waypoint_route(
+        &mut self,
+        name: &str,
+        waypoints: &[i32],
+    ) -> Result<(), RouteError> {
+        self.create_route_with_preference(name, waypoints, self.route_preference)
+    }
+
+    pub fn create_tour(&mut self, systems: &[i32]) -> Result<(), RouteError> {
+        if systems.len() > MAX_TOUR_SYSTEMS {
+            log::warn!(
+                "too many systems for a tour: {} (max {})",
+                systems.len(),
+                MAX_TOUR_SYSTEMS
+            );
+            return Err(RouteError::TooManySystems(systems.len()));
+        }
+
+        let mut systems = systems.to_vec();
+        systems.sort_unstable();
+        if let Some(start) = self
+            .location()
+            .and_then(|l| systems.iter().position(|s| *s == l))
+        {
+            systems.swap(0, start);
+        }
+
+        let mut waypoints = self.galaxy.tour_order(&systems)?;
+        if let Some(first) = waypoints.first().copied() {
+            waypoints.push(first);
+        }
+        self.create_route_with_preference(DEFAULT_ROUTE, &waypoints, self.route_preference)
+    }
+
+    pub fn compare_routes(&mut self, from: i32, to: i32) -> Result<(), RouteError> {
+        self.create_route_with_preference(SHORTEST_ROUTE, &[from, to], RoutePreference::Shortest)?;
+        self.create_route_with_preference(SAFER_ROUTE, &[from, to], RoutePreference::Safer)
+    }
+
+    fn create_route_with_preference(
+        &mut self,
+        name: &str,
+        waypoints: &[i32],
+        preference: RoutePreference,
+    ) -> Result<(), RouteError> {
+        if self
+            .route(name)
+            .map(|r| r.waypoints == waypoints && r.preference == preference)
+            .unwrap_or(false)
+        {
+            return Ok(());
+        }
+
+        let route = match self.find_route(name, waypoints, preference) {
+            Ok(route) => route,
+            Err(error) => {
+                log::warn!(
+                    "unable to create route through {:?}: {:?}",
+                    waypoints,
+                    error
+                );
+                return Err(error);
+            }
+        };
+
+        if let Some(existing) = self.routes.iter_mut().find(|r| r.name == name) {
+            *existing = route;
+        } else {
+            if self.routes.len() >= MAX_ROUTES {
+                let removed = self.routes.remove(0);
+                log::info!("too many routes, removing route: {}", removed.name);
+            }
+            self.routes.push(route);
+        }
+
+        Ok(())
+    }
+
+    pub fn refresh_routes(&mut self) {
+        let targets: Vec<_> = self
+            .routes
+            .iter()
+            .map(|r| (r.name.clone(), r.waypoints.clone(), r.preference))
+            .collect();
+
+        for (name, waypoints, preference) in targets {
+            match self.find_route(&name, &waypoints, preference) {
+                Ok(route) => {
+                    if let Some(existing) = self.routes.iter_mut().find(|r| r.name == name) {
+                        *existing = route;
+                    }
+                }
+                Err(error) => {
+                    let message = format!("Route failed: {}", self.route_error_message(error));
+                    self.event_sender
+                        .send_user_event(UserEvent::Notice(message, Severity::Warning));
+                }
+            }
+        }
+    }
+
+    pub fn route_error_message(&self, error: RouteError) -> String {
+        let system_name = |id| {
+            self.system(id)
+                .map(|s| s.name.clone())
+                .unwrap_or_else(|| id.to_string())
+        };
+        match error {
+            RouteError::UnknownSystem(system) => format!("Unknown system: {}", system_name(system)),
+            RouteError::NoRoute(from, to) => {
+                format!("No route from {} to {}", system_name(from), system_name(to))
+            }
+            RouteError::TooManySystems(count) => format!(
+                "Too many systems for a tour: {} (max {})",
+                count, MAX_TOUR_SYSTEMS
+            ),
+        }
+    }
+
+    pub fn route_danger(&self, route: &Route) -> Vec<(i32, Stats)> {
+        route
+            .nodes
+            .iter()
+            .filter_map(|node| Some((node.system_id, self.stats(node.system_id)?)))
+            .collect()
+    }
+
+    pub fn route_summary(&self, route: &Route) -> RouteSummary {
+        let mut summary = RouteSummary::default();
+        for node in route.nodes.iter() {
+            summary.systems += 1;
+
+            match node.arrive_jump {
+                Some(JumpType::System) | Some(JumpType::Constellation) | Some(JumpType::Region) => {
+                    summary.gates += 1
+                }
+                Some(JumpType::JumpGate) => summary.jump_bridges += 1,
+                Some(JumpType::Wormhole) => summary.wormholes += 1,
+                None => (),
+            }
+
+            if self.system(node.system_id).is_some() {
+                match self.security_class(node.system_id) {
+                    SecurityClass::High => summary.security.high += 1,
+                    SecurityClass::Low => summary.security.low += 1,
+                    SecurityClass::Null => summary.security.null += 1,
+                    SecurityClass::Wormhole => summary.security.wormhole += 1,
+                }
+            }
+        }
+        summary
+    }
+
+    fn find_route(
+        &self,
+        name: &str,
+        waypoints: &[i32],
+        preference: RoutePreference,
+    ) -> Result<Route, RouteError> {
+        let stats = self.system_stats.read().unwrap();
+        let nodes = self.galaxy.find_waypoint_route(
+            waypoints,
+            preference,
+            &self.avoided_systems,
+            |destination| {
+                if self.kill_penalty > 0.0 {
+                    let kills = stats
+                        .get(&destination)
+                        .map(|s| s.ship_kills + s.pod_kills)
+                        .unwrap_or(0);
+                    kills as f64 * self.kill_penalty * JUMP_COST
+                } else {
+                    0.0
+                }
+            },
+        )?;
+
+        Ok(Route {
+            name: name.to_string(),
+            waypoints: waypoints.to_vec(),
+            preference,
+            systems: nodes.iter().map(|n| n.system_id).collect(),
+            nodes,
+        })
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        self.routes.as_slice()
+    }
+
+    pub fn route(&self, name: &str) -> Option<&Route> {
+        self.routes.iter().find(|r| r.name == name)
+    }
+
+    pub fn route_as_text(&self) -> String {
+        self.route(DEFAULT_ROUTE)
+            .or_else(|| self.routes.first())
+            .map(|r| self.galaxy.route_text(&r.nodes))
+            .unwrap_or_default()
+    }
+
+    pub fn send_route_to_client(&self, name: &str) {
+        let route = match self.route(name) {
+            Some(route) => route.systems.clone(),
+            None => return,
+        };
+        let player_location = self.location();
+
+        if let Some(sender) = self.update_sender.as_ref() {
+            let _ = sender.unbounded_send(UpdateRequest::SendRouteToClient(player_location, route));
+        }
+    }
+
+    pub fn set_destination(&self, system_id: i32) {
+        if let Some(sender) = self.update_sender.as_ref() {
+            let _ = sender.unbounded_send(UpdateRequest::SetWaypoint(system_id));
+        }
+    }
+
+    pub fn jumps(&self) -> Vec<Jump> {
+        self.galaxy.jumps()
+    }
+
+    pub fn pockets(&self) -> &[(i32, HashSet<i32>)] {
+        self.pockets.as_slice()
+    }
+
+    pub fn chokepoints(&self) -> Arc<HashSet<i32>> {
+        if let Some(chokepoints) = self.chokepoints.borrow().as_ref() {
+            return chokepoints.clone();
+        }
+
+        let chokepoints = Arc::new(World::find_articulation_points(
+            &self.galaxy.system_adjacency(true),
+        ));
+        log::info!("found {} chokepoints", chokepoints.len());
+        self.chokepoints.replace(Some(chokepoints.clone()));
+        chokepoints
+    }
+
+    fn find_pockets(adjacency: &HashMap<i32, Vec<i32>>) -> Vec<(i32, HashSet<i32>)> {
+        let mut order = Vec::with_capacity(adjacency.len());
+        let mut discovered: HashMap<i32, usize> = HashMap::new();
+        let mut low: HashMap<i32, usize> = HashMap::new();
+        let mut pockets = Vec::new();
+
+        let mut roots: Vec<_> = adjacency.keys().cloned().collect();
+        roots.sort_unstable();
+
+        for root in roots {
+            if discovered.contains_key(&root) {
+                continue;
+            }
+
+            let component_start = order.len();
+            let mut separations = Vec::new();
+
+            discovered.insert(root, order.len());
+            low.insert(root, order.len());
+            order.push(root);
+            let mut stack = vec![(root, None, 0)];
+
+            while let Some(&(system, parent, index)) = stack.last() {
+                let neighbors = &adjacency[&system];
+                if let Some(&next) = neighbors.get(index) {
+                    if let Some(top) = stack.last_mut() {
+                        top.2 += 1;
+                    }
+                    if Some(next) == parent {
+                        continue;
+                    }
+                    if let Some(&next_discovered) = discovered.get(&next) {
+                        let system_low = low[&system].min(next_discovered);
+                        low.insert(system, system_low);
+                    } else {
+                        discovered.insert(next, order.len());
+                        low.insert(next, order.len());
+                        order.push(next);
+                        stack.push((next, Some(system), 0));
+                    }
+                } else {
+                    stack.pop();
+                    if let Some(parent) = parent {
+                        let parent_low = low[&parent].min(low[&system]);
+                        low.insert(parent, parent_low);
+                        if low[&system] >= discovered[&parent] {
+                            let subtree_start = discovered[&system];
+                            let subtree_size = order.len() - subtree_start;
+                            separations.push((parent, subtree_start, subtree_size));
+                        }
+                    }
+                }
+            }
+
+            let component = &order[component_start..];
+            for (gateway, subtree_start, subtree_size) in separations {
+                let remainder = component.len() - 1 - subtree_size;
+                let subtree = &order[subtree_start..subtree_start + subtree_size];
+                let pocket: HashSet<i32> = if subtree_size <= remainder {
+                    subtree.iter().cloned().collect()
+                } else if remainder > 0 {
+                    let subtree: HashSet<i32> = subtree.iter().cloned().collect();
+                    component
+                        .iter()
+                        .filter(|s| **s != gateway && !subtree.contains(s))
+                        .cloned()
+                        .collect()
+                } else {
+                    continue;
+                };
+
+                pockets.push((gateway, pocket));
+            }
+        }
+
+        pockets
+    }
+
+    fn find_articulation_points(adjacency: &HashMap<i32, Vec<i32>>) -> HashSet<i32> {
+        let mut discovered: HashMap<i32, usize> = HashMap::new();
+        let mut low: HashMap<i32, usize> = HashMap::new();
+        let mut articulation_points = HashSet::new();
+
+        let mut roots: Vec<_> = adjacency.keys().cloned().collect();
+        roots.sort_unstable();
+
+        for root in roots {
+            if discovered.contains_key(&root) {
+                continue;
+            }
+
+            let mut root_children = 0;
+            discovered.insert(root, discovered.len());
+            low.insert(root, discovered[&root]);
+            let mut stack = vec![(root, None, 0)];
+
+            while let Some(&(system, parent, index)) = stack.last() {
+                let neighbors = &adjacency[&system];
+                if let Some(&next) = neighbors.get(index) {
+                    if let Some(top) = stack.last_mut() {
+                        top.2 += 1;
+                    }
+                    if Some(next) == parent {
+                        continue;
+                    }
+                    if let Some(&next_discovered) = discovered.get(&next) {
+                        let system_low = low[&system].min(next_discovered);
+                        low.insert(system, system_low);
+                    } else {
+                        if system == root {
+                            root_children += 1;
+                        }
+                        discovered.insert(next, discovered.len());
+                        low.insert(next, discovered[&next]);
+                        stack.push((next, Some(system), 0));
+                    }
+                } else {
+                    stack.pop();
+                    if let Some(parent) = parent {
+                        let parent_low = low[&parent].min(low[&system]);
+                        low.insert(parent, parent_low);
+                        if parent != root && low[&system] >= discovered[&parent] {
+                            articulation_points.insert(parent);
+                        }
+                    }
+                }
+            }
+
+            if root_children > 1 {
+                articulation_points.insert(root);
+            }
+        }
+
+        articulation_points
+    }
+
+    fn contact_standings<T, F: Fn(T) -> (String, i32, f64)>(
+        kind: &str,
+        contacts: Result<Vec<T>, esi::Error>,
+        standing: F,
+    ) -> Option<Vec<(String, i32, f64)>> {
+        match contacts {
+            Ok(contacts) => Some(contacts.into_iter().map(standing).collect()),
+            Err(error) => {
+                log::error!("{} standings load failed: {:?}", kind, error);
+                None
+            }
+        }
+    }
+
+    pub async fn load_sov_standings(
+        sov_standings: &Arc<RwLock<HashMap<i32, Sov>>>,
+        player_affiliation: &Arc<RwLock<Option<Affiliation>>>,
+        alliances: &Arc<RwLock<HashMap<i32, esi::GetAlliance>>>,
+        corporations: &Arc<RwLock<HashMap<i32, esi::GetCorporation>>>,
+        client: &esi::Client,
+    ) {
+        let character = match client.get_character_self().await {
+            Ok(character) => Some(character),
+            Err(error) => {
+                log::error!("character lookup failed, skipping standings: {:?}", error);
+                None
+            }
+        };
+
+        if let Some(character) = character.as_ref() {
+            *player_affiliation.write().unwrap() = Some(Affiliation {
+                alliance_id: character.alliance_id,
+                corporation_id: Some(character.corporation_id),
+            });
+        }
+
+        let alliance_id = character.as_ref().and_then(|c| c.alliance_id);
+        let corporation_id = character.as_ref().map(|c| c.corporation_id);
+
+        let load_alliance_contacts = async {
+            match alliance_id {
+                Some(alliance_id) => World::contact_standings(
+                    "alliance",
+                    client.get_alliance_contacts(alliance_id).await,
+                    |c| (c.contact_type, c.contact_id, c.standing),
+                ),
+                None => Some(Vec::new()),
+            }
+        };
+
+        let load_corporation_contacts = async {
+            match corporation_id {
+                Some(corporation_id) => World::contact_standings(
+                    "corporation",
+                    client.get_corporation_contacts(corporation_id).await,
+                    |c| (c.contact_type, c.contact_id, c.standing),
+                ),
+                None => Some(Vec::new()),
+            }
+        };
+
+        let load_character_contacts = async {
+            match character {
+                Some(_) => World::contact_standings(
+                    "character",
+                    client.get_character_contacts().await,
+                    |c| (c.contact_type, c.contact_id, c.standing),
+                ),
+                None => Some(Vec::new()),
+            }
+        };
+
+        let (sov_map, alliance_contacts, corporation_contacts, character_contacts) = futures::join!(
+            client.get_sovereignty_map(),
+            load_alliance_contacts,
+            load_corporation_contacts,
+            load_character_contacts
+        );
+
+        // keep the previous standings rather than wiping them on a transient error
+        let sov_map = match sov_map {
+            Ok(sov_map) => sov_map,
+            Err(error) => {
+                log::error!("unable to load sovereignty map: {:?}", error);
+                return;
+            }
+        };
+        let (alliance_contacts, corporation_contacts, character_contacts) =
+            match (alliance_contacts, corporation_contacts, character_contacts) {
+                (Some(alliance), Some(corporation), Some(character)) => {
+                    (alliance, corporation, character)
+                }
+                _ => {
+                    log::warn!("keeping previous sov standings");
+                    return;
+                }
+            };
+
+        let mut alliance_standings = HashMap::new();
+        let mut corporation_standings = HashMap::new();
+        let contacts = alliance_contacts
+            .into_iter()
+            .chain(corporation_contacts)
+            .chain(character_contacts);
+        for (contact_type, contact_id, standing) in contacts {
+            match contact_type.as_str() {
+                "corporation" => {
+                    corporation_standings.insert(contact_id, standing);
+                }
+                "alliance" => {
+                    alliance_standings.insert(contact_id, standing);
+                }
+                _ => (),
+            }
+        }
+
+        let mut sov = HashMap::new();
+        let mut alliance_ids = Vec::new();
+        let mut corporation_ids = Vec::new();
+
+        for system in sov_map {
+            let alliance = if let Some(alliance_id) = system.alliance_id {
+                alliance_ids.push(alliance_id);
+                alliance_standings.get(&alliance_id).cloned()
+            } else {
+                None
+            };
+            let corporation = if let Some(corporation_id) = system.corporation_id {
+                corporation_ids.push(corporation_id);
+                corporation_standings.get(&corporation_id).cloned()
+            } else {
+                None
+            };
+
+            if system.alliance_id.is_some() || system.corporation_id.is_some() {
+                sov.insert(
+                    system.system_id,
+                    Sov {
+                        alliance_id: system.alliance_id,
+                        corporation_id: system.corporation_id,
+                        standing: alliance.or(corporation).unwrap_or(0.0),
+                    },
+                );
+            }
+        }
+
+        let snapshot_sov = sov.iter().map(|(id, sov)| (*id, *sov)).collect();
+        *sov_standings.write().unwrap() = sov;
+
+        alliance_ids.sort_unstable();
+        alliance_ids.dedup();
+        corporation_ids.sort_unstable();
+        corporation_ids.dedup();
+
+        let alliances_fut: FuturesUnordered<_> = alliance_ids
+            .iter()
+            .map(|alliance_id| client.get_alliance(*alliance_id))
+            .collect();
+
+        let corporations_fut: FuturesUnordered<_> = corporation_ids
+            .iter()
+            .map(|corporation_id| client.get_corporation(*corporation_id))
+            .collect();
+
+        let (alliance_res, corporation_res): (Vec<_>, Vec<_>) = futures::join!(
+            alliances_fut.filter_map(loaded("alliance")).collect(),
+            corporations_fut.filter_map(loaded("corporation")).collect()
+        );
+
+        let snapshot = SovSnapshot {
+            sov: snapshot_sov,
+            affiliation: *player_affiliation.read().unwrap(),
+            alliances: alliance_res,
+            corporations: corporation_res,
+        };
+
+        if character.is_some() {
+            client
+                .store_snapshot(SOV_SNAPSHOT, &snapshot, SOV_SNAPSHOT_LIFETIME)
+                .await;
+        }
+
+        World::apply_alliances(alliances, corporations, snapshot);
+    }
+
+    pub async fn restore_sov_standings(
+        sov_standings: &Arc<RwLock<HashMap<i32, Sov>>>,
+        player_affiliation: &Arc<RwLock<Option<Affiliation>>>,
+        alliances: &Arc<RwLock<HashMap<i32, esi::GetAlliance>>>,
+        corporations: &Arc<RwLock<HashMap<i32, esi::GetCorporation>>>,
+        client: &esi::Client,
+    ) -> bool {
+        let snapshot: SovSnapshot = match client.load_snapshot(SOV_SNAPSHOT).await {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+
+        log::info!("restored {} sov systems from snapshot", snapshot.sov.len());
+        *sov_standings.write().unwrap() = snapshot.sov.iter().copied().collect();
+        *player_affiliation.write().unwrap() = snapshot.affiliation;
+        World::apply_alliances(alliances, corporations, snapshot);
+        true
+    }
+
+    fn apply_alliances(
+        alliances: &Arc<RwLock<HashMap<i32, esi::GetAlliance>>>,
+        corporations: &Arc<RwLock<HashMap<i32, esi::GetCorporation>>>,
+        snapshot: SovSnapshot,
+    ) {
+        {
+            let mut alls = alliances.write().unwrap();
+            for alliance in snapshot.alliances {
+                alls.insert(alliance.alliance_id, alliance);
+            }
+        }
+
+        {
+            let mut corps = corporations.write().unwrap();
+            for corporation in snapshot.corporations {
+                corps.insert(corporation.corporation_id, corporation);
+            }
+        }
+    }
+
+    pub async fn load_system_stats(
+        system_stats: &Arc<RwLock<HashMap<i32, Stats>>>,
+        client: &esi::Client,
+    ) -> bool {
+        let (system_kills, system_jumps) = match futures::join!(
+            client.get_universe_system_kills(),
+            client.get_universe_system_jumps()
+        ) {
+            (Ok(system_kills), Ok(system_jumps)) => (system_kills, system_jumps),
+            (Err(error), _) | (_, Err(error)) => {
+                log::error!("unable to load system stats: {:?}", error);
+                return false;
+            }
+        };
+
+        let mut stats = system_stats.write().unwrap();
+        for sys in system_jumps {
+            if let Some(stat) = stats.get_mut(&sys.system_id) {
+                stat.jumps = sys.ship_jumps;
+            }
+        }
+
+        for sys in system_kills {
+            if let Some(stat) = stats.get_mut(&sys.system_id) {
+                stat.npc_kills = sys.npc_kills;
+                stat.ship_kills = sys.ship_kills;
+                stat.pod_kills = sys.pod_kills;
+            }
+        }
+
+        true
+    }
+
+    pub async fn load_incursions(
+        incursions: &Arc<RwLock<Vec<esi::GetIncursion>>>,
+        client: &esi::Client,
+    ) -> bool {
+        match client.get_incursions().await {
+            Ok(active) => {
+                log::info!("loaded {} active incursions", active.len());
+                *incursions.write().unwrap() = active;
+                true
+            }
+            Err(error) => {
+                log::error!("unable to load incursions: {:?}", error);
+                false
+            }
+        }
+    }
+
+    pub async fn load_fw_systems(
+        fw_systems: &Arc<RwLock<HashMap<i32, esi::GetFactionWarfareSystem>>>,
+        client: &esi::Client,
+    ) -> bool {
+        match client.get_fw_systems().await {
+            Ok(systems) => {
+                log::info!("loaded {} faction warfare systems", systems.len());
+                *fw_systems.write().unwrap() = systems
+                    .into_iter()
+                    .map(|s| (s.solar_system_id, s))
+                    .collect();
+                true
+            }
+            Err(error) => {
+                log::error!("unable to load faction warfare systems: {:?}", error);
+                false
+            }
+        }
+    }
+
+    pub fn import(&mut self, galaxy: Galaxy) {
+        let Galaxy { graph, client } = galaxy;
+
+        {
+            let mut stats = self.system_stats.write().unwrap();
+            for system_id in graph.systems.keys() {
+                stats.insert(
                     *system_id,
                     Stats {
                         jumps: 0,
@@ -664,22 +2165,15 @@ impl World {
                 );
             }
         }
-        let Galaxy {
-            systems,
-            systems_by_name,
-            stargates,
-            constellations,
-            regions,
-            graph,
-            client,
-        } = galaxy;
 
-        self.systems = systems;
-        self.systems_by_name = systems_by_name;
-        self.stargates = stargates;
-        self.constellations = constellations;
-        self.regions = regions;
-        self.graph = graph;
+        let excluded_jumps = std::mem::take(&mut self.galaxy.excluded_jumps);
+        let access_ids = std::mem::take(&mut self.galaxy.access_ids);
+        self.galaxy = graph;
+        self.galaxy.set_excluded_jumps(excluded_jumps);
+        self.galaxy.set_access_ids(access_ids);
+        self.chokepoints.replace(None);
+        self.pockets = World::find_pockets(&self.galaxy.system_adjacency(false));
+        log::info!("found {} pockets", self.pockets.len());
 
         let _ = self
             .event_sender
@@ -687,6 +2181,139 @@ impl World {
         let (tx, rx) = unbounded();
         self.update_sender = Some(tx);
         self.spawn_background_updater(client.clone(), rx);
+        self.client = Some(client);
+        if let Some(dir) = self.game_log_dir.as_ref() {
+            self.spawn_game_log_watcher(dir);
+        }
+        if let Some(path) = self.system_metrics_path.clone() {
+            self.spawn_system_metrics_loader(path);
+        }
+    }
+
+    fn spawn_system_metrics_loader(&self, path: String) {
+        let systems_by_name = self.galaxy.systems_by_name.clone();
+        let system_metrics = self.system_metrics.clone();
+        let event_sender = self.event_sender.clone();
+        spawn(async move {
+            match read_file(&path).await {
+                Ok(bytes) => {
+                    let csv = String::from_utf8_lossy(&bytes);
+                    let metrics = World::parse_system_metrics(&systems_by_name, &csv);
+                    log::info!("loaded {} system metrics from {}", metrics.len(), path);
+                    *system_metrics.write().unwrap() = metrics;
+                    event_sender
+                        .send_user_event(UserEvent::DataEvent(DataEvent::SystemMetricsChanged));
+                }
+                Err(error) => log::error!("unable to load system metrics {}: {:?}", path, error),
+            }
+        });
+    }
+
+    fn parse_system_metrics(
+        systems_by_name: &HashMap<String, i32>,
+        csv: &str,
+    ) -> HashMap<i32, f64> {
+        let systems_by_name: HashMap<String, i32> = systems_by_name
+            .iter()
+            .map(|(name, id)| (name.to_uppercase(), *id))
+            .collect();
+
+        let mut metrics = HashMap::new();
+        for line in csv.lines() {
+            let mut columns = line.split(',').map(|c| c.trim().trim_matches('"').trim());
+            let (name, value) = match (columns.next(), columns.next()) {
+                (Some(name), Some(value)) => (name, value),
+                _ => continue,
+            };
+
+            let value = match value.parse::<f64>() {
+                Ok(value) if value.is_finite() => value,
+                _ => continue,
+            };
+
+            match systems_by_name.get(&name.to_uppercase()) {
+                Some(system_id) => {
+                    metrics.insert(*system_id, value);
+                }
+                None => log::warn!("unknown system in metrics: {}", name),
+            }
+        }
+
+        metrics
+    }
+
+    fn spawn_game_log_watcher(&self, dir: &str) {
+        let mut local_systems = match watch_local_chat(dir) {
+            Some(receiver) => receiver,
+            None => return,
+        };
+
+        let systems_by_name = self.galaxy.systems_by_name.clone();
+        let player_system = self.player_system.clone();
+        let event_sender = self.event_sender.clone();
+        spawn(async move {
+            while let Some(name) = local_systems.next().await {
+                let location = systems_by_name.get(&name).cloned();
+                if location.is_none() {
+                    log::warn!("unknown system in game log: {}", name);
+                    continue;
+                }
+
+                let mut current_location = player_system.write().unwrap();
+                if location != *current_location {
+                    log::info!("game log location changed: {}", name);
+                    *current_location = location;
+                    event_sender.send_user_event(UserEvent::DataEvent(
+                        DataEvent::CharacterLocationChanged(location),
+                    ));
+                }
+            }
+        });
+    }
+
+    async fn character_online(client: &esi::Client) -> bool {
+        match client.get_character_online().await {
+            Ok(online) => online.online,
+            Err(error) => {
+                log::error!("character online check failed: {:?}", error);
+                true
+            }
+        }
+    }
+
+    async fn load_docked_location(
+        client: &esi::Client,
+        station_id: Option<i64>,
+        structure_id: Option<i64>,
+    ) -> Option<String> {
+        if let Some(station_id) = station_id {
+            match client.get_station(station_id).await {
+                Ok(station) => Some(station.name),
+                Err(error) => {
+                    log::error!("station lookup failed {}: {:?}", station_id, error);
+                    None
+                }
+            }
+        } else if let Some(structure_id) = structure_id {
+            match client.get_structure(structure_id).await {
+                Ok(structure) => Some(structure.name),
+                Err(esi::Error::Http { status, url }) if matches!(status.as_u16(), 403 | 404) => {
+                    log::warn!(
+                        "structure {} not accessible ({}): {}",
+                        structure_id,
+                        status,
+                        url
+                    );
+                    Some(String::from("Unknown Structure"))
+                }
+                Err(error) => {
+                    log::error!("structure lookup failed {}: {:?}", structure_id, error);
+                    None
+                }
+            }
+        } else {
+            None
+        }
     }
 
     fn spawn_background_updater(
@@ -696,39 +2323,204 @@ impl World {
     ) {
         let event_sender = self.event_sender.clone();
         let player_system = self.player_system.clone();
+        let docked_location = self.docked_location.clone();
         let system_stats = self.system_stats.clone();
+        let incursions = self.incursions.clone();
+        let fw_systems = self.fw_systems.clone();
         let sov_standings = self.sov.clone();
+        let player_affiliation = self.player_affiliation.clone();
         let alliances = self.alliances.clone();
         let corporations = self.corporations.clone();
 
         let alliance_logos = self.alliance_logos.clone();
+        let refresh_requested = self.refresh_requested.clone();
+        let profiles = self.profiles.clone();
+        let profile_switched = self.profile_switched.clone();
         spawn({
+            let player_system = player_system.clone();
+            let profile_switched = profile_switched.clone();
             let client = client.clone();
             let event_sender = event_sender.clone();
+            let alliances = alliances.clone();
+            let corporations = corporations.clone();
             async move {
                 loop {
                     let update = update_receiver.next().await;
                     match update {
                         Some(UpdateRequest::AllianceLogo(alliance_id)) => {
-                            let logo = client.get_alliance_logo(alliance_id, 256).await.unwrap();
-                            let logo = Arc::new(logo);
+                            let logo = match client.get_alliance_logo(alliance_id, 256).await {
+                                Ok(logo) => Arc::new(logo),
+                                Err(error) => {
+                                    log::error!(
+                                        "unable to load alliance logo {}: {:?}",
+                                        alliance_id,
+                                        error
+                                    );
+                                    continue;
+                                }
+                            };
 
                             alliance_logos.write().unwrap().insert(alliance_id, logo);
                             event_sender
                                 .send_user_event(UserEvent::DataEvent(DataEvent::ImageLoaded));
                         }
-                        Some(UpdateRequest::SendRouteToClient(player_location, route)) => {
-                            if route.len() > 0 {
-                                match client.get_character_online().await {
-                                    Ok(online) => {
-                                        if !online.online {
-                                            continue;
+                        Some(UpdateRequest::WarmCache(alliance_ids, corporation_ids)) => {
+                            let logos_fut: FuturesUnordered<_> = alliance_ids
+                                .iter()
+                                .map(|alliance_id| {
+                                    client
+                                        .get_alliance_logo(*alliance_id, 256)
+                                        .map(move |logo| (*alliance_id, logo))
+                                })
+                                .collect();
+                            let alliances_fut: FuturesUnordered<_> = alliance_ids
+                                .iter()
+                                .map(|alliance_id| client.get_alliance(*alliance_id))
+                                .collect();
+                            let corporations_fut: FuturesUnordered<_> = corporation_ids
+                                .iter()
+                                .map(|corporation_id| client.get_corporation(*corporation_id))
+                                .collect();
+
+                            let (logo_res, alliance_res, corporation_res): (
+                                Vec<_>,
+                                Vec<_>,
+                                Vec<_>,
+                            ) = futures::join!(
+                                logos_fut.collect(),
+                                alliances_fut.collect(),
+                                corporations_fut.collect()
+                            );
+
+                            {
+                                let mut logos = alliance_logos.write().unwrap();
+                                for (alliance_id, logo) in logo_res {
+                                    match logo {
+                                        Ok(logo) => {
+                                            logos.insert(alliance_id, Arc::new(logo));
+                                        }
+                                        Err(error) => log::error!(
+                                            "unable to warm alliance logo {}: {:?}",
+                                            alliance_id,
+                                            error
+                                        ),
+                                    }
+                                }
+                            }
+
+                            {
+                                let mut alls = alliances.write().unwrap();
+                                for alliance in alliance_res {
+                                    match alliance {
+                                        Ok(alliance) => {
+                                            alls.insert(alliance.alliance_id, alliance);
+                                        }
+                                        Err(error) => {
+                                            log::error!("unable to warm alliance: {:?}", error)
+                                        }
+                                    }
+                                }
+                            }
+
+                            {
+                                let mut corps = corporations.write().unwrap();
+                                for corporation in corporation_res {
+                                    match corporation {
+                                        Ok(corporation) => {
+                                            corps.insert(corporation.corporation_id, corporation);
+                                        }
+                                        Err(error) => {
+                                            log::error!("unable to warm corporation: {:?}", error)
                                         }
                                     }
+                                }
+                            }
+
+                            log::info!("finished warming cache");
+                            event_sender
+                                .send_user_event(UserEvent::DataEvent(DataEvent::ImageLoaded));
+                            event_sender.send_user_event(UserEvent::DataEvent(
+                                DataEvent::SovStandingsChanged,
+                            ));
+                        }
+                        Some(UpdateRequest::ListProfiles) => {
+                            match oauth::list_profiles(client.paths()).await {
+                                Ok(characters) => {
+                                    *profiles.write().unwrap() = characters;
+                                    event_sender.send_user_event(UserEvent::DataEvent(
+                                        DataEvent::ProfilesLoaded,
+                                    ));
+                                }
+                                Err(error) => log::error!("unable to list profiles: {:?}", error),
+                            }
+                        }
+                        Some(UpdateRequest::SwitchProfile(character_id)) => {
+                            match oauth::load_profile(client.paths(), character_id).await {
+                                Ok(profile) => {
+                                    client.replace_profile(profile).await;
+                                    *player_system.write().unwrap() = None;
+                                    profile_switched.store(true, Ordering::Relaxed);
+                                    event_sender.send_user_event(UserEvent::DataEvent(
+                                        DataEvent::CharacterLocationChanged(None),
+                                    ));
+                                }
+                                Err(error) => log::error!(
+                                    "unable to load profile {}: {:?}",
+                                    character_id,
+                                    error
+                                ),
+                            }
+                        }
+                        Some(UpdateRequest::ClearCache) => client.clear_cache().await,
+                        Some(UpdateRequest::Logout) => {
+                            let client = client.clone();
+                            let event_sender = event_sender.clone();
+                            let player_system = player_system.clone();
+                            let profile_switched = profile_switched.clone();
+                            spawn(async move {
+                                let character_id = client.character_id().await;
+                                if let Err(error) =
+                                    oauth::logout(client.paths(), character_id).await
+                                {
+                                    log::error!("unable to remove profile: {:?}", error);
+                                }
+
+                                match oauth::authorize(client.paths()).await {
+                                    Ok(profile) => {
+                                        client.replace_profile(profile).await;
+                                        *player_system.write().unwrap() = None;
+                                        profile_switched.store(true, Ordering::Relaxed);
+                                        event_sender.send_user_event(UserEvent::DataEvent(
+                                            DataEvent::CharacterLocationChanged(None),
+                                        ));
+                                    }
                                     Err(error) => {
-                                        log::error!("send route online check failed: {:?}", error);
+                                        log::error!("unable to authorize: {:?}", error);
+                                        event_sender.send_user_event(UserEvent::Notice(
+                                            "Unable to authorize character".to_string(),
+                                            Severity::Error,
+                                        ));
                                     }
                                 }
+                            });
+                        }
+                        Some(UpdateRequest::SetWaypoint(system)) => {
+                            if !World::character_online(&client).await {
+                                continue;
+                            }
+                            if let Err(error) = client.post_waypoint(false, true, system).await {
+                                log::error!("set waypoint failed: {:?}", error);
+                                event_sender.send_user_event(UserEvent::Notice(
+                                    "Unable to set waypoint".to_string(),
+                                    Severity::Error,
+                                ));
+                            }
+                        }
+                        Some(UpdateRequest::SendRouteToClient(player_location, route)) => {
+                            if route.len() > 0 {
+                                if !World::character_online(&client).await {
+                                    continue;
+                                }
                                 let player_on_route =
                                     route.iter().any(|r| Some(*r) == player_location);
                                 let mut send_systems = !player_on_route;
@@ -741,6 +2533,10 @@ impl World {
                                             client.post_waypoint(false, first, system).await;
                                         if let Err(error) = result {
                                             log::error!("send route failed: {:?}", error);
+                                            event_sender.send_user_event(UserEvent::Notice(
+                                                "Unable to send route to client".to_string(),
+                                                Severity::Error,
+                                            ));
                                             break;
                                         }
                                         first = false;
@@ -759,32 +2555,125 @@ impl World {
                 }
             }
         });
+
+        if client.offline() {
+            log::info!("offline, skipping background polling");
+            return;
+        }
+
         spawn(async move {
             let mut counter = 0;
-            let poll_interval = 10;
+            let poll_interval = 1;
+            let mut last_refresh = Instant::now();
+            let mut esi_location = None;
+            let mut esi_docked = None;
+            let mut restore_snapshot = true;
+            let mut error_limited = false;
             loop {
-                if counter % 10 == 0 {
-                    let location = client
-                        .get_character_location()
-                        .await
-                        .ok()
-                        .map(|l| l.solar_system_id);
-                    let mut current_location = player_system.write().unwrap();
-                    if location != *current_location {
-                        *current_location = location;
+                if client.error_limited() != error_limited {
+                    error_limited = !error_limited;
+                    if error_limited {
+                        event_sender.send_user_event(UserEvent::Notice(
+                            "ESI error limited, retrying".to_string(),
+                            Severity::Warning,
+                        ));
+                    }
+                }
+
+                if profile_switched.swap(false, Ordering::Relaxed) {
+                    log::info!("character changed, refreshing location and standings");
+                    restore_snapshot = true;
+                    counter = 0;
+                    esi_location = None;
+                    esi_docked = None;
+                }
+
+                let refresh = refresh_requested.swap(false, Ordering::Relaxed);
+                if refresh && last_refresh.elapsed() > FOCUS_REFRESH_THRESHOLD {
+                    log::info!("refreshing stale data");
+                    counter = 0;
+                }
+
+                if counter % 10 == 0 || refresh {
+                    let character_location = client.get_character_location().await.ok();
+                    let docked = character_location
+                        .as_ref()
+                        .map(|l| (l.station_id, l.structure_id));
+                    if docked != esi_docked {
+                        esi_docked = docked;
+                        let (station_id, structure_id) = docked.unwrap_or((None, None));
+                        let name =
+                            World::load_docked_location(&client, station_id, structure_id).await;
+                        *docked_location.write().unwrap() = name;
                         event_sender.send_user_event(UserEvent::DataEvent(
-                            DataEvent::CharacterLocationChanged(location),
+                            DataEvent::DockedLocationChanged,
                         ));
                     }
+
+                    let location = character_location.map(|l| l.solar_system_id);
+                    let mut current_location = player_system.write().unwrap();
+                    if location != esi_location {
+                        esi_location = location;
+                        if location != *current_location {
+                            *current_location = location;
+                            event_sender.send_user_event(UserEvent::DataEvent(
+                                DataEvent::CharacterLocationChanged(location),
+                            ));
+                        }
+                    }
                 }
                 if counter % 300 == 0 {
-                    World::load_system_stats(&system_stats, &client).await;
-                    World::load_sov_standings(&sov_standings, &alliances, &corporations, &client)
+                    let restored = restore_snapshot
+                        && World::restore_sov_standings(
+                            &sov_standings,
+                            &player_affiliation,
+                            &alliances,
+                            &corporations,
+                            &client,
+                        )
                         .await;
+                    if restored {
+                        event_sender
+                            .send_user_event(UserEvent::DataEvent(DataEvent::SovStandingsChanged));
+                    }
+                    restore_snapshot = false;
+
+                    last_refresh = Instant::now();
+                    let mut failed = Vec::new();
+                    if !World::load_system_stats(&system_stats, &client).await {
+                        failed.push("system stats");
+                    }
+                    World::load_sov_standings(
+                        &sov_standings,
+                        &player_affiliation,
+                        &alliances,
+                        &corporations,
+                        &client,
+                    )
+                    .await;
                     event_sender
                         .send_user_event(UserEvent::DataEvent(DataEvent::SovStandingsChanged));
                     event_sender
                         .send_user_event(UserEvent::DataEvent(DataEvent::SystemStatsChanged));
+                    if World::load_incursions(&incursions, &client).await {
+                        event_sender
+                            .send_user_event(UserEvent::DataEvent(DataEvent::IncursionsChanged));
+                    } else {
+                        failed.push("incursions");
+                    }
+                    if World::load_fw_systems(&fw_systems, &client).await {
+                        event_sender.send_user_event(UserEvent::DataEvent(
+                            DataEvent::FactionWarfareChanged,
+                        ));
+                    } else {
+                        failed.push("faction warfare");
+                    }
+                    if !failed.is_empty() {
+                        event_sender.send_user_event(UserEvent::Notice(
+                            format!("Unable to refresh {} from ESI", failed.join(", ")),
+                            Severity::Warning,
+                        ));
+                    }
                 }
                 sleep(std::time::Duration::from_secs(poll_interval)).await;
                 counter += poll_interval;
@@ -797,6 +2686,43 @@ impl World {
         sov.get(&system).cloned()
     }
 
+    pub fn resolve_waypoints(&self, list: &str) -> (Vec<i32>, Vec<String>) {
+        World::split_waypoints(list, |name| self.match_system(name).into_iter().next())
+    }
+
+    fn split_waypoints<F: Fn(&str) -> Option<i32>>(
+        list: &str,
+        resolve: F,
+    ) -> (Vec<i32>, Vec<String>) {
+        let mut waypoints = Vec::new();
+        let mut unresolved = Vec::new();
+        for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            match resolve(name) {
+                Some(system_id) => waypoints.push(system_id),
+                None => unresolved.push(name.to_string()),
+            }
+        }
+
+        (waypoints, unresolved)
+    }
+
+    pub fn is_home_sov(&self, system_id: i32) -> bool {
+        let affiliation = *self.player_affiliation.read().unwrap();
+        let sov = self.sov.read().unwrap();
+        World::holds_sov(affiliation.as_ref(), &sov, system_id)
+    }
+
+    fn holds_sov(
+        affiliation: Option<&Affiliation>,
+        sov: &HashMap<i32, Sov>,
+        system_id: i32,
+    ) -> bool {
+        match (affiliation, sov.get(&system_id)) {
+            (Some(affiliation), Some(sov)) => affiliation.holds(sov),
+            _ => false,
+        }
+    }
+
     pub fn match_system(&self, search: &str) -> Vec<i32> {
         if search == "@me" {
             if let Some(location) = self.location() {
@@ -805,306 +2731,1066 @@ impl World {
                 return Vec::new();
             }
         }
-        let search = search.to_uppercase();
-        let search = search.trim();
-        let mut matches = Vec::new();
-        for sys in self.systems.values() {
-            let name = sys.name.to_uppercase();
-            let name = name.trim();
 
-            if name.starts_with(search) {
-                matches.push(sys.system_id);
+        self.galaxy.match_system(search)
+    }
+
+    pub fn location(&self) -> Option<i32> {
+        *self.player_system.read().unwrap()
+    }
+
+    pub fn docked_location(&self) -> Option<String> {
+        self.docked_location.read().unwrap().clone()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Galaxy {
+    graph: GalaxyGraph,
+    client: crate::esi::Client,
+}
+
+impl From<Galaxy> for GalaxyGraph {
+    fn from(galaxy: Galaxy) -> Self {
+        galaxy.graph
+    }
+}
+
+// Nearest neighbor followed by 2-opt, the tour always starts at the first system
+fn shortest_tour(distances: &[Vec<u32>]) -> Vec<usize> {
+    let count = distances.len();
+    if count == 0 {
+        return Vec::new();
+    }
+
+    let mut tour = vec![0];
+    let mut remaining: Vec<usize> = (1..count).collect();
+    while !remaining.is_empty() {
+        let last = tour[tour.len() - 1];
+        let (idx, _) = remaining
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, &next)| distances[last][next])
+            .unwrap();
+        tour.push(remaining.swap_remove(idx));
+    }
+
+    let distance = |a: usize, b: usize| distances[a][b] as i64;
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 1..count {
+            for j in i + 1..count {
+                let (a, b) = (tour[i - 1], tour[i]);
+                let (c, d) = (tour[j], tour[(j + 1) % count]);
+                let delta = distance(a, c) + distance(b, d) - distance(a, b) - distance(c, d);
+                if delta < 0 {
+                    tour[i..=j].reverse();
+                    improved = true;
+                }
+            }
+        }
+    }
+
+    tour
+}
+
+// the galaxy can't be built without the id lists, so keep retrying them unless offline
+// where the cache will never fill in
+async fn load_ids<F, Fut>(kind: &str, fetch: F) -> Result<Vec<i32>, String>
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = Result<Vec<i32>, esi::Error>>,
+{
+    loop {
+        match fetch().await {
+            Ok(ids) => break Ok(ids),
+            Err(esi::Error::Offline) => {
+                log::error!("no cached {} ids available offline", kind);
+                break Err(format!("No cached {} available offline", kind));
+            }
+            Err(error) => {
+                log::error!("unable to load {} ids, retrying: {:?}", kind, error);
+                async_std::task::sleep(std::time::Duration::from_secs(10)).await;
+            }
+        }
+    }
+}
+
+fn loaded<T>(
+    kind: &'static str,
+) -> impl FnMut(Result<T, esi::Error>) -> futures::future::Ready<Option<T>> {
+    move |result| {
+        futures::future::ready(match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                log::error!("unable to load {}, skipping: {:?}", kind, error);
+                None
+            }
+        })
+    }
+}
+
+fn parse_access_ids(ids: &str) -> Vec<i32> {
+    ids.split(|c: char| c == ',' || c.is_whitespace())
+        .filter_map(|id| id.parse().ok())
+        .collect()
+}
+
+fn load_progress<T>(event_sender: EventSender, stage: LoadStage, total: usize) -> impl FnMut(&T) {
+    event_sender.send_user_event(UserEvent::DataEvent(DataEvent::LoadProgress {
+        stage,
+        done: 0,
+        total,
+    }));
+
+    let mut done = 0;
+    move |_| {
+        done += 1;
+        if done % LOAD_PROGRESS_INTERVAL == 0 || done == total {
+            event_sender.send_user_event(UserEvent::DataEvent(DataEvent::LoadProgress {
+                stage,
+                done,
+                total,
+            }));
+        }
+    }
+}
+
+impl Galaxy {
+    pub async fn load(
+        event_sender: EventSender,
+        paths: crate::paths::Paths,
+        concurrency: usize,
+        timeout: std::time::Duration,
+        offline: bool,
+    ) -> Result<Self, String> {
+        let profile = loop {
+            match crate::oauth::load_or_authorize(&paths, offline).await {
+                Ok(profile) => break profile,
+                Err(error) if offline => {
+                    log::error!("unable to load profile offline: {:?}", error);
+                    return Err("No saved profile available offline".to_string());
+                }
+                Err(error) => {
+                    log::error!("unable to authorize, retrying: {:?}", error);
+                    async_std::task::sleep(std::time::Duration::from_secs(10)).await;
+                }
+            }
+        };
+        let client = crate::esi::Client::new(profile, paths, concurrency, timeout, offline).await;
+        let load_start = Instant::now();
+
+        let regions = load_ids("regions", || client.get_universe_regions());
+        let constellations = load_ids("constellations", || client.get_universe_constellations());
+        let systems = load_ids("systems", || client.get_universe_systems());
+
+        let (regions, constellations, systems) =
+            futures::try_join!(regions, constellations, systems)?;
+
+        let regions_fut = futures::stream::iter(regions.iter().copied())
+            .map(|region_id| client.get_universe_region(region_id))
+            .buffered(GALAXY_LOAD_WINDOW)
+            .inspect(load_progress(
+                event_sender.clone(),
+                LoadStage::Regions,
+                regions.len(),
+            ));
+
+        let constellations_fut = futures::stream::iter(constellations.iter().copied())
+            .map(|constellation_id| client.get_universe_constellation(constellation_id))
+            .buffered(GALAXY_LOAD_WINDOW)
+            .inspect(load_progress(
+                event_sender.clone(),
+                LoadStage::Constellations,
+                constellations.len(),
+            ));
+
+        let systems_fut = futures::stream::iter(systems.iter().copied())
+            .map(|system_id| client.get_universe_system(system_id))
+            .buffered(GALAXY_LOAD_WINDOW)
+            .inspect(load_progress(
+                event_sender.clone(),
+                LoadStage::Systems,
+                systems.len(),
+            ));
+
+        let (regions, constellations, systems): (Vec<_>, Vec<_>, Vec<_>) = futures::join!(
+            regions_fut.filter_map(loaded("region")).collect(),
+            constellations_fut
+                .filter_map(loaded("constellation"))
+                .collect(),
+            systems_fut.filter_map(loaded("system")).collect(),
+        );
+
+        let all_stargate_ids: Vec<_> = systems
+            .iter()
+            .filter_map(|system| system.stargates.as_ref())
+            .flatten()
+            .copied()
+            .collect();
+
+        let stargates_fut = futures::stream::iter(all_stargate_ids.iter().copied())
+            .map(|stargate_id| client.get_universe_stargate(stargate_id))
+            .buffered(GALAXY_LOAD_WINDOW)
+            .inspect(load_progress(
+                event_sender.clone(),
+                LoadStage::Stargates,
+                all_stargate_ids.len(),
+            ));
+
+        let stargates: Vec<_> = stargates_fut.filter_map(loaded("stargate")).collect().await;
+
+        let mut galaxy = GalaxyGraph::build(regions, constellations, systems, stargates);
+
+        let mut virtual_gate_id = 0;
+        if file_exists("bridges.tsv") {
+            let bridges = read_file("bridges.tsv").await.unwrap();
+            let bridges_tsv = String::from_utf8(bridges).unwrap();
+
+            for line in bridges_tsv.lines() {
+                let line_parts: Vec<_> = line.split('\t').collect();
+                let left = line_parts[1].split(' ').next().unwrap();
+                let right = line_parts[2].split(' ').next().unwrap();
+                let allowed = line_parts.get(3).map(|ids| parse_access_ids(ids));
+
+                let left = galaxy
+                    .systems_by_name
+                    .get(left)
+                    .and_then(|id| galaxy.systems.get(id))
+                    .cloned()
+                    .unwrap();
+                let right = galaxy
+                    .systems_by_name
+                    .get(right)
+                    .and_then(|id| galaxy.systems.get(id))
+                    .cloned()
+                    .unwrap();
+
+                galaxy.add_virtual_gates(virtual_gate_id, &left, &right, false);
+                virtual_gate_id += 2;
+
+                if let Some(allowed) = allowed.filter(|ids| !ids.is_empty()) {
+                    galaxy.restrict_bridge(left.system_id, right.system_id, allowed);
+                }
+            }
+        }
+
+        if file_exists("wormholes.tsv") {
+            let wormholes = read_file("wormholes.tsv").await.unwrap();
+            let wormholes_tsv = String::from_utf8_lossy(&wormholes);
+
+            let mut wormhole_count = 0;
+            for line in wormholes_tsv.lines() {
+                let line_parts: Vec<_> = line.split('\t').map(str::trim).collect();
+                if line_parts.len() < 2 || line.starts_with('#') {
+                    continue;
+                }
+
+                let left = galaxy
+                    .systems_by_name
+                    .get(line_parts[0])
+                    .and_then(|id| galaxy.systems.get(id))
+                    .cloned();
+                let right = galaxy
+                    .systems_by_name
+                    .get(line_parts[1])
+                    .and_then(|id| galaxy.systems.get(id))
+                    .cloned();
+
+                if let (Some(left), Some(right)) = (left, right) {
+                    galaxy.add_virtual_gates(virtual_gate_id, &left, &right, true);
+                    virtual_gate_id += 2;
+                    wormhole_count += 1;
+                } else {
+                    log::warn!("unknown systems in wormhole connection: {}", line);
+                }
             }
+            log::info!("loaded {} wormhole connections", wormhole_count);
         }
 
-        matches
-    }
+        log::info!("galaxy loaded in {}ms", load_start.elapsed().as_millis());
 
-    pub fn location(&self) -> Option<i32> {
-        *self.player_system.read().unwrap()
+        Ok(Galaxy {
+            graph: galaxy,
+            client,
+        })
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct Galaxy {
-    systems: HashMap<i32, esi::GetUniverseSystem>,
-    systems_by_name: HashMap<String, i32>,
-    stargates: HashMap<i32, esi::GetUniverseStargate>,
-    constellations: HashMap<i32, esi::GetUniverseConstellation>,
-    regions: HashMap<i32, esi::GetUniverseRegion>,
-    graph: Graph<Node, Edge, petgraph::Undirected, u32>,
-    client: crate::esi::Client,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl Galaxy {
-    pub async fn load() -> Self {
-        let profile = crate::oauth::load_or_authorize().await.unwrap();
-        let client = crate::esi::Client::new(profile).await;
+    fn adjacency(edges: &[(i32, i32)]) -> HashMap<i32, Vec<i32>> {
+        let mut adjacency: HashMap<i32, Vec<i32>> = HashMap::new();
+        for &(left, right) in edges {
+            adjacency.entry(left).or_default().push(right);
+            adjacency.entry(right).or_default().push(left);
+        }
+        adjacency
+    }
 
-        let mut galaxy = Galaxy {
-            systems: HashMap::new(),
-            systems_by_name: HashMap::new(),
-            stargates: HashMap::new(),
-            constellations: HashMap::new(),
-            regions: HashMap::new(),
-            graph: Graph::new_undirected(),
-            client: client.clone(),
-        };
+    #[test]
+    fn pockets_behind_single_gateway() {
+        // 1-2-3 form a loop, 3 leads to the dead end pocket 4-5
+        let adjacency = adjacency(&[(1, 2), (2, 3), (3, 1), (3, 4), (4, 5)]);
+        let mut pockets = World::find_pockets(&adjacency);
+        pockets.sort_by_key(|(gateway, pocket)| (*gateway, pocket.len()));
+
+        let expected: Vec<(i32, HashSet<i32>)> = vec![
+            (3, [4, 5].iter().cloned().collect()),
+            (4, [5].iter().cloned().collect()),
+        ];
+        assert_eq!(pockets, expected);
+    }
 
-        let regions = client.get_universe_regions();
-        let constellations = client.get_universe_constellations();
-        let systems = client.get_universe_systems();
+    #[test]
+    fn articulation_points_split_the_graph() {
+        // 1-2-3 form a loop, 3 and 4 are the only links to 5
+        let loop_with_tail = adjacency(&[(1, 2), (2, 3), (3, 1), (3, 4), (4, 5)]);
+        let expected: HashSet<i32> = [3, 4].iter().cloned().collect();
+        assert_eq!(World::find_articulation_points(&loop_with_tail), expected);
 
-        let (regions, constellations, systems) = futures::join!(regions, constellations, systems);
+        // the dfs root is only a chokepoint when it has multiple children
+        let star = adjacency(&[(1, 2), (1, 3), (1, 4)]);
+        let expected: HashSet<i32> = [1].iter().cloned().collect();
+        assert_eq!(World::find_articulation_points(&star), expected);
 
-        let regions = regions.unwrap();
-        let constellations = constellations.unwrap();
-        let systems = systems.unwrap();
+        let cycle = adjacency(&[(1, 2), (2, 3), (3, 4), (4, 1)]);
+        assert!(World::find_articulation_points(&cycle).is_empty());
+    }
 
-        let mut all_systems = HashMap::new();
-        let mut all_stargates = HashMap::new();
-        let mut all_stargate_ids = Vec::new();
+    #[test]
+    fn no_pockets_in_cycle() {
+        let adjacency = adjacency(&[(1, 2), (2, 3), (3, 4), (4, 1)]);
+        assert!(World::find_pockets(&adjacency).is_empty());
+    }
 
-        let regions_fut: FuturesUnordered<_> = regions
+    fn search(
+        systems: &[(i32, f64)],
+        jumps: &[(i32, i32)],
+        from: i32,
+        to: i32,
+        preference: RoutePreference,
+        avoided: &HashSet<i32>,
+    ) -> Option<Vec<i32>> {
+        let mut graph = Graph::new_undirected();
+        let nodes: HashMap<i32, _> = systems
             .iter()
-            .map(|region_id| client.get_universe_region(*region_id))
+            .map(|&(system, _)| (system, graph.add_node(Node::System { system })))
             .collect();
+        for &(left, right) in jumps {
+            graph.add_edge(nodes[&left], nodes[&right], Edge::Jump { left, right });
+        }
 
-        let constellations_fut: FuturesUnordered<_> = constellations
-            .iter()
-            .map(|constellation_id| client.get_universe_constellation(*constellation_id))
-            .collect();
+        let security: HashMap<i32, f64> = systems.iter().cloned().collect();
+        let (_cost, path) =
+            GalaxyGraph::search_route(&graph, nodes[&from], to, avoided, |edge, destination| {
+                let class = SecurityClass::classify(destination, security[&destination]);
+                edge.distance() + preference.security_penalty(class)
+            })?;
+
+        Some(
+            path.into_iter()
+                .map(|n| GalaxyGraph::node_system(&graph, n))
+                .collect(),
+        )
+    }
 
-        let systems_fut: FuturesUnordered<_> = systems
-            .iter()
-            .map(|system_id| client.get_universe_system(*system_id))
-            .collect();
+    fn preferred_route(
+        systems: &[(i32, f64)],
+        jumps: &[(i32, i32)],
+        from: i32,
+        to: i32,
+        preference: RoutePreference,
+    ) -> Vec<i32> {
+        search(systems, jumps, from, to, preference, &HashSet::new()).unwrap()
+    }
 
-        let (regions, constellations, systems): (Vec<_>, Vec<_>, Vec<_>) = futures::join!(
-            regions_fut.map(Result::unwrap).collect(),
-            constellations_fut.map(Result::unwrap).collect(),
-            systems_fut.map(Result::unwrap).collect(),
+    #[test]
+    fn parses_system_metrics() {
+        let systems_by_name: HashMap<String, i32> =
+            vec![("Jita".to_string(), 1), ("Amarr".to_string(), 2)]
+                .into_iter()
+                .collect();
+        let csv = "system,value\n\"Jita\", 1500.5\namarr,-2\nNowhere,3\nJita\n";
+        let metrics = World::parse_system_metrics(&systems_by_name, csv);
+
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics.get(&1), Some(&1500.5));
+        assert_eq!(metrics.get(&2), Some(&-2.0));
+    }
+
+    fn name_index() -> Vec<(String, i32)> {
+        let systems_by_name: HashMap<String, i32> = vec![
+            ("Jita".to_string(), 1),
+            ("Jatate".to_string(), 2),
+            ("Amarr".to_string(), 3),
+            ("Jita ".to_string(), 4),
+            ("Jitanen".to_string(), 5),
+            ("Ikuchi".to_string(), 6),
+            ("Jita IV".to_string(), 7),
+        ]
+        .into_iter()
+        .collect();
+        GalaxyGraph::build_name_index(&systems_by_name)
+    }
+
+    #[test]
+    fn name_index_matches_prefixes() {
+        let index = name_index();
+        assert_eq!(
+            GalaxyGraph::search_name_index(&index, "j"),
+            vec![1, 4, 2, 7, 5]
         );
+        assert_eq!(
+            GalaxyGraph::search_name_index(&index, " JI"),
+            vec![1, 4, 7, 5]
+        );
+        assert_eq!(
+            GalaxyGraph::search_name_index(&index, "zz"),
+            Vec::<i32>::new()
+        );
+    }
 
-        for region in regions {
-            galaxy.regions.insert(region.region_id, region);
-        }
+    #[test]
+    fn name_index_matches_exact_names() {
+        let index = name_index();
+        assert_eq!(GalaxyGraph::search_name_index(&index, "amarr"), vec![3]);
+        assert_eq!(GalaxyGraph::search_name_index(&index, "Ikuchi"), vec![6]);
+        assert_eq!(GalaxyGraph::search_name_index(&index, "Jitanen"), vec![5]);
+    }
 
-        for constellation in constellations {
-            galaxy
-                .constellations
-                .insert(constellation.constellation_id, constellation);
+    #[test]
+    fn name_index_results_are_ordered() {
+        for _ in 0..10 {
+            let index = name_index();
+            assert_eq!(
+                GalaxyGraph::search_name_index(&index, ""),
+                vec![1, 4, 3, 6, 2, 7, 5]
+            );
         }
+    }
 
-        for system in systems {
-            if let Some(stargates) = &system.stargates {
-                all_stargate_ids.extend_from_slice(stargates);
-            }
+    #[test]
+    fn name_index_ranks_exact_matches_first() {
+        let index = name_index();
+        assert_eq!(
+            GalaxyGraph::search_name_index(&index, "jita"),
+            vec![1, 4, 7, 5]
+        );
+        assert_eq!(GalaxyGraph::search_name_index(&index, "jita iv"), vec![7]);
+
+        let systems_by_name: HashMap<String, i32> =
+            (0..100).map(|id| (format!("System {}", id), id)).collect();
+        let index = GalaxyGraph::build_name_index(&systems_by_name);
+        let matches = GalaxyGraph::search_name_index(&index, "system 1");
+        assert_eq!(matches.len(), 11);
+        assert_eq!(matches[0], 1);
+        assert_eq!(
+            GalaxyGraph::search_name_index(&index, "sys").len(),
+            MAX_SYSTEM_MATCHES
+        );
+    }
 
-            let node_id = galaxy.graph.add_node(Node::System {
-                system: system.system_id,
-            });
-            all_systems.insert(system.system_id, node_id);
+    // 1 -> 2 -> 5 passes through low-sec, 1 -> 3 -> 4 -> 5 stays in high-sec
+    const DETOUR_SYSTEMS: &[(i32, f64)] = &[(1, 0.9), (2, 0.3), (3, 0.8), (4, 0.7), (5, 0.6)];
+    const DETOUR_JUMPS: &[(i32, i32)] = &[(1, 2), (2, 5), (1, 3), (3, 4), (4, 5)];
+
+    #[test]
+    fn shortest_route_ignores_security() {
+        let route = preferred_route(
+            DETOUR_SYSTEMS,
+            DETOUR_JUMPS,
+            1,
+            5,
+            RoutePreference::Shortest,
+        );
+        assert_eq!(route, vec![1, 2, 5]);
+    }
 
-            galaxy
-                .systems_by_name
-                .insert(system.name.clone(), system.system_id);
-            galaxy.systems.insert(system.system_id, system);
-        }
+    #[test]
+    fn safer_route_takes_high_sec_detour() {
+        let route = preferred_route(DETOUR_SYSTEMS, DETOUR_JUMPS, 1, 5, RoutePreference::Safer);
+        assert_eq!(route, vec![1, 3, 4, 5]);
+    }
 
-        let stargates_fut: FuturesUnordered<_> = all_stargate_ids
-            .iter()
-            .map(|stargate_id| client.get_universe_stargate(*stargate_id))
-            .collect();
+    #[test]
+    fn less_secure_route_avoids_high_sec() {
+        let systems = &[(1, 0.9), (2, 0.3), (3, 0.2), (4, 0.8), (5, 0.1)];
+        let jumps = &[(1, 2), (2, 3), (3, 5), (1, 4), (4, 5)];
+        let route = preferred_route(systems, jumps, 1, 5, RoutePreference::LessSecure);
+        assert_eq!(route, vec![1, 2, 3, 5]);
+    }
 
-        let stargates: Vec<_> = stargates_fut.map(Result::unwrap).collect().await;
+    #[test]
+    fn security_classes_split_at_boundaries() {
+        assert_eq!(SecurityClass::classify(30000142, 0.45), SecurityClass::High);
+        assert_eq!(SecurityClass::classify(30000142, 0.449), SecurityClass::Low);
+        assert_eq!(SecurityClass::classify(30000142, 0.01), SecurityClass::Low);
+        assert_eq!(SecurityClass::classify(30000142, 0.0), SecurityClass::Null);
+        assert_eq!(SecurityClass::classify(30000142, -0.5), SecurityClass::Null);
+        assert_eq!(
+            SecurityClass::classify(31000005, -0.99),
+            SecurityClass::Wormhole
+        );
+    }
 
-        for stargate in stargates {
-            let node_id = galaxy.graph.add_node(Node::Stargate {
-                stargate: stargate.stargate_id,
-                source: stargate.system_id,
-                destination: stargate.destination.system_id,
+    #[test]
+    fn failed_sov_fetches_keep_previous_standings() {
+        use crate::mock_http::{mock_server, mock_server_with, Reply};
+
+        for failing in [
+            None,
+            Some("/sovereignty/map/"),
+            Some("/alliances/99/contacts/"),
+        ] {
+            let esi = mock_server_with(move |path| {
+                Some(if failing.is_some_and(|f| path.starts_with(f)) {
+                    Reply::Respond(404, "", "{}")
+                } else if path.contains("/contacts/") {
+                    Reply::Respond(
+                        200,
+                        "",
+                        r#"[{"contact_id":100,"contact_type":"alliance","standing":-10.0}]"#,
+                    )
+                } else if path.starts_with("/characters/1/") {
+                    Reply::Respond(
+                        200,
+                        "",
+                        r#"{"alliance_id":99,"birthday":"","bloodline_id":1,"corporation_id":98,"gender":"","name":"Test","race_id":1}"#,
+                    )
+                } else if path.starts_with("/sovereignty/map/") {
+                    Reply::Respond(200, "", r#"[{"system_id":1,"alliance_id":100}]"#)
+                } else {
+                    Reply::Respond(404, "", "{}")
+                })
             });
-            all_stargates.insert(stargate.stargate_id, node_id);
-            galaxy.stargates.insert(stargate.stargate_id, stargate);
-        }
+            let token = mock_server(Vec::new());
+            let client = esi::Client::mock(&esi, &token, 1, esi::DEFAULT_TIMEOUT);
 
-        for system in galaxy.systems.values() {
-            let system_node = all_systems.get(&system.system_id).unwrap();
-            let system_position: math::V3<f64> =
-                math::V3::new(system.position.x, system.position.y, system.position.z);
+            let previous = Sov {
+                alliance_id: Some(99),
+                corporation_id: None,
+                standing: 10.0,
+            };
+            let sov_standings = Arc::new(RwLock::new([(2, previous)].iter().cloned().collect()));
+            block_on(World::load_sov_standings(
+                &sov_standings,
+                &Default::default(),
+                &Default::default(),
+                &Default::default(),
+                &client,
+            ));
+
+            let standings: Vec<_> = sov_standings
+                .read()
+                .unwrap()
+                .iter()
+                .map(|(system, sov)| (*system, sov.standing))
+                .collect();
+            match failing {
+                None => assert_eq!(standings, vec![(1, -10.0)]),
+                Some(_) => assert_eq!(standings, vec![(2, 10.0)]),
+            }
+        }
+    }
 
-            if let Some(system_stargates) = &system.stargates {
-                for stargate_id in system_stargates {
-                    let stargate = galaxy.stargates.get(stargate_id).unwrap();
-                    let stargate_node = all_stargates.get(&stargate_id).unwrap();
-                    let stargate_position: math::V3<f64> = math::V3::new(
-                        stargate.position.x,
-                        stargate.position.y,
-                        stargate.position.z,
-                    );
+    #[test]
+    fn sov_holders_without_standings_are_neutral() {
+        use crate::mock_http::{mock_server, mock_server_with, Reply};
+
+        // the holder has no contact standing and its alliance lookup fails
+        let esi = mock_server_with(|path| {
+            Some(if path.contains("/contacts/") {
+                Reply::Respond(
+                    200,
+                    "",
+                    r#"[{"contact_id":100,"contact_type":"alliance","standing":-10.0}]"#,
+                )
+            } else if path.starts_with("/characters/1/") {
+                Reply::Respond(
+                    200,
+                    "",
+                    r#"{"alliance_id":99,"birthday":"","bloodline_id":1,"corporation_id":98,"gender":"","name":"Test","race_id":1}"#,
+                )
+            } else if path.starts_with("/sovereignty/map/") {
+                Reply::Respond(200, "", r#"[{"system_id":1,"alliance_id":200}]"#)
+            } else {
+                Reply::Respond(404, "", "{}")
+            })
+        });
+        let token = mock_server(Vec::new());
+        let client = esi::Client::mock(&esi, &token, 1, esi::DEFAULT_TIMEOUT);
+
+        let sov_standings = Arc::new(RwLock::new(HashMap::new()));
+        let alliances = Arc::new(RwLock::new(HashMap::new()));
+        block_on(World::load_sov_standings(
+            &sov_standings,
+            &Default::default(),
+            &alliances,
+            &Default::default(),
+            &client,
+        ));
+
+        let sov = sov_standings.read().unwrap()[&1];
+        assert_eq!(sov.alliance_id, Some(200));
+        assert_eq!(sov.standing, 0.0);
+        assert!(alliances.read().unwrap().is_empty());
+    }
 
-                    let edge = Edge::Warp {
-                        system: system.system_id,
-                        distance: system_position.distance(&stargate_position) / 1e12,
-                    };
+    #[test]
+    fn offline_id_loads_are_not_retried() {
+        let result = block_on(load_ids("systems", || async { Err(esi::Error::Offline) }));
+        assert_eq!(
+            result,
+            Err("No cached systems available offline".to_string())
+        );
+    }
 
-                    galaxy
-                        .graph
-                        .add_edge(system_node.clone(), stargate_node.clone(), edge);
+    #[test]
+    fn missing_stargates_are_skipped() {
+        let system = esi::GetUniverseSystem {
+            system_id: 1,
+            name: "System 1".to_string(),
+            position: esi::Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            security_status: 1.0,
+            constellation_id: 100,
+            stargates: Some(vec![101, 102]),
+        };
+        let galaxy = GalaxyGraph::build(Vec::new(), Vec::new(), vec![system], Vec::new());
+        assert!(galaxy.system(1).is_some());
+        assert!(galaxy.jumps().is_empty());
+    }
 
-                    for stargate_id_inner in system_stargates {
-                        if stargate_id >= stargate_id_inner {
-                            continue;
-                        }
+    #[test]
+    fn nearest_hub_by_jumps() {
+        let galaxy = test_galaxy(
+            &[
+                (1, 100, 1.0),
+                (2, 100, 1.0),
+                (3, 100, 1.0),
+                (4, 100, 1.0),
+                (5, 100, 1.0),
+                (6, 100, 1.0),
+            ],
+            &[(1, 2), (2, 3), (3, 4), (4, 5)],
+        );
+        let hubs = [5, 1];
 
-                        let stargate_inner_node = all_stargates.get(&stargate_id_inner).unwrap();
-                        let stargate_inner = galaxy.stargates.get(stargate_id_inner).unwrap();
-                        let stargate_inner_position: math::V3<f64> = math::V3::new(
-                            stargate_inner.position.x,
-                            stargate_inner.position.y,
-                            stargate_inner.position.z,
-                        );
+        assert_eq!(galaxy.nearest_system(2, &hubs), Some((1, 1)));
+        assert_eq!(galaxy.nearest_system(4, &hubs), Some((5, 1)));
+        assert_eq!(galaxy.nearest_system(5, &hubs), Some((5, 0)));
+        assert_eq!(galaxy.nearest_system(6, &hubs), None);
+        assert_eq!(galaxy.nearest_system(3, &[7]), None);
+    }
 
-                        let edge = Edge::Warp {
-                            system: system.system_id,
-                            distance: stargate_position.distance(&stargate_inner_position) / 1e12,
-                        };
+    #[test]
+    fn known_space_excludes_jove_and_j_space() {
+        assert!(is_known_space_region(10000002));
+        assert!(is_known_space_region(10000070));
+        assert!(!is_known_space_region(10000004));
+        assert!(!is_known_space_region(11000001));
+        assert!(!is_known_space_region(12000001));
+
+        let galaxy = test_galaxy(&[(1, 100, 1.0), (2, 110, -1.0)], &[(1, 2)]);
+        assert!(galaxy.is_known_space(1));
+        assert!(!galaxy.is_known_space(2));
+        assert!(!galaxy.is_known_space(3));
+    }
 
-                        galaxy.graph.add_edge(
-                            stargate_node.clone(),
-                            stargate_inner_node.clone(),
-                            edge,
-                        );
-                    }
+    #[test]
+    fn avoided_chokepoint_forces_detour() {
+        let avoided = [2].iter().cloned().collect();
+        let route = search(
+            DETOUR_SYSTEMS,
+            DETOUR_JUMPS,
+            1,
+            5,
+            RoutePreference::Shortest,
+            &avoided,
+        );
+        assert_eq!(route, Some(vec![1, 3, 4, 5]));
+    }
 
-                    if stargate.system_id >= stargate.destination.system_id {
-                        continue;
-                    }
+    #[test]
+    fn fully_avoided_destination_has_no_route() {
+        let avoided = [2, 4].iter().cloned().collect();
+        let route = search(
+            DETOUR_SYSTEMS,
+            DETOUR_JUMPS,
+            1,
+            5,
+            RoutePreference::Shortest,
+            &avoided,
+        );
+        assert_eq!(route, None);
+    }
 
-                    let destination_node = all_stargates.get(&stargate.destination.stargate_id);
+    #[test]
+    fn unreachable_pair_has_no_route() {
+        // 1-2 and 3-4 are disconnected islands
+        let systems = &[(1, 1.0), (2, 1.0), (3, 1.0), (4, 1.0)];
+        let jumps = &[(1, 2), (3, 4)];
+        let route = search(
+            systems,
+            jumps,
+            1,
+            4,
+            RoutePreference::Shortest,
+            &HashSet::new(),
+        );
+        assert_eq!(route, None);
+    }
 
-                    if let Some(destination_node) = destination_node {
-                        let edge = Edge::Jump {
-                            left: stargate.system_id,
-                            right: stargate.destination.system_id,
-                        };
+    fn test_galaxy(systems: &[(i32, i32, f64)], gates: &[(i32, i32)]) -> GalaxyGraph {
+        let position = |id: i32| esi::Position {
+            x: id as f64 * 1e12,
+            y: 0.0,
+            z: 0.0,
+        };
 
-                        galaxy.graph.add_edge(
-                            stargate_node.clone(),
-                            destination_node.clone(),
-                            edge,
-                        );
-                    }
-                }
+        let mut stargates = Vec::new();
+        for &(left, right) in gates {
+            for &(source, destination) in &[(left, right), (right, left)] {
+                stargates.push(esi::GetUniverseStargate {
+                    stargate_id: source * 100 + destination,
+                    name: format!("Stargate ({})", destination),
+                    position: position(source),
+                    destination: esi::GetUniverseStargateDestination {
+                        stargate_id: destination * 100 + source,
+                        system_id: destination,
+                    },
+                    system_id: source,
+                });
             }
         }
 
-        if file_exists("bridges.tsv") {
-            let bridges = read_file("bridges.tsv").await.unwrap();
-            let bridges_tsv = String::from_utf8(bridges).unwrap();
+        let systems: Vec<_> = systems
+            .iter()
+            .map(
+                |&(system_id, constellation_id, security_status)| esi::GetUniverseSystem {
+                    system_id,
+                    name: format!("System {}", system_id),
+                    position: position(system_id),
+                    security_status,
+                    constellation_id,
+                    stargates: Some(
+                        stargates
+                            .iter()
+                            .filter(|g| g.system_id == system_id)
+                            .map(|g| g.stargate_id)
+                            .collect(),
+                    ),
+                },
+            )
+            .collect();
 
-            let mut jb_id = 0;
-            for line in bridges_tsv.lines() {
-                let line_parts: Vec<_> = line.split('\t').collect();
-                let left = line_parts[1].split(' ').next().unwrap();
-                let right = line_parts[2].split(' ').next().unwrap();
+        let constellations = vec![(100, 10000002), (101, 10000002), (110, 10000004)]
+            .into_iter()
+            .map(
+                |(constellation_id, region_id)| esi::GetUniverseConstellation {
+                    constellation_id,
+                    name: format!("Constellation {}", constellation_id),
+                    position: position(0),
+                    region_id,
+                    systems: None,
+                },
+            )
+            .collect();
 
-                let left = galaxy
-                    .systems_by_name
-                    .get(left)
-                    .and_then(|id| galaxy.systems.get(id))
-                    .cloned()
-                    .unwrap();
-                let right = galaxy
-                    .systems_by_name
-                    .get(right)
-                    .and_then(|id| galaxy.systems.get(id))
-                    .cloned()
-                    .unwrap();
+        let regions = vec![10000002, 10000004]
+            .into_iter()
+            .map(|region_id| esi::GetUniverseRegion {
+                region_id,
+                name: format!("Region {}", region_id),
+                description: None,
+                constellations: None,
+            })
+            .collect();
 
-                let left_jb_id = jb_id;
-                let right_jb_id = jb_id + 1;
-                jb_id += 2;
-                let left_jb = esi::GetUniverseStargate {
-                    stargate_id: left_jb_id,
-                    name: format!("{} » {}", left.name, right.name),
-                    destination: esi::GetUniverseStargateDestination {
-                        stargate_id: right_jb_id,
-                        system_id: right.system_id,
-                    },
-                    position: esi::Position {
-                        x: left.position.x,
-                        y: left.position.y,
-                        z: left.position.z,
-                    },
-                    system_id: left.system_id,
-                };
+        GalaxyGraph::build(regions, constellations, systems, stargates)
+    }
 
-                let right_jb = esi::GetUniverseStargate {
-                    stargate_id: right_jb_id,
-                    name: format!("{} » {}", right.name, left.name),
-                    destination: esi::GetUniverseStargateDestination {
-                        stargate_id: left_jb_id,
-                        system_id: left.system_id,
-                    },
-                    position: esi::Position {
-                        x: right.position.x,
-                        y: right.position.y,
-                        z: right.position.z,
-                    },
-                    system_id: right.system_id,
-                };
+    #[test]
+    fn excluded_jumps_are_not_routed() {
+        let mut galaxy = test_galaxy(
+            &[(1, 100, 1.0), (2, 100, 1.0), (3, 101, 1.0)],
+            &[(1, 2), (2, 3)],
+        );
+        let (one, three) = (galaxy.systems[&1].clone(), galaxy.systems[&3].clone());
+        galaxy.add_virtual_gates(0, &one, &three, false);
 
-                galaxy.stargates.insert(left_jb_id, left_jb);
-                let left_node = Node::JumpGate {
-                    stargate: left_jb_id,
-                    source: left.system_id,
-                    destination: right.system_id,
-                };
-                let left_node_id = galaxy.graph.add_node(left_node);
-                all_stargates.insert(left_jb_id, left_node_id);
-                let left_system_node = all_systems.get(&left.system_id).unwrap();
-
-                galaxy.stargates.insert(right_jb_id, right_jb);
-                let right_node = Node::JumpGate {
-                    stargate: right_jb_id,
-                    source: right.system_id,
-                    destination: left.system_id,
-                };
-                let right_node_id = galaxy.graph.add_node(right_node);
-                all_stargates.insert(right_jb_id, right_node_id);
-                let right_system_node = all_systems.get(&right.system_id).unwrap();
+        let systems = |galaxy: &GalaxyGraph| -> Vec<i32> {
+            let route = galaxy.route(1, 3, RoutePreference::Shortest).unwrap();
+            route.iter().map(|n| n.system_id).collect()
+        };
+        assert_eq!(systems(&galaxy), vec![1, 3]);
+        assert_eq!(galaxy.distances_from(1).get(&3), Some(&1));
+
+        galaxy.set_excluded_jumps([JumpType::JumpGate].iter().cloned().collect());
+        assert_eq!(systems(&galaxy), vec![1, 2, 3]);
+        assert_eq!(galaxy.distances_from(1).get(&3), Some(&2));
+
+        let excluded = [JumpType::System, JumpType::JumpGate];
+        galaxy.set_excluded_jumps(excluded.iter().cloned().collect());
+        assert_eq!(
+            galaxy.route(2, 1, RoutePreference::Shortest).unwrap_err(),
+            RouteError::NoRoute(2, 1)
+        );
+    }
 
-                let left_warp = Edge::Warp {
-                    system: left.system_id,
-                    distance: 1.0,
-                };
+    #[test]
+    fn bridges_shorten_routes() {
+        let mut galaxy = test_galaxy(
+            &[
+                (1, 100, 1.0),
+                (2, 100, 1.0),
+                (3, 100, 1.0),
+                (4, 100, 1.0),
+                (5, 100, 1.0),
+            ],
+            &[(1, 2), (2, 3), (3, 4), (4, 5)],
+        );
+        let (one, two, five) = (
+            galaxy.systems[&1].clone(),
+            galaxy.systems[&2].clone(),
+            galaxy.systems[&5].clone(),
+        );
+        galaxy.add_virtual_gates(0, &one, &five, false);
+        galaxy.add_virtual_gates(2, &one, &two, false);
 
-                let right_warp = Edge::Warp {
-                    system: right.system_id,
-                    distance: 1.0,
-                };
+        let route = galaxy.route(1, 5, RoutePreference::Shortest).unwrap();
+        let systems: Vec<_> = route.iter().map(|n| n.system_id).collect();
+        assert_eq!(systems, vec![1, 5]);
+        assert_eq!(route[1].arrive_jump, Some(JumpType::JumpGate));
+        assert_eq!(galaxy.distances_from(1).get(&5), Some(&1));
 
-                let edge = Edge::JumpBridge {
-                    left: left.system_id,
-                    right: right.system_id,
-                };
+        let route = galaxy.route(1, 2, RoutePreference::Shortest).unwrap();
+        assert_eq!(route[1].arrive_jump, Some(JumpType::System));
+    }
 
-                galaxy
-                    .graph
-                    .add_edge(left_node_id.clone(), left_system_node.clone(), left_warp);
-                galaxy
-                    .graph
-                    .add_edge(right_node_id.clone(), right_system_node.clone(), right_warp);
-                galaxy
-                    .graph
-                    .add_edge(left_node_id.clone(), right_node_id.clone(), edge);
+    #[test]
+    fn tours_visit_grid_systems() {
+        // 3x3 grid, system id = row * 3 + column + 1
+        let systems: Vec<_> = (1..=9).map(|id| (id, 100, 1.0)).collect();
+        let mut gates = Vec::new();
+        for row in 0..3 {
+            for column in 0..3 {
+                let id = row * 3 + column + 1;
+                if column < 2 {
+                    gates.push((id, id + 1));
+                }
+                if row < 2 {
+                    gates.push((id, id + 3));
+                }
             }
         }
+        let galaxy = test_galaxy(&systems, &gates);
 
-        log::info!("galaxy loaded");
+        let corners = [9, 1, 7, 3];
+        let tour = galaxy.tour_order(&corners).unwrap();
+        assert_eq!(tour[0], 9);
+        let mut visited = tour.clone();
+        visited.sort_unstable();
+        assert_eq!(visited, vec![1, 3, 7, 9]);
 
-        galaxy
+        let length: u32 = tour
+            .iter()
+            .zip(tour.iter().cycle().skip(1))
+            .map(|(from, to)| galaxy.distances_from(*from)[to])
+            .sum();
+        assert_eq!(length, 8);
+
+        let distances = vec![
+            vec![0, 1, 5, 1],
+            vec![1, 0, 1, 5],
+            vec![5, 1, 0, 1],
+            vec![1, 5, 1, 0],
+        ];
+        let tour = shortest_tour(&distances);
+        let length: u32 = tour
+            .iter()
+            .zip(tour.iter().cycle().skip(1))
+            .map(|(from, to)| distances[*from][*to])
+            .sum();
+        assert_eq!(length, 4);
+
+        assert_eq!(galaxy.tour_order(&[1, 10]), Err(RouteError::NoRoute(1, 10)));
+    }
+
+    #[test]
+    fn restricted_bridges_fall_back_to_gates() {
+        let mut galaxy = test_galaxy(
+            &[(1, 100, 1.0), (2, 100, 1.0), (3, 101, 1.0)],
+            &[(1, 2), (2, 3)],
+        );
+        let (one, three) = (galaxy.systems[&1].clone(), galaxy.systems[&3].clone());
+        galaxy.add_virtual_gates(0, &one, &three, false);
+        galaxy.restrict_bridge(3, 1, parse_access_ids("99000001, 98000001"));
+
+        let systems = |galaxy: &GalaxyGraph| -> Vec<i32> {
+            let route = galaxy.route(1, 3, RoutePreference::Shortest).unwrap();
+            route.iter().map(|n| n.system_id).collect()
+        };
+        assert_eq!(systems(&galaxy), vec![1, 3]);
+
+        assert!(galaxy.set_access_ids(vec![99000002, 98000002]));
+        assert_eq!(systems(&galaxy), vec![1, 2, 3]);
+        assert_eq!(galaxy.distances_from(1).get(&3), Some(&2));
+        assert!(galaxy.jumps().iter().any(|j| j.restricted));
+
+        assert!(galaxy.set_access_ids(vec![99000002, 98000001]));
+        assert!(!galaxy.set_access_ids(vec![99000002, 98000001]));
+        assert_eq!(systems(&galaxy), vec![1, 3]);
+        assert!(galaxy.jumps().iter().all(|j| !j.restricted));
+    }
+
+    #[test]
+    fn sov_snapshot_round_trips() {
+        let snapshot = SovSnapshot {
+            sov: vec![(
+                30000142,
+                Sov {
+                    alliance_id: Some(99000001),
+                    corporation_id: Some(98000001),
+                    standing: -10.0,
+                },
+            )],
+            affiliation: Some(Affiliation {
+                alliance_id: Some(99000002),
+                corporation_id: Some(98000002),
+            }),
+            alliances: vec![esi::GetAlliance {
+                alliance_id: 99000001,
+                name: String::from("Test Alliance"),
+                ticker: String::from("TEST"),
+            }],
+            corporations: Vec::new(),
+        };
+
+        let bytes = flexbuffers::to_vec(&snapshot).unwrap();
+        let restored: SovSnapshot = flexbuffers::from_slice(&bytes).unwrap();
+        assert_eq!(restored.sov, snapshot.sov);
+        assert_eq!(restored.affiliation, snapshot.affiliation);
+        assert_eq!(restored.alliances[0].ticker, "TEST");
+    }
+
+    #[test]
+    fn waypoint_lists_report_unknown_names() {
+        let galaxy = test_galaxy(&[(1, 100, 1.0), (2, 100, 1.0), (3, 101, 1.0)], &[]);
+        let resolve = |name: &str| galaxy.match_system(name).into_iter().next();
+
+        let (waypoints, unresolved) =
+            World::split_waypoints("System 1, Nowhere,,  system 3 ,Jita", resolve);
+        assert_eq!(waypoints, vec![1, 3]);
+        assert_eq!(unresolved, vec!["Nowhere".to_string(), "Jita".to_string()]);
+
+        let (waypoints, unresolved) = World::split_waypoints(" , ", resolve);
+        assert!(waypoints.is_empty());
+        assert!(unresolved.is_empty());
+    }
+
+    #[test]
+    fn waypoint_route_chains_legs() {
+        // 1-5 is a shortcut that the waypoint at 3 forces the route around
+        let galaxy = test_galaxy(
+            &[
+                (1, 100, 1.0),
+                (2, 100, 1.0),
+                (3, 101, 1.0),
+                (4, 110, 1.0),
+                (5, 110, 1.0),
+            ],
+            &[(1, 2), (2, 3), (3, 4), (4, 5), (1, 5)],
+        );
+
+        let route = galaxy
+            .find_waypoint_route(
+                &[1, 3, 5],
+                RoutePreference::Shortest,
+                &HashSet::new(),
+                |_| 0.0,
+            )
+            .unwrap();
+        let systems: Vec<_> = route.iter().map(|n| n.system_id).collect();
+        assert_eq!(systems, vec![1, 2, 3, 4, 5]);
+        assert_eq!(route[2].arrive_jump, Some(JumpType::Constellation));
+        assert_eq!(route[2].leave_jump, Some(JumpType::Region));
+    }
+
+    #[test]
+    fn home_sov_matches_alliance_or_corporation() {
+        let sov: HashMap<i32, Sov> = vec![
+            (1, Some(99), Some(98)),
+            (2, Some(77), Some(76)),
+            (3, None, Some(55)),
+        ]
+        .into_iter()
+        .map(|(system_id, alliance_id, corporation_id)| {
+            let sov = Sov {
+                alliance_id,
+                corporation_id,
+                standing: 0.0,
+            };
+            (system_id, sov)
+        })
+        .collect();
+
+        let alliance = Affiliation {
+            alliance_id: Some(99),
+            corporation_id: Some(12),
+        };
+        assert!(World::holds_sov(Some(&alliance), &sov, 1));
+        assert!(!World::holds_sov(Some(&alliance), &sov, 2));
+        assert!(!World::holds_sov(Some(&alliance), &sov, 4));
+        assert!(!World::holds_sov(None, &sov, 1));
+
+        let corporation = Affiliation {
+            alliance_id: None,
+            corporation_id: Some(55),
+        };
+        assert!(World::holds_sov(Some(&corporation), &sov, 3));
+        assert!(!World::holds_sov(Some(&corporation), &sov, 1));
+    }
+
+    #[test]
+    fn route_text_lists_each_system() {
+        let galaxy = test_galaxy(&[(1, 100, 0.946), (2, 101, 0.2)], &[(1, 2)]);
+        let route = galaxy.route(1, 2, RoutePreference::Shortest).unwrap();
+
+        assert_eq!(
+            galaxy.route_text(&route),
+            "1 jump\nSystem 1 (0.95) » System 2 [GATE]\nSystem 2 (0.20)\n"
+        );
+        assert_eq!(galaxy.route_text(&[]), "");
+    }
+
+    #[test]
+    fn distance_cache_evicts_least_recent() {
+        let mut cache = DistanceCache::new(2);
+        let distances = |d| Arc::new([(0, d)].iter().cloned().collect());
+        cache.insert(1, distances(1));
+        cache.insert(2, distances(2));
+        assert!(cache.get(1).is_some());
+
+        cache.insert(3, distances(3));
+        assert!(cache.get(2).is_none());
+        assert_eq!(cache.get(1).map(|d| d[&0]), Some(1));
+        assert_eq!(cache.get(3).map(|d| d[&0]), Some(3));
+    }
+
+    #[test]
+    fn repeated_distance_lookups_are_cached() {
+        let galaxy = test_galaxy(&[(1, 100, 1.0), (2, 100, 1.0)], &[(1, 2)]);
+
+        let first = galaxy.distances_from(1);
+        assert!(Arc::ptr_eq(&first, &galaxy.distances_from(1)));
+        assert!(!Arc::ptr_eq(&first, &galaxy.distances_from(2)));
     }
 }
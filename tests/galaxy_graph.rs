@@ -0,0 +1,118 @@
+use eve_mapper::{
+    GalaxyGraph, GetUniverseConstellation, GetUniverseRegion, GetUniverseStargate,
+    GetUniverseStargateDestination, GetUniverseSystem, JumpType, Position, RouteError,
+    RoutePreference,
+};
+
+fn position(id: i32) -> Position {
+    Position {
+        x: id as f64 * 1e12,
+        y: 0.0,
+        z: 0.0,
+    }
+}
+
+// Systems are (system_id, constellation_id, security_status), gates join two systems both ways
+fn build_galaxy(systems: &[(i32, i32, f64)], gates: &[(i32, i32)]) -> GalaxyGraph {
+    let mut stargates = Vec::new();
+    for &(left, right) in gates {
+        for &(source, destination) in &[(left, right), (right, left)] {
+            stargates.push(GetUniverseStargate {
+                stargate_id: source * 100 + destination,
+                name: format!("Stargate ({})", destination),
+                position: position(source),
+                destination: GetUniverseStargateDestination {
+                    stargate_id: destination * 100 + source,
+                    system_id: destination,
+                },
+                system_id: source,
+            });
+        }
+    }
+
+    let systems = systems
+        .iter()
+        .map(
+            |&(system_id, constellation_id, security_status)| GetUniverseSystem {
+                system_id,
+                name: format!("System {}", system_id),
+                position: position(system_id),
+                security_status,
+                constellation_id,
+                stargates: Some(
+                    stargates
+                        .iter()
+                        .filter(|g| g.system_id == system_id)
+                        .map(|g| g.stargate_id)
+                        .collect(),
+                ),
+            },
+        )
+        .collect();
+
+    let constellations = vec![(100, 10000002), (101, 10000002), (110, 10000004)]
+        .into_iter()
+        .map(|(constellation_id, region_id)| GetUniverseConstellation {
+            constellation_id,
+            name: format!("Constellation {}", constellation_id),
+            position: position(0),
+            region_id,
+            systems: None,
+        })
+        .collect();
+
+    let regions = vec![10000002, 10000004]
+        .into_iter()
+        .map(|region_id| GetUniverseRegion {
+            region_id,
+            name: format!("Region {}", region_id),
+            description: None,
+            constellations: None,
+        })
+        .collect();
+
+    GalaxyGraph::build(regions, constellations, systems, stargates)
+}
+
+#[test]
+fn galaxy_graph_routes_through_stargates() {
+    // 1-2-3-4 crosses a constellation and a region border, 5 is a low sec shortcut
+    let galaxy = build_galaxy(
+        &[
+            (1, 100, 1.0),
+            (2, 100, 1.0),
+            (3, 101, 1.0),
+            (4, 110, 1.0),
+            (5, 100, 0.1),
+        ],
+        &[(1, 2), (2, 3), (3, 4), (1, 5), (5, 4)],
+    );
+
+    let route = galaxy.route(1, 4, RoutePreference::Safer).unwrap();
+    let systems: Vec<_> = route.iter().map(|n| n.system_id).collect();
+    assert_eq!(systems, vec![1, 2, 3, 4]);
+
+    let arrivals: Vec<_> = route.iter().map(|n| n.arrive_jump).collect();
+    assert_eq!(
+        arrivals,
+        vec![
+            None,
+            Some(JumpType::System),
+            Some(JumpType::Constellation),
+            Some(JumpType::Region)
+        ]
+    );
+    assert_eq!(route.last().unwrap().leave_jump, None);
+
+    let route = galaxy.route(1, 4, RoutePreference::Shortest).unwrap();
+    let systems: Vec<_> = route.iter().map(|n| n.system_id).collect();
+    assert_eq!(systems, vec![1, 5, 4]);
+
+    assert_eq!(galaxy.distances_from(1).get(&3), Some(&2));
+    assert_eq!(galaxy.match_system("system 5"), vec![5]);
+    assert_eq!(galaxy.jumps().len(), 5);
+    assert_eq!(
+        galaxy.route(1, 9, RoutePreference::Shortest).unwrap_err(),
+        RouteError::UnknownSystem(9)
+    );
+}